@@ -0,0 +1,100 @@
+//! Python bindings for the xmas language, built with pyo3.
+//!
+//! ```python
+//! import xmas_py
+//! print(xmas_py.run("_ = 6 * 7"))               # "42"
+//!
+//! interp = xmas_py.Interpreter()
+//! interp.set_var("steps", 10)
+//! interp.run("_ = steps * 2")
+//! print(interp.get_var("_"))                    # "20"
+//! ```
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+use xmas_core::interpreter::Value;
+use xmas_core::{lexer, parser, run_source};
+
+fn value_to_py(py: Python<'_>, value: &Value) -> PyObject {
+    match value {
+        Value::Number(n) => n.to_object(py),
+        Value::Str(s) => s.to_object(py),
+        Value::Bool(b) => b.to_object(py),
+        Value::Array1D(items) => items
+            .iter()
+            .map(|v| value_to_py(py, v))
+            .collect::<Vec<_>>()
+            .to_object(py),
+        other => other.to_string().to_object(py),
+    }
+}
+
+fn py_to_value(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if let Ok(b) = obj.extract::<bool>() {
+        Ok(Value::Bool(b))
+    } else if let Ok(n) = obj.extract::<i64>() {
+        Ok(Value::Number(n))
+    } else if let Ok(s) = obj.extract::<String>() {
+        Ok(Value::Str(s))
+    } else if let Ok(items) = obj.extract::<Vec<Bound<'_, PyAny>>>() {
+        Ok(Value::Array1D(
+            items.iter().map(py_to_value).collect::<PyResult<_>>()?,
+        ))
+    } else {
+        Err(PyRuntimeError::new_err("unsupported value type"))
+    }
+}
+
+/// Runs a program, returning the value of `_` (or None).
+#[pyfunction]
+#[pyo3(signature = (source, input=None))]
+fn run(py: Python<'_>, source: &str, input: Option<&str>) -> PyResult<Option<PyObject>> {
+    match run_source(source, input) {
+        Ok(result) => Ok(result.map(|v| value_to_py(py, &v))),
+        Err(message) => Err(PyRuntimeError::new_err(message)),
+    }
+}
+
+/// A persistent interpreter with variable access between runs.
+#[pyclass]
+struct Interpreter {
+    inner: xmas_core::interpreter::Interpreter,
+}
+
+#[pymethods]
+impl Interpreter {
+    #[new]
+    fn new() -> Self {
+        Interpreter {
+            inner: xmas_core::interpreter::Interpreter::new(),
+        }
+    }
+
+    fn set_input(&mut self, input: &str) {
+        self.inner.set_input(input.to_string());
+    }
+
+    fn set_var(&mut self, name: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.inner.set_global(name, py_to_value(value)?);
+        Ok(())
+    }
+
+    fn get_var(&self, py: Python<'_>, name: &str) -> Option<PyObject> {
+        self.inner.get_var(name).map(|v| value_to_py(py, v))
+    }
+
+    fn run(&mut self, source: &str) -> PyResult<()> {
+        let program = lexer::lex(source)
+            .and_then(|tokens| parser::parse(tokens, source))
+            .map_err(PyRuntimeError::new_err)?;
+        self.inner.run(&program).map_err(PyRuntimeError::new_err)
+    }
+}
+
+#[pymodule]
+fn xmas_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_class::<Interpreter>()?;
+    Ok(())
+}
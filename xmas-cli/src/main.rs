@@ -0,0 +1,523 @@
+//! Command-line runner for xmas programs.
+
+use std::process::ExitCode;
+
+mod report;
+mod verify;
+
+use xmas_core::interpreter::{Interpreter, Value};
+use xmas_core::{lexer, parser};
+
+const USAGE: &str = "\
+usage: xmas [run] <program.xmas> [more.xmas ...] [options]
+       xmas run --day <n> [--part <n>] [--year <n>] [--root <dir>] [options]
+       xmas verify <day-dir>
+
+Multiple program files are executed in order against the same interpreter
+state, so earlier files can define functions and variables for later ones.
+
+`xmas verify` runs each partN.xmas in the directory against sample.txt,
+checking its `// sample: <value>` annotation, and only then against
+input.txt.
+
+With --day, the program and input resolve to the conventional layout:
+<root>/aoc-<year>/day-NN/partP.xmas and input.txt alongside it. The year
+defaults to the most recent Advent of Code (the current year in December,
+the previous year otherwise) and the part to 1.
+
+options:
+  -i, --input <file>   puzzle input file, available as `input`
+      --day <n>        run the solution for day n from the standard layout
+      --part <n>       which part of the day to run (default: 1)
+      --year <n>       which year's event (default: the most recent)
+      --root <dir>     root of the aoc-<year> directories (default: .)
+      --var <name=value>
+                       set a global before execution; the value may be a
+                       number, string, true/false, or [v, v, ...] array
+                       (repeatable)
+  -d, --debug          print every executed statement to stderr
+      --debug-max-events <n>
+                       stop printing debug lines after n events
+      --checkpoint <file>
+                       periodically save global variables to the file, and
+                       resume from it when it already exists
+      --checkpoint-every <n>
+                       statements between checkpoints (default: 100000)
+      --animate        let animate() clear the terminal and draw grid frames
+      --animate-delay <ms>
+                       pause between animation frames (default: 50)
+      --trace <file>   record executed statements and values to a file
+      --profile        print a per-function timing report to stderr
+      --heatmap        print the source annotated with per-line execution
+                       counts to stderr
+      --profile-out <file>
+                       write folded stack samples (inferno/speedscope format)
+      --max-steps <n>  abort after executing n statements (default: unlimited)
+      --max-depth <n>  error when function calls nest deeper than n
+                       (default: 200)
+      --max-memory <bytes>
+                       abort when variables exceed roughly this many bytes
+                       (default: unlimited)
+  -h, --help           show this help";
+
+struct Options {
+    programs: Vec<String>,
+    input: Option<String>,
+    day: Option<u32>,
+    part: Option<u32>,
+    year: Option<i64>,
+    root: Option<String>,
+    debug: bool,
+    debug_max_events: Option<u64>,
+    profile: bool,
+    heatmap: bool,
+    trace: Option<String>,
+    vars: Vec<(String, Value)>,
+    max_steps: Option<u64>,
+    max_depth: Option<usize>,
+    max_memory: Option<usize>,
+    profile_out: Option<String>,
+    checkpoint: Option<String>,
+    checkpoint_every: u64,
+    animate: bool,
+    animate_delay_ms: u64,
+}
+
+/// Parses the value half of a `--var name=value` argument: a number, a
+/// (optionally quoted) string, a bool, or a flat `[v, v, ...]` array.
+fn parse_var_value(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    if let Ok(n) = text.parse::<i64>() {
+        return Ok(Value::Number(n));
+    }
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or_else(|| format!("unterminated array in --var value: {text}"))?;
+        let mut items = Vec::new();
+        if !inner.trim().is_empty() {
+            for item in inner.split(',') {
+                match parse_var_value(item)? {
+                    Value::Array1D(_) => {
+                        return Err(format!("nested arrays are not supported in --var: {text}"))
+                    }
+                    value => items.push(value),
+                }
+            }
+        }
+        return Ok(Value::Array1D(items));
+    }
+    let text = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text);
+    Ok(Value::Str(text.to_string()))
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options {
+        programs: Vec::new(),
+        input: None,
+        day: None,
+        part: None,
+        year: None,
+        root: None,
+        debug: false,
+        debug_max_events: None,
+        profile: false,
+        heatmap: false,
+        trace: None,
+        vars: Vec::new(),
+        max_steps: None,
+        max_depth: None,
+        max_memory: None,
+        profile_out: None,
+        checkpoint: None,
+        checkpoint_every: 100_000,
+        animate: false,
+        animate_delay_ms: 50,
+    };
+
+    fn numeric_arg<T: std::str::FromStr>(
+        flag: &str,
+        value: Option<&String>,
+    ) -> Result<T, String> {
+        let value = value.ok_or_else(|| format!("{flag} requires a number argument"))?;
+        value
+            .parse()
+            .map_err(|_| format!("{flag} expects a number, got: {value}"))
+    }
+    // `xmas run ...` is the same as `xmas ...`; the word exists so that
+    // day-based invocations read naturally.
+    let args = args.strip_prefix(&["run".to_string()][..]).unwrap_or(args);
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-i" | "--input" => {
+                opts.input = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("{arg} requires a file argument"))?
+                        .clone(),
+                );
+            }
+            "-d" | "--debug" => opts.debug = true,
+            "--debug-max-events" => {
+                opts.debug_max_events = Some(numeric_arg(arg, iter.next())?);
+            }
+            "--var" => {
+                let spec = iter
+                    .next()
+                    .ok_or_else(|| format!("{arg} requires a name=value argument"))?;
+                let (name, value) = spec
+                    .split_once('=')
+                    .ok_or_else(|| format!("--var expects name=value, got: {spec}"))?;
+                opts.vars
+                    .push((name.trim().to_string(), parse_var_value(value)?));
+            }
+            "--profile-out" => {
+                opts.profile_out = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("{arg} requires a file argument"))?
+                        .clone(),
+                );
+            }
+            "--day" => opts.day = Some(numeric_arg(arg, iter.next())?),
+            "--part" => opts.part = Some(numeric_arg(arg, iter.next())?),
+            "--year" => opts.year = Some(numeric_arg(arg, iter.next())?),
+            "--root" => {
+                opts.root = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("{arg} requires a directory argument"))?
+                        .clone(),
+                );
+            }
+            "--max-steps" => {
+                opts.max_steps = Some(numeric_arg(arg, iter.next())?);
+            }
+            "--max-depth" => {
+                opts.max_depth = Some(numeric_arg(arg, iter.next())?);
+            }
+            "--max-memory" => {
+                opts.max_memory = Some(numeric_arg(arg, iter.next())?);
+            }
+            "--trace" => {
+                opts.trace = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("{arg} requires a file argument"))?
+                        .clone(),
+                );
+            }
+            "--checkpoint" => {
+                opts.checkpoint = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("{arg} requires a file argument"))?
+                        .clone(),
+                );
+            }
+            "--checkpoint-every" => {
+                opts.checkpoint_every = numeric_arg(arg, iter.next())?;
+            }
+            "--animate" => opts.animate = true,
+            "--animate-delay" => {
+                opts.animate_delay_ms = numeric_arg(arg, iter.next())?;
+            }
+            "--profile" => opts.profile = true,
+            "--heatmap" => opts.heatmap = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other if other.starts_with('-') => return Err(format!("unknown option: {other}")),
+            other => opts.programs.push(other.to_string()),
+        }
+    }
+    Ok(opts)
+}
+
+/// The conventional paths for a day's solution and input:
+/// `<root>/aoc-<year>/day-NN/partP.xmas` and `input.txt` next to it.
+fn day_paths(root: &str, year: i64, day: u32, part: u32) -> (String, String) {
+    let dir = format!("{root}/aoc-{year}/day-{day:02}");
+    (format!("{dir}/part{part}.xmas"), format!("{dir}/input.txt"))
+}
+
+/// Days-since-epoch to (year, month, day), via the usual civil-calendar
+/// arithmetic.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
+
+/// The most recent Advent of Code year: the current year once December
+/// starts, and the previous year's event before that.
+fn default_year() -> i64 {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let (year, month, _) = civil_from_days(secs.div_euclid(86400));
+    if month == 12 {
+        year
+    } else {
+        year - 1
+    }
+}
+
+/// Fills in `programs` and `input` from `--day`/`--part`/`--year`/`--root`.
+fn resolve_day(opts: &mut Options) -> Result<(), String> {
+    let Some(day) = opts.day else {
+        return Ok(());
+    };
+    if !opts.programs.is_empty() {
+        return Err("--day cannot be combined with program files".to_string());
+    }
+    let root = opts.root.as_deref().unwrap_or(".");
+    let year = opts.year.unwrap_or_else(default_year);
+    let part = opts.part.unwrap_or(1);
+    let (program, input) = day_paths(root, year, day, part);
+    if !std::path::Path::new(&program).exists() {
+        return Err(format!("no solution at {program}"));
+    }
+    opts.programs.push(program);
+    if opts.input.is_none() && std::path::Path::new(&input).exists() {
+        opts.input = Some(input);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("verify") {
+        let [_, dir] = args.as_slice() else {
+            eprintln!("usage: xmas verify <day-dir>");
+            return ExitCode::FAILURE;
+        };
+        return match verify::verify_day(dir) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(message) => {
+                eprintln!("error: {message}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut opts = match parse_args(&args) {
+        Ok(opts) => opts,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(message) = resolve_day(&mut opts) {
+        eprintln!("error: {message}");
+        return ExitCode::FAILURE;
+    }
+    if opts.programs.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut interp = Interpreter::new();
+    interp.set_debug(opts.debug);
+    if let Some(limit) = opts.debug_max_events {
+        interp.set_max_debug_events(limit);
+    }
+    for (name, value) in &opts.vars {
+        interp.set_global(name, value.clone());
+    }
+    if let Some(limit) = opts.max_steps {
+        interp.set_max_steps(limit);
+    }
+    if let Some(limit) = opts.max_depth {
+        interp.set_max_depth(limit);
+    }
+    if let Some(limit) = opts.max_memory {
+        interp.set_max_memory(limit);
+    }
+    if opts.profile {
+        interp.enable_profiling();
+    }
+    if opts.heatmap {
+        interp.enable_heatmap();
+    }
+    if opts.animate {
+        interp.set_animate(std::time::Duration::from_millis(opts.animate_delay_ms));
+    }
+    if let Some(path) = &opts.checkpoint {
+        if let Ok(text) = std::fs::read_to_string(path) {
+            match xmas_core::snapshot::parse(&text) {
+                Ok(globals) => {
+                    eprintln!("[checkpoint] resuming {} globals from {path}", globals.len());
+                    for (name, value) in globals {
+                        interp.set_global(&name, value);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: cannot resume from {path}: {e}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        // Write to a scratch file first so an interrupted save never
+        // clobbers the last good checkpoint.
+        let path = path.clone();
+        interp.set_checkpoint(
+            opts.checkpoint_every,
+            Box::new(move |text| {
+                let scratch = format!("{path}.tmp");
+                std::fs::write(&scratch, text).map_err(|e| e.to_string())?;
+                std::fs::rename(&scratch, &path).map_err(|e| e.to_string())
+            }),
+        );
+    }
+    if opts.profile_out.is_some() {
+        interp.enable_folded_profiling();
+    }
+    if let Some(trace_path) = &opts.trace {
+        match std::fs::File::create(trace_path) {
+            Ok(file) => interp.set_trace(Box::new(std::io::BufWriter::new(file))),
+            Err(e) => {
+                eprintln!("error: cannot create {trace_path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(input_path) = &opts.input {
+        match std::fs::read_to_string(input_path) {
+            Ok(input) => {
+                interp.set_input(input);
+                interp.set_input_name(input_path.clone());
+            }
+            Err(e) => {
+                eprintln!("error: cannot read {input_path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    for program_path in &opts.programs {
+        let source = match std::fs::read_to_string(program_path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("error: cannot read {program_path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let program = match lexer::lex(&source).and_then(|tokens| parser::parse(tokens, &source)) {
+            Ok(program) => program,
+            Err(err) => {
+                eprint!("{}", report::render(program_path, &source, &err));
+                return ExitCode::FAILURE;
+            }
+        };
+        if let Err(err) = interp.run(&program) {
+            eprint!("{}", report::render(program_path, &source, &err));
+            return ExitCode::FAILURE;
+        }
+        if let Some(listing) = interp.heatmap_report(&source) {
+            eprint!("{listing}");
+        }
+    }
+
+    let named = interp.named_results();
+    if named.is_empty() {
+        if let Some(result) = interp.result() {
+            println!("{result}");
+        }
+    } else {
+        for (label, value) in named {
+            println!("{label}: {value}");
+        }
+    }
+    for warning in interp.warnings() {
+        eprintln!("warning: {warning}");
+    }
+    if let Some(report) = interp.profile_report() {
+        eprint!("{report}");
+    }
+    if let (Some(path), Some(folded)) = (&opts.profile_out, interp.folded_report()) {
+        if let Err(e) = std::fs::write(path, folded) {
+            eprintln!("error: cannot write {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn var_values_parse() {
+        assert_eq!(parse_var_value("42").unwrap(), Value::Number(42));
+        assert_eq!(parse_var_value("-7").unwrap(), Value::Number(-7));
+        assert_eq!(parse_var_value("true").unwrap(), Value::Bool(true));
+        assert_eq!(parse_var_value("abc").unwrap(), Value::Str("abc".into()));
+        assert_eq!(
+            parse_var_value("\"quoted\"").unwrap(),
+            Value::Str("quoted".into())
+        );
+        assert_eq!(
+            parse_var_value("[1, 2]").unwrap(),
+            Value::Array1D(vec![Value::Number(1), Value::Number(2)])
+        );
+        assert!(parse_var_value("[[1]]").is_err());
+    }
+
+    #[test]
+    fn limit_flags_are_parsed() {
+        let args: Vec<String> = ["prog.xmas", "--max-steps", "100", "--max-memory", "4096"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let opts = parse_args(&args).unwrap();
+        assert_eq!(opts.max_steps, Some(100));
+        assert_eq!(opts.max_memory, Some(4096));
+        assert!(parse_args(&["p".into(), "--max-steps".into(), "x".into()]).is_err());
+    }
+
+    #[test]
+    fn day_flags_resolve_conventional_paths() {
+        let args: Vec<String> = ["run", "--day", "5", "--part", "2", "--year", "2015"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let opts = parse_args(&args).unwrap();
+        assert_eq!((opts.day, opts.part, opts.year), (Some(5), Some(2), Some(2015)));
+        assert_eq!(
+            day_paths(".", 2015, 5, 2),
+            (
+                "./aoc-2015/day-05/part2.xmas".to_string(),
+                "./aoc-2015/day-05/input.txt".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn civil_calendar_conversion() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        // 2015-12-25 is 16794 days after the epoch.
+        assert_eq!(civil_from_days(16794), (2015, 12, 25));
+    }
+
+    #[test]
+    fn var_flag_is_parsed() {
+        let args: Vec<String> = ["prog.xmas", "--var", "steps=10"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let opts = parse_args(&args).unwrap();
+        assert_eq!(opts.vars, vec![("steps".to_string(), Value::Number(10))]);
+    }
+}
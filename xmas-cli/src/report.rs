@@ -0,0 +1,90 @@
+//! Human-friendly error rendering for the CLI: shows the offending source
+//! line with a caret, an error code, and a hint, colored when stderr is a
+//! terminal (rustc-style).
+
+use std::io::IsTerminal;
+
+use xmas_core::error::{ErrorKind, XmasError};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders an error against the program it came from, pointing a caret at
+/// its span when it has one.
+pub fn render(path: &str, source: &str, err: &XmasError) -> String {
+    let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let (red, blue, bold, reset) = if color {
+        (RED, BLUE, BOLD, RESET)
+    } else {
+        ("", "", "", "")
+    };
+
+    let code = match err.kind {
+        ErrorKind::Lex | ErrorKind::Parse => "E0001",
+        ErrorKind::Runtime => "E0100",
+    };
+    let detail = &err.message;
+
+    let mut out = format!("{red}error[{code}]{reset}{bold}: {detail}{reset}\n");
+    if let Some(span) = err.span {
+        let (line, col) = (span.line, span.col);
+        out.push_str(&format!("  {blue}-->{reset} {path}:{line}:{col}\n"));
+        if let Some(text) = source.lines().nth(line - 1) {
+            let gutter = line.to_string();
+            let pad = " ".repeat(gutter.len());
+            out.push_str(&format!("{blue} {pad} |{reset}\n"));
+            out.push_str(&format!("{blue} {gutter} |{reset} {text}\n"));
+            let caret_pad = " ".repeat(col.saturating_sub(1));
+            out.push_str(&format!("{blue} {pad} |{reset} {caret_pad}{red}^{reset}\n"));
+        }
+    } else {
+        out.push_str(&format!("  {blue}-->{reset} {path}\n"));
+    }
+    if let Some(hint) = hint_for(detail) {
+        out.push_str(&format!("  {blue}= hint:{reset} {hint}\n"));
+    }
+    out
+}
+
+fn hint_for(detail: &str) -> Option<&'static str> {
+    if detail.starts_with("undefined variable") {
+        Some("check the spelling, or assign the variable before using it")
+    } else if detail.starts_with("undefined function") || detail.starts_with("unknown function") {
+        Some("define it with `fn name(args) = ...` before the call")
+    } else if detail.contains("division by zero") || detail.contains("modulo by zero") {
+        Some("guard the division with an `if` on the denominator")
+    } else if detail.starts_with("unterminated string") {
+        Some("strings cannot span lines; close the quote before the line ends")
+    } else if detail.starts_with("expected end of statement") {
+        Some("statements end at a newline; split this line or check for a missing operator")
+    } else if detail.starts_with("no input provided") {
+        Some("pass the puzzle input with `-i input.txt`")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_excerpt_with_caret() {
+        let err = XmasError::new(ErrorKind::Lex, "unexpected character '@'").at(2, 5);
+        let out = render("prog.xmas", "x = 1\ny = @\n", &err);
+        assert!(out.contains("error[E0001]"), "{out}");
+        assert!(out.contains("prog.xmas:2:5"), "{out}");
+        assert!(out.contains("y = @"), "{out}");
+        assert!(out.contains("    ^"), "{out}");
+    }
+
+    #[test]
+    fn renders_positionless_runtime_error_with_hint() {
+        let err = XmasError::new(ErrorKind::Runtime, "undefined variable: nope");
+        let out = render("prog.xmas", "x = 1\n", &err);
+        assert!(out.contains("error[E0100]"), "{out}");
+        assert!(out.contains("hint"), "{out}");
+    }
+}
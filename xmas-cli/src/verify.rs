@@ -0,0 +1,76 @@
+//! `xmas verify`: run a day's solutions against the annotated sample input
+//! before the real one, failing fast when the sample answer regresses.
+//!
+//! A solution opts in with a `// sample: <value>` comment; the expected value
+//! is checked against `sample.txt` in the same directory, and only if it
+//! matches does the solution run against `input.txt`.
+
+use std::path::Path;
+
+use xmas_core::run_source;
+
+/// Verifies every `partN.xmas` in `dir`, sample first, then real input.
+pub fn verify_day(dir: &str) -> Result<(), String> {
+    let dir = Path::new(dir);
+    let mut found_any = false;
+    for part in 1..=2 {
+        let program = dir.join(format!("part{part}.xmas"));
+        if !program.exists() {
+            continue;
+        }
+        found_any = true;
+        let source = read(&program)?;
+
+        if let Some(expected) = sample_expectation(&source) {
+            let sample = read(&dir.join("sample.txt"))?;
+            let result = run_part(&source, &sample)
+                .map_err(|e| format!("part{part} (sample): {e}"))?;
+            if result != expected {
+                return Err(format!(
+                    "part{part}: sample expected {expected}, got {result}"
+                ));
+            }
+            println!("part{part}: sample ok ({expected})");
+        }
+
+        let input = read(&dir.join("input.txt"))?;
+        let result = run_part(&source, &input).map_err(|e| format!("part{part}: {e}"))?;
+        println!("part{part}: {result}");
+    }
+    if found_any {
+        Ok(())
+    } else {
+        Err(format!("no part1.xmas or part2.xmas in {}", dir.display()))
+    }
+}
+
+/// The value of the `// sample: <value>` annotation, if the solution has one.
+fn sample_expectation(source: &str) -> Option<String> {
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("// sample:"))
+        .map(|rest| rest.trim().to_string())
+}
+
+fn run_part(source: &str, input: &str) -> Result<String, String> {
+    run_source(source, Some(input))
+        .map_err(|e| e.to_string())?
+        .map(|value| value.to_string())
+        .ok_or_else(|| "program did not set _".to_string())
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_sample_annotation() {
+        let source = "// Day 1\n// sample: -1\n_ = 0";
+        assert_eq!(sample_expectation(source), Some("-1".to_string()));
+        assert_eq!(sample_expectation("_ = 0"), None);
+    }
+}
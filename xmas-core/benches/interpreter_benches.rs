@@ -0,0 +1,97 @@
+//! Coarse benchmarks for the evaluator: lexing, parsing, and representative
+//! interpreter workloads (grid scan, recursion, array pipeline).
+//!
+//! Dependency-free on purpose — each benchmark runs a fixed workload in a
+//! timed loop and reports the per-iteration time, so an optimization (or a
+//! regression) shows up as a number, not a feeling. Compare runs of
+//! `cargo bench -p xmas-core` before and after a change.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use xmas_core::{lexer, parser, run_source};
+
+/// Times `f` for at least `MIN_TIME`, returning the mean per-call duration.
+fn bench(name: &str, mut f: impl FnMut()) {
+    const MIN_TIME: Duration = Duration::from_millis(200);
+    // Warm up and estimate a batch size so the clock is read infrequently.
+    f();
+    let start = Instant::now();
+    f();
+    let once = start.elapsed().max(Duration::from_nanos(1));
+    let batch = (Duration::from_millis(10).as_nanos() / once.as_nanos()).max(1) as u64;
+
+    let mut iters = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < MIN_TIME {
+        for _ in 0..batch {
+            f();
+        }
+        iters += batch;
+    }
+    let per_iter = start.elapsed() / iters.max(1) as u32;
+    println!("{name:<24} {per_iter:>12.2?}/iter  ({iters} iters)");
+}
+
+/// A source string with plenty of tokens and nesting for the front end.
+fn front_end_source() -> String {
+    let mut source = String::new();
+    for i in 0..200 {
+        source.push_str(&format!(
+            "x{i} = (1 + 2 * {i}) % 97\nif (x{i} > 50) {{ x{i} -= 25 }}\n"
+        ));
+    }
+    source
+}
+
+fn run(source: &str, input: Option<&str>) {
+    black_box(run_source(black_box(source), input).expect("benchmark program should run"));
+}
+
+fn main() {
+    let source = front_end_source();
+    bench("lex", || {
+        black_box(lexer::lex(black_box(&source)).expect("benchmark source should lex"));
+    });
+
+    let tokens = lexer::lex(&source).expect("benchmark source should lex");
+    bench("parse", || {
+        black_box(parser::parse(black_box(tokens.clone()), &source).expect("should parse"));
+    });
+
+    // Grid scan: visit every cell of the input grid.
+    let grid: String = vec!["x.y.z.x.y.z.x.y.z.x.y.z."; 50].join("\n");
+    let grid_scan = "\
+count = 0
+for (row in input) {
+    for (cell in row) {
+        if (cell == \"x\") { count += 1 }
+    }
+}
+_ = count";
+    bench("grid scan", || run(grid_scan, Some(&grid)));
+
+    // Recursion: a doubling call tree exercises calls and the stack.
+    let recursion = "\
+fn f0(n) = (n * 31 + 7) % 9973
+fn f1(n) = f0(f0(n))
+fn f2(n) = f1(f1(n))
+fn f3(n) = f2(f2(n))
+fn f4(n) = f3(f3(n))
+fn f5(n) = f4(f4(n))
+fn f6(n) = f5(f5(n))
+fn f7(n) = f6(f6(n))
+fn f8(n) = f7(f7(n))
+_ = f8(1)";
+    bench("recursion", || run(recursion, None));
+
+    // Array pipeline: build, transform and aggregate a packed number array.
+    let pipeline = "\
+a = sort(reverse(digits(987654321) * 200))
+total = 0
+for (x in a) {
+    total += x
+}
+_ = total";
+    bench("array pipeline", || run(pipeline, None));
+}
@@ -0,0 +1,308 @@
+//! Abstract syntax tree for xmas programs.
+
+use crate::intern::Symbol;
+
+/// A block of statements, each paired with the source line it starts on.
+pub type Block = Vec<(usize, Stmt)>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+    /// `x = expr`
+    Assign { name: Symbol, value: Expr },
+    /// `arr[i] = expr` / `grid[r][c] = expr` — assignment through a chain
+    /// of index expressions.
+    IndexAssign { target: AssignTarget, value: Expr },
+    /// `global x = expr` — writes the top-level variable even from inside a
+    /// function body, where plain assignment binds locally.
+    GlobalAssign { name: Symbol, value: Expr },
+    /// `x += expr` and friends; the target may be indexed, as in
+    /// `grid[r][c] += 1`.
+    AssignOp {
+        target: AssignTarget,
+        op: BinOp,
+        value: Expr,
+    },
+    /// `fn name(a, b) = expr` or `fn name(a, b) { ... }`, optionally
+    /// prefixed with `memo` and preceded by `///` doc comment lines.
+    FnDef {
+        name: Symbol,
+        params: Vec<Symbol>,
+        body: FnBody,
+        memoized: bool,
+        doc: Option<String>,
+    },
+    If {
+        cond: Expr,
+        then_block: Block,
+        else_block: Block,
+    },
+    While {
+        cond: Expr,
+        body: Block,
+        /// `outer: while (...) { ... }`
+        label: Option<Symbol>,
+    },
+    /// `for (x in iter) { ... }`, with an optional accumulator clause:
+    /// `for (x in iter, acc = 0) { ... }`.
+    For {
+        var: Symbol,
+        iter: Expr,
+        init: Option<(Symbol, Expr)>,
+        body: Block,
+        /// `outer: for (...) { ... }`
+        label: Option<Symbol>,
+    },
+    /// `break` — exits the innermost loop; `break outer` exits the
+    /// enclosing loop with that label.
+    Break { label: Option<Symbol> },
+    /// `continue` — skips to the next iteration of the innermost loop.
+    Continue,
+    /// `return expr` — unwinds out of the enclosing block-bodied function
+    /// with that value.
+    Return(Expr),
+    /// `match (expr) { pattern => { ... } ... }` — the first arm whose
+    /// pattern matches runs; no match is a runtime error.
+    Match { subject: Expr, arms: Vec<MatchArm> },
+    Expr(Expr),
+}
+
+/// One `pattern => { ... }` arm of a `match` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Block,
+}
+
+/// What a match arm accepts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    /// A literal the value must equal, like `3` or `"->"`.
+    Literal(Expr),
+    /// A name that matches any value and binds it.
+    Binding(Symbol),
+    /// `[p, p, ...]` — an array of exactly these elements, or with
+    /// `...rest` last, an array of at least the listed elements where
+    /// `rest` binds whatever follows them.
+    Array(Vec<Pattern>, Option<Symbol>),
+}
+
+/// A function body: the classic `= expr` form, or a braced statement block
+/// whose result is the `_` the block assigns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FnBody {
+    Expr(Expr),
+    Block(Block),
+}
+
+/// The left-hand side of a compound assignment: a variable, optionally
+/// followed by a chain of index expressions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssignTarget {
+    pub name: Symbol,
+    pub indices: Vec<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Str(String),
+    Bool(bool),
+    Identifier(Symbol),
+    Array(Vec<Expr>),
+    /// `{"a": 1, "b": 2}` — key-value entries, in source order.
+    Map(Vec<(Expr, Expr)>),
+    /// `[a..b]` — a half-open range.
+    Range(Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    /// `arr[a:b]` with either bound optional.
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// `f(a, b, key = c)` — positional arguments, then named arguments.
+    Call(Symbol, Vec<Expr>, Vec<(Symbol, Expr)>),
+    /// `lhs |> rhs`
+    Pipe(Box<Expr>, Box<Expr>),
+    /// The `input` keyword.
+    Input,
+    /// `input.meta` — facts about the loaded puzzle input.
+    InputMeta,
+}
+
+impl Expr {
+    /// The literal number `n`.
+    pub fn number(n: i64) -> Expr {
+        Expr::Number(n)
+    }
+
+    /// The literal string `s`.
+    pub fn string(s: impl Into<String>) -> Expr {
+        Expr::Str(s.into())
+    }
+
+    /// A reference to the variable (or function) `name`.
+    pub fn ident(name: &str) -> Expr {
+        Expr::Identifier(Symbol::intern(name))
+    }
+
+    /// A call of `name` with positional arguments.
+    pub fn call(name: &str, args: Vec<Expr>) -> Expr {
+        Expr::Call(Symbol::intern(name), args, Vec::new())
+    }
+
+    /// The binary operation `lhs op rhs`.
+    pub fn binary(lhs: Expr, op: BinOp, rhs: Expr) -> Expr {
+        Expr::Binary(Box::new(lhs), op, Box::new(rhs))
+    }
+
+    /// The unary operation `op operand`.
+    pub fn unary(op: UnaryOp, operand: Expr) -> Expr {
+        Expr::Unary(op, Box::new(operand))
+    }
+
+    /// The indexing expression `base[index]`.
+    pub fn index(base: Expr, index: Expr) -> Expr {
+        Expr::Index(Box::new(base), Box::new(index))
+    }
+}
+
+/// Builder for assembling a [`Block`] directly, so Rust tools can synthesize
+/// or rewrite programs without going through source text. Statements are
+/// numbered in push order, standing in for source lines in trace and debug
+/// output.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+    stmts: Block,
+}
+
+impl Program {
+    pub fn new() -> Program {
+        Program::default()
+    }
+
+    /// Appends any statement.
+    pub fn push(&mut self, stmt: Stmt) {
+        let line = self.stmts.len() + 1;
+        self.stmts.push((line, stmt));
+    }
+
+    /// Appends `name = value`.
+    pub fn push_assign(&mut self, name: &str, value: Expr) {
+        self.push(Stmt::Assign {
+            name: Symbol::intern(name),
+            value,
+        });
+    }
+
+    /// Appends an expression statement.
+    pub fn push_expr(&mut self, expr: Expr) {
+        self.push(Stmt::Expr(expr));
+    }
+
+    /// Appends `fn name(params) = body`.
+    pub fn push_fn(&mut self, name: &str, params: &[&str], body: Expr) {
+        self.push(Stmt::FnDef {
+            name: Symbol::intern(name),
+            params: params.iter().map(|param| Symbol::intern(param)).collect(),
+            body: FnBody::Expr(body),
+            memoized: false,
+            doc: None,
+        });
+    }
+
+    /// The assembled statements, as [`crate::interpreter::Interpreter::run`]
+    /// takes them.
+    pub fn into_block(self) -> Block {
+        self.stmts
+    }
+
+    pub fn block(&self) -> &Block {
+        &self.stmts
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `-x`
+    Neg,
+    /// `!x`
+    Not,
+    /// `~x` — convert a string to a number.
+    ToNum,
+    /// `$x` — convert a value to its string representation.
+    ToStr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+}
+
+impl BinOp {
+    /// The operator as written in source, for error messages.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::NotEq => "!=",
+            BinOp::Lt => "<",
+            BinOp::LtEq => "<=",
+            BinOp::Gt => ">",
+            BinOp::GtEq => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, Value};
+
+    #[test]
+    fn built_programs_run_like_parsed_ones() {
+        let mut program = Program::new();
+        program.push_fn(
+            "double",
+            &["x"],
+            Expr::binary(Expr::ident("x"), BinOp::Mul, Expr::number(2)),
+        );
+        program.push_assign("_", Expr::call("double", vec![Expr::number(21)]));
+        let mut interp = Interpreter::new();
+        interp.run(program.block()).expect("program should run");
+        assert_eq!(interp.result(), Some(Value::Number(42)));
+    }
+
+    #[test]
+    fn builders_produce_the_parser_shapes() {
+        assert_eq!(Expr::number(7), Expr::Number(7));
+        assert_eq!(Expr::string("hi"), Expr::Str("hi".into()));
+        assert_eq!(
+            Expr::unary(UnaryOp::Neg, Expr::ident("x")),
+            Expr::Unary(UnaryOp::Neg, Box::new(Expr::Identifier(Symbol::intern("x"))))
+        );
+        assert_eq!(
+            Expr::index(Expr::ident("arr"), Expr::number(0)),
+            Expr::Index(
+                Box::new(Expr::Identifier(Symbol::intern("arr"))),
+                Box::new(Expr::Number(0))
+            )
+        );
+    }
+}
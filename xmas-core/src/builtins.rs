@@ -0,0 +1,2230 @@
+//! The builtin function registry.
+//!
+//! Each builtin is a [`BuiltinSpec`]: its name, an arity spec, and a handler.
+//! Adding a builtin means adding one entry to [`BUILTINS`] — dispatch,
+//! arity checking and name lookup all come from the table.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::interpreter::{
+    compare_values, repeat_count, stable_hash, to_number, unpack, values_equal,
+    BitSet, CharGrid, Graph, Interpreter, LruCache, MapVal, OverflowMode, RangeSet, SetVal,
+    SparseGrid, Value,
+};
+
+type Handler = fn(&mut Interpreter, Vec<Value>) -> Result<Value, String>;
+
+/// One registered builtin: name, accepted argument counts, a one-line help
+/// string, and handler.
+pub struct BuiltinSpec {
+    pub name: &'static str,
+    pub min_args: usize,
+    /// `None` means variadic.
+    pub max_args: Option<usize>,
+    pub doc: &'static str,
+    handler: Handler,
+}
+
+impl BuiltinSpec {
+    pub(crate) fn call(
+        &self,
+        interp: &mut Interpreter,
+        args: Vec<Value>,
+    ) -> Result<Value, String> {
+        let ok = args.len() >= self.min_args
+            && self.max_args.is_none_or(|max| args.len() <= max);
+        if !ok {
+            let expected = match (self.min_args, self.max_args) {
+                (min, Some(max)) if min == max => format!("{min}"),
+                (min, Some(max)) => format!("{min} to {max}"),
+                (min, None) => format!("at least {min}"),
+            };
+            return Err(format!(
+                "{} expects {expected} argument(s), got {}",
+                self.name,
+                args.len()
+            ));
+        }
+        (self.handler)(interp, args)
+    }
+}
+
+macro_rules! spec {
+    ($name:literal, $min:literal..=$max:literal, $doc:literal, $handler:expr) => {
+        BuiltinSpec {
+            name: $name,
+            min_args: $min,
+            max_args: Some($max),
+            doc: $doc,
+            handler: $handler,
+        }
+    };
+    ($name:literal, $min:literal.., $doc:literal, $handler:expr) => {
+        BuiltinSpec {
+            name: $name,
+            min_args: $min,
+            max_args: None,
+            doc: $doc,
+            handler: $handler,
+        }
+    };
+}
+
+/// Every builtin, in rough usefulness order. The parser treats any of these
+/// names as a call when followed by `(`.
+pub static BUILTINS: &[BuiltinSpec] = &[
+    spec!("print", 0.., "print(...): print the arguments separated by spaces", print),
+    spec!("help", 1..=1, "help(name): print the documentation for a function", help),
+    spec!("len", 1..=1, "len(x): the length of a string, array or range", len),
+    spec!("isEmpty", 1..=1, "isEmpty(x): whether x has no elements", is_empty),
+    spec!("max", 1..=2, "max(arr) or max(a, b): the largest value", max),
+    spec!("min", 1..=2, "min(arr) or min(a, b): the smallest value", min),
+    spec!("sum", 1..=1, "sum(arr): the sum of a numeric array", sum),
+    spec!("product", 1..=1, "product(arr): the product of a numeric array", product),
+    spec!("minOf", 1..=1, "minOf(arr): the smallest element of a numeric array", min_of),
+    spec!("maxOf", 1..=1, "maxOf(arr): the largest element of a numeric array", max_of),
+    spec!("argmax", 1..=2, "argmax(arr) or argmax(arr, f): the index of the largest value (or largest f(item))", argmax),
+    spec!("argmin", 1..=2, "argmin(arr) or argmin(arr, f): the index of the smallest value (or smallest f(item))", argmin),
+    spec!("floor", 2..=2, "floor(a, b): a divided by b, rounded down", floor),
+    spec!("ceil", 2..=2, "ceil(a, b): a divided by b, rounded up", ceil),
+    spec!("safeDiv", 3..=3, "safeDiv(a, b, d): a / b, or d when b is 0 (or the division overflows)", safe_div),
+    spec!("checked", 0..=0, "checked(): make arithmetic overflow an error (the default)", checked),
+    spec!("wrapping", 0..=0, "wrapping(): make arithmetic overflow wrap around", wrapping),
+    spec!("saturating", 0..=0, "saturating(): make arithmetic overflow clamp to the i64 limits", saturating),
+    spec!("strictSlices", 0..=0, "strictSlices(): make out-of-range slices an error instead of clamping", strict_slices),
+    spec!("abs", 1..=1, "abs(n): the absolute value of n", abs),
+    spec!("eval", 1..=1, "eval(s): evaluate s as an xmas expression in the current program state", eval),
+    spec!("evalWithPrecedence", 2..=2, "evalWithPrecedence(s, prec): evaluate arithmetic with operator precedence from a map like {\"+\": 2, \"*\": 1}", eval_with_precedence),
+    spec!("toNum", 1..=1, "toNum(s): parse a string as a number, like ~s", to_num),
+    spec!("digits", 1..=1, "digits(n): the decimal digits of n as an array", digits),
+    spec!("fromDigits", 1..=1, "fromDigits(arr): the number with those decimal digits", from_digits),
+    spec!("numLen", 1..=1, "numLen(n): how many decimal digits n has", num_len),
+    spec!("split", 1..=2, "split(s) or split(s, delim): s broken on whitespace, or on each delim", split),
+    spec!("join", 2..=2, "join(arr, sep): the elements rendered as strings, separated by sep", join),
+    spec!("concat", 1..=1, "concat(arr): join the elements into one string", concat),
+    spec!("sumOfDigits", 1..=1, "sumOfDigits(s): the sum of the digit characters in s", sum_of_digits),
+    spec!("nums", 1..=1, "nums(s): every integer in the string, minus signs included", nums),
+    spec!("match", 2..=2, "match(s, pat): the first regex match as [whole, group...], or [] if none", regex_match),
+    spec!("matchAll", 2..=2, "matchAll(s, pat): every regex match as rows of [whole, group...]", regex_match_all),
+    spec!("extract", 2..=2, "extract(s, pat): the substrings matching the {} holes in pat", extract),
+    spec!("toArray", 1..=1, "toArray(x): x materialized as a plain array", to_array),
+    spec!("fill", 2..=2, "fill(n, v): an array of n copies of v", fill),
+    spec!("fill2d", 3..=3, "fill2d(rows, cols, v): a 2d array filled with v", fill2d),
+    spec!("grid", 1..=1, "grid(s): a 2d char grid from a multi-line string", grid),
+    spec!("render", 1..=2, "render(grid) or render(sg, empty): the grid as a multi-line string", render),
+    spec!("animate", 1..=2, "animate(grid): draw the grid as an animation frame (with --animate)", animate),
+    spec!("generate", 2..=2, "generate(n, f): the array [f(0), ..., f(n - 1)]", generate),
+    spec!("map", 2..=2, "map(arr, f): a new array of f applied to each element", map),
+    spec!("filter", 2..=2, "filter(arr, f): the elements where f(x) is truthy", filter),
+    spec!("reduce", 3..=3, "reduce(arr, f, init): fold the array with acc = f(acc, x)", reduce),
+    spec!("sort", 1..=1, "sort(arr): the array in ascending order", sort),
+    spec!("sortBy", 2..=3, "sortBy(arr, f) or sortBy(arr, [f1, f2], desc): order by key fns, later keys breaking ties", sort_by),
+    spec!("reverse", 1..=1, "reverse(x): a string, array or range backwards", reverse),
+    spec!("rotate", 2..=2, "rotate(arr, n): the array shifted n places right, wrapping", rotate),
+    spec!("splitAt", 2..=2, "splitAt(arr, i): [arr[:i], arr[i:]]", split_at),
+    spec!("partition", 2..=2, "partition(arr, pred): [elements where pred(x), the rest]", partition),
+    spec!("pairs", 1..=1, "pairs(arr): every unordered pair of elements as [a, b]", pairs),
+    spec!("contains", 2..=2, "contains(x, v): whether x has an element v", contains),
+    spec!("point", 2..=2, "point(r, c): the grid coordinate (r, c)", point),
+    spec!("polygonArea", 1..=1, "polygonArea(pts): the area enclosed by the polygon's vertex points (shoelace)", polygon_area),
+    spec!("perimeter", 1..=1, "perimeter(pts): the Manhattan length of the polygon's boundary", perimeter),
+    spec!("point3", 3..=3, "point3(x, y, z): the 3d coordinate (x, y, z)", point3),
+    spec!("rotate3", 2..=2, "rotate3(p, i): p under axis-aligned rotation i (0..24)", rotate3),
+    spec!("rotations3", 1..=1, "rotations3(p): p under all 24 axis-aligned rotations", rotations3),
+    spec!("segmentsIntersect", 4..=4, "segmentsIntersect(p1, p2, p3, p4): whether segments p1-p2 and p3-p4 cross or touch", segments_intersect),
+    spec!("pointsOnLine", 2..=2, "pointsOnLine(p1, p2): every lattice point from p1 to p2, inclusive", points_on_line),
+    spec!("sparse", 1..=2, "sparse(default) or sparse(grid, default): a point-keyed sparse grid", sparse),
+    spec!("bounds", 1..=1, "bounds(sg): [min, max] corner points of the set cells", bounds),
+    spec!("dense", 1..=1, "dense(sg): the sparse grid materialized as a 2d array", dense),
+    spec!("bfs", 3..=3, "bfs(start, nf, gf): fewest steps from start to a state where gf, expanding with nf; -1 if unreachable", bfs),
+    spec!("bfsPath", 3..=3, "bfsPath(start, nf, gf): the shortest chain of states from start to the goal, [] if unreachable", bfs_path),
+    spec!("gridDistances", 3..=3, "gridDistances(grid, start, f): BFS distances from start through cells where f(cell), -1 if unreachable", grid_distances),
+    spec!("rows", 1..=1, "rows(grid): how many rows the grid has", rows),
+    spec!("cols", 1..=1, "cols(grid): how many columns the grid's first row has", cols),
+    spec!("transpose", 1..=1, "transpose(grid): the grid with rows and columns swapped", transpose),
+    spec!("inBounds", 2..=3, "inBounds(grid, r, c) or inBounds(grid, p): whether the cell is inside the grid", in_bounds),
+    spec!("find2d", 2..=2, "find2d(grid, v): the point of the first v, or (-1, -1)", find2d),
+    spec!("neighbors", 2..=3, "neighbors(grid, r, c) or neighbors(g, node): adjacent points or nodes", neighbors),
+    spec!("neighbors4", 2..=3, "neighbors4(grid, p): the in-bounds orthogonal neighbor points; also takes (rows, cols) dimensions", neighbors4),
+    spec!("neighbors8", 2..=3, "neighbors8(grid, p): the in-bounds orthogonal and diagonal neighbor points", neighbors8),
+    spec!("graph", 0..=0, "graph(): an empty directed graph", graph),
+    spec!("addEdge", 3..=3, "addEdge(g, a, b): the graph with the edge a -> b added", add_edge),
+    spec!("nodes", 1..=1, "nodes(g): the graph's nodes in insertion order", nodes),
+    spec!("connectedComponents", 1..=1, "connectedComponents(g): node groups connected ignoring direction", connected_components),
+    spec!("topoSort", 1..=1, "topoSort(g): the nodes in dependency order; errors on a cycle", topo_sort),
+    spec!("maxFlow", 3..=3, "maxFlow(g, s, t): maximum flow from s to t, 1 unit per edge", max_flow),
+    spec!("minCut", 1..=1, "minCut(g): the two node groups either side of the smallest cut", min_cut),
+    spec!("bitset", 0..=0, "bitset(): an empty bitset, growing as bits are set", bitset),
+    spec!("cache", 1..=1, "cache(n): a map holding n entries, evicting the least recently used", cache),
+    spec!("put", 3..=3, "put(c, k, v) or put(m, k, v): store k = v in a cache, or a map with k = v added", put),
+    spec!("keys", 1..=1, "keys(m): the map's keys, in sorted order", keys),
+    spec!("values", 1..=1, "values(m): the map's values, in keys(m) order", values),
+    spec!("has", 2..=2, "has(x, k): whether a map has key k, or a set element k", has),
+    spec!("del", 2..=2, "del(m, k): the map with key k removed", del),
+    spec!("rangeSet", 0..=0, "rangeSet(): an empty set of i64 intervals", range_set),
+    spec!("covered", 1..=1, "covered(rs): how many integers the range set's intervals cover", covered),
+    spec!("spans", 1..=1, "spans(rs): the range set's disjoint intervals as ranges", spans),
+    spec!("set", 1..=2, "set(arr) or set(bs, i): a set of the elements, or the bitset with bit i on", set),
+    spec!("add", 2..=2, "add(s, v): the set with v added", add),
+    spec!("remove", 2..=2, "remove(s, v): the set with v removed", remove),
+    spec!("union", 2..=2, "union(a, b): the elements in either set", union),
+    spec!("intersect", 2..=2, "intersect(a, b): the elements in both sets or arrays", intersect),
+    spec!("difference", 2..=2, "difference(a, b): the elements of a not in b (sets or arrays)", difference),
+    spec!("get", 2..=2, "get(bs, i) or get(c, k): a bitset bit, or a cached value", get),
+    spec!("getOr", 3..=3, "getOr(x, i, d): x[i], or d when the index is out of range or missing", get_or),
+    spec!("count", 1..=1, "count(bs): how many bits are set", count),
+    spec!("and", 2..=2, "and(a, b): the bits set in both bitsets", and),
+    spec!("or", 2..=2, "or(a, b): the bits set in either bitset", or),
+    spec!("xor", 2..=2, "xor(a, b): the bits set in exactly one bitset", xor),
+    spec!("deadline", 3..=3, "deadline(ms, f, fallback): f() within a time budget, else the fallback", deadline),
+    spec!("hash", 1..=1, "hash(v): a stable 64-bit fingerprint of the value", hash),
+    spec!("memoStats", 1..=1, "memoStats(name): [hits, misses, entries] for a memo fn's cache", memo_stats),
+    spec!("memoClear", 1..=1, "memoClear(name): drop a memo fn's cached results; returns how many", memo_clear),
+];
+
+/// Looks up a builtin by name.
+pub fn lookup(name: &str) -> Option<&'static BuiltinSpec> {
+    BUILTINS.iter().find(|spec| spec.name == name)
+}
+
+/// Whether `name` names a builtin.
+pub fn is_builtin(name: &str) -> bool {
+    lookup(name).is_some()
+}
+
+fn help(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Str(name) => match interp.doc_for(name) {
+            Some(text) => {
+                println!("{text}");
+                Ok(Value::Str(text))
+            }
+            None => Err(format!("help: unknown function {name}")),
+        },
+        _ => Err("help expects a function name string".to_string()),
+    }
+}
+
+fn print(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let text = args
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+    println!("{text}");
+    Ok(args.into_iter().next_back().unwrap_or(Value::Bool(true)))
+}
+
+fn len(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Str(s) => Ok(Value::Number(s.chars().count() as i64)),
+        Value::Array1D(items) => Ok(Value::Number(items.len() as i64)),
+        Value::NumArray(nums) => Ok(Value::Number(nums.len() as i64)),
+        Value::Array2D(rows) => Ok(Value::Number(rows.len() as i64)),
+        Value::Grid(g) => Ok(Value::Number(g.rows() as i64)),
+        Value::Range(r) => Ok(Value::Number(r.len)),
+        Value::Point(..) => Ok(Value::Number(2)),
+        Value::Sparse(grid) => Ok(Value::Number(grid.cells.len() as i64)),
+        Value::Graph(g) => Ok(Value::Number(g.nodes().len() as i64)),
+        Value::Cache(cache) => Ok(Value::Number(cache.borrow().len() as i64)),
+        Value::Map(map) => Ok(Value::Number(map.entries.len() as i64)),
+        Value::Set(set) => Ok(Value::Number(set.items.len() as i64)),
+        other => Err(format!("len: unsupported type {}", other.type_name())),
+    }
+}
+
+fn is_empty(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Str(s) => Ok(Value::Bool(s.is_empty())),
+        Value::Array1D(items) => Ok(Value::Bool(items.is_empty())),
+        Value::NumArray(nums) => Ok(Value::Bool(nums.is_empty())),
+        Value::Array2D(rows) => Ok(Value::Bool(rows.is_empty())),
+        Value::Grid(g) => Ok(Value::Bool(g.rows() == 0)),
+        Value::Range(r) => Ok(Value::Bool(r.len == 0)),
+        Value::Sparse(grid) => Ok(Value::Bool(grid.cells.is_empty())),
+        Value::Graph(g) => Ok(Value::Bool(g.nodes().is_empty())),
+        Value::Cache(cache) => Ok(Value::Bool(cache.borrow().is_empty())),
+        Value::Map(map) => Ok(Value::Bool(map.entries.is_empty())),
+        Value::Set(set) => Ok(Value::Bool(set.items.is_empty())),
+        other => Err(format!("isEmpty: unsupported type {}", other.type_name())),
+    }
+}
+
+fn extreme(name: &str, args: Vec<Value>, want_greater: bool) -> Result<Value, String> {
+    let items = match args.as_slice() {
+        [Value::NumArray(nums)] => {
+            let best = if want_greater {
+                nums.iter().max()
+            } else {
+                nums.iter().min()
+            };
+            return best
+                .copied()
+                .map(Value::Number)
+                .ok_or_else(|| format!("{name}: empty array"));
+        }
+        [Value::Array1D(items)] => items.clone(),
+        [a, b] => vec![a.clone(), b.clone()],
+        _ => return Err(format!("{name} expects an array or 2 arguments")),
+    };
+    if items.is_empty() {
+        return Err(format!("{name}: empty array"));
+    }
+    let mut best = items[0].clone();
+    for item in &items[1..] {
+        let ord = compare_values(item, &best)?;
+        let better = if want_greater {
+            ord == std::cmp::Ordering::Greater
+        } else {
+            ord == std::cmp::Ordering::Less
+        };
+        if better {
+            best = item.clone();
+        }
+    }
+    Ok(best)
+}
+
+fn max(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    extreme("max", args, true)
+}
+
+fn min(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    extreme("min", args, false)
+}
+
+/// Shared by `argmin`/`argmax`: the index of the extreme element, or of the
+/// extreme key when a key function is given. Ties go to the first.
+fn extreme_index(
+    interp: &mut Interpreter,
+    name: &str,
+    args: Vec<Value>,
+    want_greater: bool,
+) -> Result<Value, String> {
+    let (items, func) = match args.as_slice() {
+        [Value::Array1D(items)] => (items.clone(), None),
+        [Value::NumArray(nums)] => (unpack(nums), None),
+        [Value::Array1D(items), func] => (items.clone(), Some(func.clone())),
+        [Value::NumArray(nums), func] => (unpack(nums), Some(func.clone())),
+        _ => return Err(format!("{name} expects an array and an optional function")),
+    };
+    if items.is_empty() {
+        return Err(format!("{name}: empty array"));
+    }
+    let mut best_index = 0;
+    let mut best_key = match &func {
+        Some(func) => interp.call_fn_value(func, vec![items[0].clone()])?,
+        None => items[0].clone(),
+    };
+    for (index, item) in items.iter().enumerate().skip(1) {
+        let key = match &func {
+            Some(func) => interp.call_fn_value(func, vec![item.clone()])?,
+            None => item.clone(),
+        };
+        let ord = compare_values(&key, &best_key)?;
+        let better = if want_greater {
+            ord == std::cmp::Ordering::Greater
+        } else {
+            ord == std::cmp::Ordering::Less
+        };
+        if better {
+            best_index = index;
+            best_key = key;
+        }
+    }
+    Ok(Value::Number(best_index as i64))
+}
+
+fn argmax(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    extreme_index(interp, "argmax", args, true)
+}
+
+fn argmin(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    extreme_index(interp, "argmin", args, false)
+}
+
+fn int_div(name: &str, args: &[Value], ceiling: bool) -> Result<Value, String> {
+    match args {
+        [Value::Number(a), Value::Number(b)] => {
+            if *b == 0 {
+                return Err(format!("{name}: division by zero"));
+            }
+            let value = if ceiling {
+                a.div_euclid(*b) + i64::from(a.rem_euclid(*b) != 0)
+            } else {
+                a.div_euclid(*b)
+            };
+            Ok(Value::Number(value))
+        }
+        _ => Err(format!("{name} expects 2 numbers")),
+    }
+}
+
+fn floor(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    int_div("floor", &args, false)
+}
+
+/// The elements of a numeric array, with a per-element error naming the
+/// offender so mixed arrays fail with something actionable.
+fn numeric_elements(name: &str, value: &Value) -> Result<Vec<i64>, String> {
+    match value {
+        Value::NumArray(nums) => Ok(nums.clone()),
+        Value::Array1D(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| match item {
+                Value::Number(n) => Ok(*n),
+                other => Err(format!(
+                    "{name}: element {i} is a {}, not a number",
+                    other.type_name()
+                )),
+            })
+            .collect(),
+        other => Err(format!("{name} expects an array, got {}", other.type_name())),
+    }
+}
+
+fn sum(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    // Accumulate in i128 so a running total may swing outside the i64 range
+    // as long as it comes back; only the final result has to fit.
+    let mut total = 0i128;
+    for n in numeric_elements("sum", &args[0])? {
+        total += i128::from(n);
+    }
+    i64::try_from(total)
+        .map(Value::Number)
+        .map_err(|_| format!("sum: the total {total} does not fit in a number"))
+}
+
+fn product(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let mut total = 1i128;
+    for n in numeric_elements("product", &args[0])? {
+        total = total
+            .checked_mul(i128::from(n))
+            .ok_or_else(|| "product: number overflow".to_string())?;
+    }
+    i64::try_from(total)
+        .map(Value::Number)
+        .map_err(|_| format!("product: the total {total} does not fit in a number"))
+}
+
+fn min_of(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    numeric_elements("minOf", &args[0])?
+        .into_iter()
+        .min()
+        .map(Value::Number)
+        .ok_or_else(|| "minOf: empty array".to_string())
+}
+
+fn max_of(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    numeric_elements("maxOf", &args[0])?
+        .into_iter()
+        .max()
+        .map(Value::Number)
+        .ok_or_else(|| "maxOf: empty array".to_string())
+}
+
+fn safe_div(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Number(a), Value::Number(b), default] => Ok(a
+            .checked_div(*b)
+            .map_or_else(|| default.clone(), Value::Number)),
+        _ => Err("safeDiv expects 2 numbers and a default".to_string()),
+    }
+}
+
+fn ceil(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    int_div("ceil", &args, true)
+}
+
+fn checked(interp: &mut Interpreter, _args: Vec<Value>) -> Result<Value, String> {
+    interp.set_overflow_mode(OverflowMode::Checked);
+    Ok(Value::Bool(true))
+}
+
+fn wrapping(interp: &mut Interpreter, _args: Vec<Value>) -> Result<Value, String> {
+    interp.set_overflow_mode(OverflowMode::Wrapping);
+    Ok(Value::Bool(true))
+}
+
+fn saturating(interp: &mut Interpreter, _args: Vec<Value>) -> Result<Value, String> {
+    interp.set_overflow_mode(OverflowMode::Saturating);
+    Ok(Value::Bool(true))
+}
+
+fn strict_slices(interp: &mut Interpreter, _args: Vec<Value>) -> Result<Value, String> {
+    interp.set_strict_slices(true);
+    Ok(Value::Bool(true))
+}
+
+fn abs(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Number(n.abs())),
+        _ => Err("abs expects a number".to_string()),
+    }
+}
+
+fn eval(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let Value::Str(source) = &args[0] else {
+        return Err(format!("eval expects a string, got {}", args[0].type_name()));
+    };
+    let tokens = crate::lexer::lex(source).map_err(|e| format!("eval: {e}"))?;
+    let expr =
+        crate::parser::parse_expression(tokens, source).map_err(|e| format!("eval: {e}"))?;
+    interp.eval_expr(&expr)
+}
+
+fn eval_with_precedence(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let [Value::Str(source), Value::Map(prec)] = args.as_slice() else {
+        return Err("evalWithPrecedence expects a string and a precedence map".to_string());
+    };
+    let mut levels = HashMap::new();
+    for (key, value) in &prec.entries {
+        let (Value::Str(op), Value::Number(level)) = (key, value) else {
+            return Err(
+                "evalWithPrecedence: the map takes operator strings to numbers".to_string(),
+            );
+        };
+        let mut chars = op.chars();
+        match (chars.next(), chars.next()) {
+            (Some(op @ ('+' | '-' | '*' | '/' | '%')), None) => {
+                levels.insert(op, *level);
+            }
+            _ => return Err(format!("evalWithPrecedence: unknown operator \"{op}\"")),
+        }
+    }
+    let tokens: Vec<char> = source.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut parser = ArithParser {
+        tokens,
+        pos: 0,
+        levels,
+    };
+    let result = parser.expr(i64::MIN)?;
+    if parser.pos != parser.tokens.len() {
+        return Err(format!(
+            "evalWithPrecedence: unexpected '{}'",
+            parser.tokens[parser.pos]
+        ));
+    }
+    Ok(Value::Number(result))
+}
+
+/// Precedence-climbing evaluator over `evalWithPrecedence` input: numbers,
+/// parentheses and the five arithmetic operators, all left-associative.
+struct ArithParser {
+    tokens: Vec<char>,
+    pos: usize,
+    levels: HashMap<char, i64>,
+}
+
+impl ArithParser {
+    fn expr(&mut self, min_level: i64) -> Result<i64, String> {
+        let mut left = self.atom()?;
+        while let Some(&op) = self.tokens.get(self.pos) {
+            let Some(&level) = self.levels.get(&op) else {
+                break;
+            };
+            if level < min_level {
+                break;
+            }
+            self.pos += 1;
+            let right = self.expr(level + 1)?;
+            left = match op {
+                '+' => left.checked_add(right),
+                '-' => left.checked_sub(right),
+                '*' => left.checked_mul(right),
+                '/' => left.checked_div(right),
+                _ => left.checked_rem(right),
+            }
+            .ok_or_else(|| format!("evalWithPrecedence: '{op}' overflowed or divided by 0"))?;
+        }
+        Ok(left)
+    }
+
+    fn atom(&mut self) -> Result<i64, String> {
+        match self.tokens.get(self.pos) {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.expr(i64::MIN)?;
+                if self.tokens.get(self.pos) != Some(&')') {
+                    return Err("evalWithPrecedence: missing ')'".to_string());
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                while self
+                    .tokens
+                    .get(self.pos)
+                    .is_some_and(char::is_ascii_digit)
+                {
+                    self.pos += 1;
+                }
+                let digits: String = self.tokens[start..self.pos].iter().collect();
+                digits
+                    .parse()
+                    .map_err(|_| format!("evalWithPrecedence: number too large: {digits}"))
+            }
+            Some(c) => Err(format!("evalWithPrecedence: unexpected '{c}'")),
+            None => Err("evalWithPrecedence: unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn to_num(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    to_number(&args[0])
+}
+
+fn digits(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Number(n) => {
+            let mut n = n.unsigned_abs();
+            let mut out = Vec::new();
+            loop {
+                out.push((n % 10) as i64);
+                n /= 10;
+                if n == 0 {
+                    break;
+                }
+            }
+            out.reverse();
+            Ok(Value::NumArray(out))
+        }
+        _ => Err("digits expects a number".to_string()),
+    }
+}
+
+fn from_digits(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Array1D(items) => {
+            let mut n: i64 = 0;
+            for item in items {
+                match item {
+                    Value::Number(d @ 0..=9) => n = n.wrapping_mul(10).wrapping_add(*d),
+                    other => {
+                        return Err(format!("fromDigits: expected a digit, got {other}"))
+                    }
+                }
+            }
+            Ok(Value::Number(n))
+        }
+        Value::NumArray(nums) => {
+            let mut n: i64 = 0;
+            for d in nums {
+                match d {
+                    0..=9 => n = n.wrapping_mul(10).wrapping_add(*d),
+                    other => return Err(format!("fromDigits: expected a digit, got {other}")),
+                }
+            }
+            Ok(Value::Number(n))
+        }
+        _ => Err("fromDigits expects an array of digits".to_string()),
+    }
+}
+
+fn num_len(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Number(n) => {
+            let mut n = n.unsigned_abs();
+            let mut count = 1;
+            while n >= 10 {
+                n /= 10;
+                count += 1;
+            }
+            Ok(Value::Number(count))
+        }
+        _ => Err("numLen expects a number".to_string()),
+    }
+}
+
+/// Both regex builtins take `(text, pattern)` and render each match as
+/// `[whole, group1, ...]`; a group the match never entered renders as "".
+fn regex_args<'a>(name: &str, args: &'a [Value]) -> Result<(&'a str, crate::regex::Regex), String> {
+    match args {
+        [Value::Str(text), Value::Str(pattern)] => {
+            let regex = crate::regex::Regex::new(pattern).map_err(|e| format!("{name}: {e}"))?;
+            Ok((text, regex))
+        }
+        _ => Err(format!("{name} expects a string and a string pattern")),
+    }
+}
+
+fn capture_row(caps: Vec<Option<String>>) -> Vec<Value> {
+    caps.into_iter()
+        .map(|group| Value::Str(group.unwrap_or_default()))
+        .collect()
+}
+
+fn nums(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let text = match &args[0] {
+        Value::Str(s) => s.clone(),
+        // A char array (a grid row, say) scans like the string it spells.
+        Value::Array1D(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Str(s) => Ok(s.as_str()),
+                other => Err(format!(
+                    "nums: array elements must be strings, got {}",
+                    other.type_name()
+                )),
+            })
+            .collect::<Result<String, _>>()?,
+        other => Err(format!("nums expects a string, got {}", other.type_name()))?,
+    };
+    let mut out = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let negative = chars[i] == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+        if negative || chars[i].is_ascii_digit() {
+            let start = i;
+            i += usize::from(negative);
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            let n: i64 = digits
+                .parse()
+                .map_err(|_| format!("nums: number too large: {digits}"))?;
+            out.push(n);
+        } else {
+            i += 1;
+        }
+    }
+    Ok(Value::NumArray(out))
+}
+
+fn regex_match(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (text, regex) = regex_args("match", &args)?;
+    Ok(Value::Array1D(
+        regex.captures(text).map(capture_row).unwrap_or_default(),
+    ))
+}
+
+fn regex_match_all(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (text, regex) = regex_args("matchAll", &args)?;
+    let rows: Vec<Vec<Value>> = regex.captures_all(text).into_iter().map(capture_row).collect();
+    Ok(Value::Array2D(Rc::new(rows)))
+}
+
+fn split(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let pieces = |parts: Vec<&str>| {
+        Value::array(
+            parts
+                .into_iter()
+                .map(|part| Value::Str(part.to_string()))
+                .collect(),
+        )
+    };
+    match args.as_slice() {
+        [Value::Str(s)] => Ok(pieces(s.split_whitespace().collect())),
+        [Value::Str(s), Value::Str(delim)] => {
+            if delim.is_empty() {
+                return Err("split: the delimiter cannot be empty".to_string());
+            }
+            Ok(pieces(s.split(delim.as_str()).collect()))
+        }
+        _ => Err("split expects a string and an optional string delimiter".to_string()),
+    }
+}
+
+fn join(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Array1D(items), Value::Str(sep)] => {
+            let parts: Vec<String> = items.iter().map(Value::to_string).collect();
+            Ok(Value::Str(parts.join(sep)))
+        }
+        [Value::NumArray(nums), Value::Str(sep)] => {
+            let parts: Vec<String> = nums.iter().map(i64::to_string).collect();
+            Ok(Value::Str(parts.join(sep)))
+        }
+        _ => Err("join expects an array and a string separator".to_string()),
+    }
+}
+
+fn concat(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Array1D(items) => {
+            let mut out = String::new();
+            for item in items {
+                out.push_str(&item.to_string());
+            }
+            Ok(Value::Str(out))
+        }
+        Value::NumArray(nums) => {
+            let mut out = String::new();
+            for n in nums {
+                out.push_str(&n.to_string());
+            }
+            Ok(Value::Str(out))
+        }
+        _ => Err("concat expects an array".to_string()),
+    }
+}
+
+fn sum_of_digits(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Str(s) => Ok(Value::Number(
+            s.chars()
+                .filter_map(|c| c.to_digit(10))
+                .map(i64::from)
+                .sum(),
+        )),
+        _ => Err("sumOfDigits expects a string".to_string()),
+    }
+}
+
+/// Scanf-style extraction: `pat` is literal text with `{}` holes, and each
+/// hole captures the (possibly empty) text up to the next literal chunk.
+/// `extract("17x9: ok", "{}x{}: {}")` gives `["17", "9", "ok"]`.
+fn extract(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (s, pattern) = match args.as_slice() {
+        [Value::Str(s), Value::Str(pattern)] => (s, pattern),
+        _ => return Err("extract expects a string and a pattern string".to_string()),
+    };
+    let chunks: Vec<&str> = pattern.split("{}").collect();
+    let mut rest = s.as_str();
+    let mut captures = Vec::with_capacity(chunks.len() - 1);
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i == 0 {
+            rest = rest
+                .strip_prefix(chunk)
+                .ok_or_else(|| format!("extract: \"{s}\" does not start with \"{chunk}\""))?;
+            continue;
+        }
+        let captured = if chunk.is_empty() {
+            // A trailing (or doubled) hole takes everything that's left.
+            std::mem::take(&mut rest)
+        } else {
+            let at = rest
+                .find(chunk)
+                .ok_or_else(|| format!("extract: \"{s}\" has no \"{chunk}\" for hole {i}"))?;
+            let captured = &rest[..at];
+            rest = &rest[at + chunk.len()..];
+            captured
+        };
+        captures.push(Value::Str(captured.to_string()));
+    }
+    Ok(Value::Array1D(captures))
+}
+
+/// Materializes lazy and structured values as plain arrays so the array
+/// builtins compose with them. Arrays pass through unchanged.
+fn to_array(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Array1D(_) | Value::NumArray(_) => Ok(args[0].clone()),
+        Value::Range(r) => Ok(Value::NumArray(r.iter().collect())),
+        Value::Point(r, c) => Ok(Value::NumArray(vec![*r, *c])),
+        Value::Array2D(rows) => Ok(Value::Array1D(
+            rows.iter().cloned().map(Value::Array1D).collect(),
+        )),
+        Value::Grid(g) => Ok(Value::Array1D(
+            (0..g.rows()).map(|r| Value::Array1D(g.row_values(r))).collect(),
+        )),
+        Value::Sparse(grid) => {
+            // `[point, value]` pairs in row-major order, so output is stable.
+            let mut entries: Vec<(&(i64, i64), &Value)> = grid.cells.iter().collect();
+            entries.sort_by_key(|(&key, _)| key);
+            Ok(Value::Array1D(
+                entries
+                    .into_iter()
+                    .map(|(&(r, c), cell)| {
+                        Value::Array1D(vec![Value::Point(r, c), cell.clone()])
+                    })
+                    .collect(),
+            ))
+        }
+        Value::Set(set) => Ok(Value::array(deterministic_order(
+            set.items.iter().cloned().collect(),
+        ))),
+        Value::Str(s) => Ok(Value::Array1D(
+            s.chars().map(|c| Value::Str(c.to_string())).collect(),
+        )),
+        other => Err(format!("toArray: unsupported type {}", other.type_name())),
+    }
+}
+
+fn fill(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Number(n), Value::Number(value)] => {
+            let count = repeat_count(*n)?;
+            Ok(Value::NumArray(vec![*value; count]))
+        }
+        [Value::Number(n), value] => {
+            let count = repeat_count(*n)?;
+            Ok(Value::Array1D(vec![value.clone(); count]))
+        }
+        _ => Err("fill expects a count and a value".to_string()),
+    }
+}
+
+fn fill2d(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Number(rows), Value::Number(cols), value] => {
+            let rows = repeat_count(*rows)?;
+            let cols = repeat_count(*cols)?;
+            Ok(Value::Array2D(Rc::new(vec![vec![value.clone(); cols]; rows])))
+        }
+        _ => Err("fill2d expects row and column counts and a value".to_string()),
+    }
+}
+
+fn grid(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Str(text) => Ok(interp.grid_value(text)),
+        other => Err(format!(
+            "grid expects a multi-line string, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn render(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    render_text("render", &args).map(Value::Str)
+}
+
+fn animate(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let frame = render_text("animate", &args)?;
+    if let Some(delay) = interp.animation_delay() {
+        // Clear the screen and home the cursor before drawing the frame.
+        println!("\x1b[2J\x1b[H{frame}");
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+        std::thread::sleep(delay);
+    }
+    Ok(args.into_iter().next().expect("arity checked"))
+}
+
+/// The multi-line text form of a grid or sparse grid, shared by `render` and
+/// `animate`.
+fn render_text(builtin: &str, args: &[Value]) -> Result<String, String> {
+    match args {
+        [Value::Array2D(rows)] => {
+            let mut out = String::new();
+            for (i, row) in rows.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                for cell in row {
+                    out.push_str(&cell.to_string());
+                }
+            }
+            Ok(out)
+        }
+        [Value::Grid(g)] => {
+            let mut out = String::new();
+            for r in 0..g.rows() {
+                if r > 0 {
+                    out.push('\n');
+                }
+                for c in 0..g.cols() {
+                    out.push(g.get(r, c));
+                }
+            }
+            Ok(out)
+        }
+        [Value::Sparse(sg)] | [Value::Sparse(sg), _] => {
+            let empty = match args.get(1) {
+                Some(value) => value.to_string(),
+                None => sg.default.to_string(),
+            };
+            let Some(((min_r, min_c), (max_r, max_c))) = sg.bounds() else {
+                return Ok(String::new());
+            };
+            let mut out = String::new();
+            for r in min_r..=max_r {
+                if r > min_r {
+                    out.push('\n');
+                }
+                for c in min_c..=max_c {
+                    match sg.cells.get(&(r, c)) {
+                        Some(cell) => out.push_str(&cell.to_string()),
+                        None => out.push_str(&empty),
+                    }
+                }
+            }
+            Ok(out)
+        }
+        _ => Err(format!("{builtin} expects a 2d array or a sparse grid")),
+    }
+}
+
+fn generate(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Number(n), func] => {
+            let count = repeat_count(*n)?;
+            let func = func.clone();
+            let mut items = Vec::with_capacity(count);
+            for i in 0..count {
+                items.push(interp.call_fn_value(&func, vec![Value::Number(i as i64)])?);
+            }
+            Ok(Value::array(items))
+        }
+        _ => Err("generate expects a count and a function".to_string()),
+    }
+}
+
+/// Sorts with a fallible comparator, surfacing the first comparison error.
+fn sort_values<T>(
+    items: &mut [T],
+    mut key: impl FnMut(&T) -> Value,
+) -> Result<(), String> {
+    let mut error = None;
+    items.sort_by(|a, b| match compare_values(&key(a), &key(b)) {
+        Ok(ord) => ord,
+        Err(e) => {
+            error.get_or_insert(e);
+            std::cmp::Ordering::Equal
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn sort(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::NumArray(nums) => {
+            let mut nums = nums.clone();
+            nums.sort_unstable();
+            Ok(Value::NumArray(nums))
+        }
+        Value::Array1D(items) => {
+            let mut items = items.clone();
+            sort_values(&mut items, Value::clone)?;
+            Ok(Value::Array1D(items))
+        }
+        _ => Err("sort expects an array".to_string()),
+    }
+}
+
+fn map(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (items, func) = match args.as_slice() {
+        [Value::Array1D(items), func] => (items.clone(), func),
+        [Value::NumArray(nums), func] => (unpack(nums), func),
+        [Value::Range(r), func] => (r.iter().map(Value::Number).collect(), func),
+        _ => return Err("map expects an array and a function".to_string()),
+    };
+    let mut mapped = Vec::with_capacity(items.len());
+    for item in items {
+        mapped.push(interp.call_fn_value(func, vec![item])?);
+    }
+    Ok(Value::array(mapped))
+}
+
+fn filter(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (items, func) = match args.as_slice() {
+        [Value::Array1D(items), func] => (items.clone(), func),
+        [Value::NumArray(nums), func] => (unpack(nums), func),
+        [Value::Range(r), func] => (r.iter().map(Value::Number).collect(), func),
+        _ => return Err("filter expects an array and a function".to_string()),
+    };
+    let mut kept = Vec::new();
+    for item in items {
+        let verdict = interp.call_fn_value(func, vec![item.clone()])?;
+        if interp.is_truthy(&verdict) {
+            kept.push(item);
+        }
+    }
+    Ok(Value::array(kept))
+}
+
+fn reduce(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (items, func, init) = match args.as_slice() {
+        [Value::Array1D(items), func, init] => (items.clone(), func, init),
+        [Value::NumArray(nums), func, init] => (unpack(nums), func, init),
+        [Value::Range(r), func, init] => (r.iter().map(Value::Number).collect(), func, init),
+        _ => return Err("reduce expects an array, a function and an initial value".to_string()),
+    };
+    let mut acc = init.clone();
+    for item in items {
+        acc = interp.call_fn_value(func, vec![acc, item])?;
+    }
+    Ok(acc)
+}
+
+fn sort_by(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (items, keys, desc) = match args.as_slice() {
+        [Value::Array1D(items), keys, rest @ ..] => (items.clone(), keys.clone(), rest.first()),
+        [Value::NumArray(nums), keys, rest @ ..] => (unpack(nums), keys.clone(), rest.first()),
+        _ => return Err("sortBy expects an array and one or more key functions".to_string()),
+    };
+    // A single function sorts by it; an array of functions sorts by the
+    // first, breaking ties with the later ones in order.
+    let funcs = match keys {
+        Value::Array1D(funcs) => funcs,
+        single => vec![single],
+    };
+    if funcs.is_empty() {
+        return Err("sortBy: at least one key function is required".to_string());
+    }
+    let descending: Vec<bool> = match desc {
+        None => vec![false; funcs.len()],
+        Some(Value::Bool(flag)) => vec![*flag; funcs.len()],
+        Some(Value::Array1D(flags)) => flags
+            .iter()
+            .map(|flag| match flag {
+                Value::Bool(flag) => Ok(*flag),
+                other => Err(format!(
+                    "sortBy: descending flags must be bools, got {}",
+                    other.type_name()
+                )),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(other) => {
+            return Err(format!(
+                "sortBy: descending flags must be a bool or bool array, got {}",
+                other.type_name()
+            ))
+        }
+    };
+    if descending.len() != funcs.len() {
+        return Err(format!(
+            "sortBy: {} key function(s) but {} descending flag(s)",
+            funcs.len(),
+            descending.len()
+        ));
+    }
+    let mut keyed = Vec::with_capacity(items.len());
+    for item in items {
+        let mut item_keys = Vec::with_capacity(funcs.len());
+        for func in &funcs {
+            item_keys.push(interp.call_fn_value(func, vec![item.clone()])?);
+        }
+        keyed.push((item_keys, item));
+    }
+    // `sort_by` is stable, so equal key tuples keep their input order.
+    let mut error = None;
+    keyed.sort_by(|(a, _), (b, _)| {
+        for ((ka, kb), descending) in a.iter().zip(b).zip(&descending) {
+            match compare_values(ka, kb) {
+                Ok(std::cmp::Ordering::Equal) => continue,
+                Ok(ord) if *descending => return ord.reverse(),
+                Ok(ord) => return ord,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    return std::cmp::Ordering::Equal;
+                }
+            }
+        }
+        std::cmp::Ordering::Equal
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(Value::array(keyed.into_iter().map(|(_, v)| v).collect())),
+    }
+}
+
+fn reverse(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Array1D(items) => Ok(Value::Array1D(items.iter().rev().cloned().collect())),
+        Value::NumArray(nums) => Ok(Value::NumArray(nums.iter().rev().copied().collect())),
+        Value::Str(s) => Ok(Value::Str(s.chars().rev().collect())),
+        Value::Range(r) => Ok(Value::Range(r.reversed())),
+        other => Err(format!("reverse: unsupported type {}", other.type_name())),
+    }
+}
+
+fn rotate(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    /// Shifts `items` `n` places to the right; negative shifts go left, and
+    /// any magnitude wraps around the length.
+    fn rotated<T: Clone>(items: &[T], n: i64) -> Vec<T> {
+        if items.is_empty() {
+            return Vec::new();
+        }
+        let len = items.len() as i64;
+        let split = (len - n.rem_euclid(len)) as usize;
+        let mut out = Vec::with_capacity(items.len());
+        out.extend_from_slice(&items[split..]);
+        out.extend_from_slice(&items[..split]);
+        out
+    }
+    match args.as_slice() {
+        [Value::Array1D(items), Value::Number(n)] => Ok(Value::Array1D(rotated(items, *n))),
+        [Value::NumArray(nums), Value::Number(n)] => Ok(Value::NumArray(rotated(nums, *n))),
+        _ => Err("rotate expects an array and a number".to_string()),
+    }
+}
+
+fn split_at(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (items, i) = match args.as_slice() {
+        [Value::Array1D(items), Value::Number(i)] => (items.clone(), *i),
+        [Value::NumArray(nums), Value::Number(i)] => (unpack(nums), *i),
+        _ => return Err("splitAt expects an array and an index".to_string()),
+    };
+    // Out-of-range indices clamp, so `splitAt(arr, len(arr))` and beyond
+    // give an empty right half rather than erroring.
+    let i = i.clamp(0, items.len() as i64) as usize;
+    let (left, right) = items.split_at(i);
+    Ok(Value::Array1D(vec![
+        Value::array(left.to_vec()),
+        Value::array(right.to_vec()),
+    ]))
+}
+
+fn partition(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (items, pred) = match args.as_slice() {
+        [Value::Array1D(items), pred] => (items.clone(), pred.clone()),
+        [Value::NumArray(nums), pred] => (unpack(nums), pred.clone()),
+        _ => return Err("partition expects an array and a predicate".to_string()),
+    };
+    let mut matching = Vec::new();
+    let mut rest = Vec::new();
+    for item in items {
+        let keep = interp.call_fn_value(&pred, vec![item.clone()])?;
+        if interp.is_truthy(&keep) {
+            matching.push(item);
+        } else {
+            rest.push(item);
+        }
+    }
+    Ok(Value::Array1D(vec![
+        Value::array(matching),
+        Value::array(rest),
+    ]))
+}
+
+fn pairs(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let items = match &args[0] {
+        Value::Array1D(items) => items.clone(),
+        Value::NumArray(nums) => unpack(nums),
+        _ => return Err("pairs expects an array".to_string()),
+    };
+    let mut out = Vec::with_capacity(items.len() * items.len().saturating_sub(1) / 2);
+    for (i, a) in items.iter().enumerate() {
+        for b in &items[i + 1..] {
+            out.push(Value::array(vec![a.clone(), b.clone()]));
+        }
+    }
+    Ok(Value::Array1D(out))
+}
+
+fn contains(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Range(r), Value::Number(n)] => Ok(Value::Bool(r.contains(*n))),
+        [Value::Array1D(items), needle] => Ok(Value::Bool(
+            items.iter().any(|item| values_equal(item, needle)),
+        )),
+        [Value::NumArray(nums), Value::Number(n)] => Ok(Value::Bool(nums.contains(n))),
+        [Value::NumArray(nums), needle] => Ok(Value::Bool(
+            unpack(nums).iter().any(|item| values_equal(item, needle)),
+        )),
+        [Value::Str(s), Value::Str(needle)] => Ok(Value::Bool(s.contains(needle))),
+        [Value::Cache(cache), key] => Ok(Value::Bool(cache.borrow().contains(key))),
+        [Value::Set(set), item] => Ok(Value::Bool(set.items.contains(item))),
+        _ => Err("contains expects a range, array or string and a value".to_string()),
+    }
+}
+
+fn point(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Number(r), Value::Number(c)] => Ok(Value::Point(*r, *c)),
+        _ => Err("point expects 2 numbers".to_string()),
+    }
+}
+
+/// Pulls the `(r, c)` pairs out of an array of points.
+fn vertex_list(name: &str, value: &Value) -> Result<Vec<(i64, i64)>, String> {
+    let Value::Array1D(items) = value else {
+        return Err(format!("{name} expects an array of points"));
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Point(r, c) => Ok((*r, *c)),
+            other => Err(format!(
+                "{name}: expected a point, got a {}",
+                other.type_name()
+            )),
+        })
+        .collect()
+}
+
+fn polygon_area(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let pts = vertex_list("polygonArea", &args[0])?;
+    if pts.len() < 3 {
+        return Err("polygonArea: a polygon needs at least 3 points".to_string());
+    }
+    // Shoelace formula, accumulated in i128 so large coordinates don't
+    // overflow mid-sum. Lattice polygons whose edges follow the grid always
+    // have an integer area; otherwise the half-unit is rounded down.
+    let mut twice: i128 = 0;
+    for (i, &(r1, c1)) in pts.iter().enumerate() {
+        let (r2, c2) = pts[(i + 1) % pts.len()];
+        twice += c1 as i128 * r2 as i128 - c2 as i128 * r1 as i128;
+    }
+    let area = twice.abs() / 2;
+    i64::try_from(area)
+        .map(Value::Number)
+        .map_err(|_| "polygonArea: area overflows".to_string())
+}
+
+fn perimeter(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let pts = vertex_list("perimeter", &args[0])?;
+    if pts.len() < 2 {
+        return Err("perimeter: need at least 2 points".to_string());
+    }
+    let mut total: i64 = 0;
+    for (i, &(r1, c1)) in pts.iter().enumerate() {
+        let (r2, c2) = pts[(i + 1) % pts.len()];
+        let edge = (r2 - r1).abs().checked_add((c2 - c1).abs());
+        total = edge
+            .and_then(|e| total.checked_add(e))
+            .ok_or("perimeter: length overflows")?;
+    }
+    Ok(Value::Number(total))
+}
+
+fn point3(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Number(x), Value::Number(y), Value::Number(z)] => Ok(Value::Point3(*x, *y, *z)),
+        _ => Err("point3 expects 3 numbers".to_string()),
+    }
+}
+
+/// One of the 24 axis-aligned orientations: six facings of the x axis,
+/// each with four rolls around it. All are proper rotations, so applying
+/// every `i` in `0..24` hits each orientation exactly once.
+fn orient3(x: i64, y: i64, z: i64, i: i64) -> (i64, i64, i64) {
+    let (x, y, z) = match i / 4 {
+        0 => (x, y, z),
+        1 => (-x, -y, z),
+        2 => (y, -x, z),
+        3 => (-y, x, z),
+        4 => (z, y, -x),
+        _ => (-z, y, x),
+    };
+    match i % 4 {
+        0 => (x, y, z),
+        1 => (x, -z, y),
+        2 => (x, -y, -z),
+        _ => (x, z, -y),
+    }
+}
+
+fn rotate3(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Point3(x, y, z), Value::Number(i)] => {
+            if !(0..24).contains(i) {
+                return Err(format!("rotate3: rotation index {i} is not in [0..24]"));
+            }
+            let (x, y, z) = orient3(*x, *y, *z, *i);
+            Ok(Value::Point3(x, y, z))
+        }
+        _ => Err("rotate3 expects a 3d point and a number".to_string()),
+    }
+}
+
+fn rotations3(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Point3(x, y, z)] => Ok(Value::Array1D(
+            (0..24)
+                .map(|i| {
+                    let (x, y, z) = orient3(*x, *y, *z, i);
+                    Value::Point3(x, y, z)
+                })
+                .collect(),
+        )),
+        _ => Err("rotations3 expects a 3d point".to_string()),
+    }
+}
+
+/// The sign of the cross product of `b - a` and `c - a`: which side of the
+/// line through a and b the point c falls on.
+fn orientation(a: (i64, i64), b: (i64, i64), c: (i64, i64)) -> i32 {
+    let cross = (b.0 - a.0) as i128 * (c.1 - a.1) as i128
+        - (b.1 - a.1) as i128 * (c.0 - a.0) as i128;
+    match cross.cmp(&0) {
+        std::cmp::Ordering::Less => -1,
+        std::cmp::Ordering::Equal => 0,
+        std::cmp::Ordering::Greater => 1,
+    }
+}
+
+/// Whether c, known collinear with a-b, lies within the segment's box.
+fn on_segment(a: (i64, i64), b: (i64, i64), c: (i64, i64)) -> bool {
+    c.0 >= a.0.min(b.0) && c.0 <= a.0.max(b.0) && c.1 >= a.1.min(b.1) && c.1 <= a.1.max(b.1)
+}
+
+fn segments_intersect(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let [Value::Point(r1, c1), Value::Point(r2, c2), Value::Point(r3, c3), Value::Point(r4, c4)] =
+        args.as_slice()
+    else {
+        return Err("segmentsIntersect expects 4 points".to_string());
+    };
+    let (a, b, c, d) = ((*r1, *c1), (*r2, *c2), (*r3, *c3), (*r4, *c4));
+    let o1 = orientation(a, b, c);
+    let o2 = orientation(a, b, d);
+    let o3 = orientation(c, d, a);
+    let o4 = orientation(c, d, b);
+    let crossing = o1 != o2 && o3 != o4;
+    // Collinear endpoints touching the other segment also count.
+    let touching = (o1 == 0 && on_segment(a, b, c))
+        || (o2 == 0 && on_segment(a, b, d))
+        || (o3 == 0 && on_segment(c, d, a))
+        || (o4 == 0 && on_segment(c, d, b));
+    Ok(Value::Bool(crossing || touching))
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn points_on_line(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let [Value::Point(r1, c1), Value::Point(r2, c2)] = args.as_slice() else {
+        return Err("pointsOnLine expects 2 points".to_string());
+    };
+    let (dr, dc) = (r2 - r1, c2 - c1);
+    let steps = gcd(dr.abs(), dc.abs());
+    if steps == 0 {
+        return Ok(Value::Array1D(vec![Value::Point(*r1, *c1)]));
+    }
+    let (sr, sc) = (dr / steps, dc / steps);
+    let points = (0..=steps)
+        .map(|i| Value::Point(r1 + sr * i, c1 + sc * i))
+        .collect();
+    Ok(Value::Array1D(points))
+}
+
+fn sparse(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let grid = match args.as_slice() {
+        [default] => SparseGrid {
+            cells: HashMap::new(),
+            default: default.clone(),
+        },
+        [Value::Grid(g), default] => {
+            let mut cells = HashMap::new();
+            for r in 0..g.rows() {
+                for c in 0..g.cols() {
+                    let cell = Value::Str(g.get(r, c).to_string());
+                    if &cell != default {
+                        cells.insert((r as i64, c as i64), cell);
+                    }
+                }
+            }
+            SparseGrid {
+                cells,
+                default: default.clone(),
+            }
+        }
+        [Value::Array2D(rows), default] => {
+            // Dense cells matching the default stay implicit.
+            let mut cells = HashMap::new();
+            for (r, row) in rows.iter().enumerate() {
+                for (c, cell) in row.iter().enumerate() {
+                    if cell != default {
+                        cells.insert((r as i64, c as i64), cell.clone());
+                    }
+                }
+            }
+            SparseGrid {
+                cells,
+                default: default.clone(),
+            }
+        }
+        _ => return Err("sparse expects a default, or a 2d array and a default".to_string()),
+    };
+    Ok(Value::Sparse(Rc::new(grid)))
+}
+
+fn bounds(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Sparse(grid) => {
+            let ((min_r, min_c), (max_r, max_c)) = grid
+                .bounds()
+                .ok_or_else(|| "bounds: no cells set".to_string())?;
+            Ok(Value::Array1D(vec![
+                Value::Point(min_r, min_c),
+                Value::Point(max_r, max_c),
+            ]))
+        }
+        _ => Err("bounds expects a sparse grid".to_string()),
+    }
+}
+
+fn dense(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Sparse(grid) => {
+            let Some(((min_r, min_c), (max_r, max_c))) = grid.bounds() else {
+                return Ok(Value::Array2D(Rc::new(Vec::new())));
+            };
+            let rows = (min_r..=max_r)
+                .map(|r| {
+                    (min_c..=max_c)
+                        .map(|c| grid.cells.get(&(r, c)).unwrap_or(&grid.default).clone())
+                        .collect()
+                })
+                .collect();
+            Ok(Value::Array2D(Rc::new(rows)))
+        }
+        _ => Err("dense expects a sparse grid".to_string()),
+    }
+}
+
+fn bfs(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match bfs_search(interp, args, "bfs")? {
+        Some(path) => Ok(Value::Number(path.len() as i64 - 1)),
+        None => Ok(Value::Number(-1)),
+    }
+}
+
+fn bfs_path(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::array(
+        bfs_search(interp, args, "bfsPath")?.unwrap_or_default(),
+    ))
+}
+
+/// Breadth-first search over arbitrary state values. Returns the shortest
+/// start-to-goal chain, or `None` once every reachable state is exhausted.
+fn bfs_search(
+    interp: &mut Interpreter,
+    args: Vec<Value>,
+    builtin: &str,
+) -> Result<Option<Vec<Value>>, String> {
+    let [start, neighbors_fn, goal_fn] = args.as_slice() else {
+        return Err(format!(
+            "{builtin} expects a start state and 2 functions"
+        ));
+    };
+    let mut parents = MapVal::default();
+    let mut seen = SetVal::default();
+    seen.items.insert(start.clone());
+    let mut queue = VecDeque::from([start.clone()]);
+    while let Some(state) = queue.pop_front() {
+        let done = interp.call_fn_value(goal_fn, vec![state.clone()])?;
+        if interp.is_truthy(&done) {
+            let mut path = vec![state];
+            while let Some(prev) = parents.entries.get(path.last().expect("path is never empty")) {
+                path.push(prev.clone());
+            }
+            path.reverse();
+            return Ok(Some(path));
+        }
+        let next = interp.call_fn_value(neighbors_fn, vec![state.clone()])?;
+        let next = match next {
+            Value::Array1D(items) => items,
+            Value::NumArray(nums) => unpack(&nums),
+            other => {
+                return Err(format!(
+                    "{builtin}: the neighbor function must return an array, got {}",
+                    other.type_name()
+                ))
+            }
+        };
+        for neighbor in next {
+            if seen.items.insert(neighbor.clone()) {
+                parents.entries.insert(neighbor.clone(), state.clone());
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn grid_distances(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let [grid, Value::Point(sr, sc), passable] = args.as_slice() else {
+        return Err("gridDistances expects a 2d array, a point and a function".to_string());
+    };
+    enum Rows<'a> {
+        General(&'a [Vec<Value>]),
+        Packed(&'a CharGrid),
+    }
+    let rows = match grid {
+        Value::Array2D(rows) => Rows::General(rows),
+        Value::Grid(g) => Rows::Packed(g),
+        other => {
+            return Err(format!(
+                "gridDistances expects a 2d array, got {}",
+                other.type_name()
+            ))
+        }
+    };
+    let in_bounds = |r: i64, c: i64| match &rows {
+        Rows::General(rows) => {
+            r >= 0 && c >= 0 && (r as usize) < rows.len() && (c as usize) < rows[r as usize].len()
+        }
+        Rows::Packed(g) => r >= 0 && c >= 0 && (r as usize) < g.rows() && (c as usize) < g.cols(),
+    };
+    if !in_bounds(*sr, *sc) {
+        return Err(format!("gridDistances: start ({sr}, {sc}) is outside the grid"));
+    }
+    let mut dist: Vec<Vec<i64>> = match &rows {
+        Rows::General(rows) => rows.iter().map(|row| vec![-1; row.len()]).collect(),
+        Rows::Packed(g) => vec![vec![-1; g.cols()]; g.rows()],
+    };
+    let mut queue = VecDeque::from([(*sr, *sc)]);
+    dist[*sr as usize][*sc as usize] = 0;
+    while let Some((r, c)) = queue.pop_front() {
+        let d = dist[r as usize][c as usize];
+        for (dr, dc) in [(-1i64, 0i64), (1, 0), (0, -1), (0, 1)] {
+            let (nr, nc) = (r + dr, c + dc);
+            if !in_bounds(nr, nc) || dist[nr as usize][nc as usize] != -1 {
+                continue;
+            }
+            let cell = match &rows {
+                Rows::General(rows) => rows[nr as usize][nc as usize].clone(),
+                Rows::Packed(g) => Value::Str(g.get(nr as usize, nc as usize).to_string()),
+            };
+            let ok = interp.call_fn_value(passable, vec![cell])?;
+            if interp.is_truthy(&ok) {
+                dist[nr as usize][nc as usize] = d + 1;
+                queue.push_back((nr, nc));
+            }
+        }
+    }
+    let out = dist
+        .into_iter()
+        .map(|row| row.into_iter().map(Value::Number).collect())
+        .collect();
+    Ok(Value::Array2D(Rc::new(out)))
+}
+
+/// The `(rows, cols)` dimensions of either grid representation. Ragged
+/// `Array2D` grids report their first row's width.
+fn grid_dims(builtin: &str, value: &Value) -> Result<(usize, usize), String> {
+    match value {
+        Value::Grid(g) => Ok((g.rows(), g.cols())),
+        Value::Array2D(rows) => Ok((rows.len(), rows.first().map_or(0, Vec::len))),
+        other => Err(format!(
+            "{builtin} expects a 2d array, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn rows(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    grid_dims("rows", &args[0]).map(|(rows, _)| Value::Number(rows as i64))
+}
+
+fn cols(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    grid_dims("cols", &args[0]).map(|(_, cols)| Value::Number(cols as i64))
+}
+
+fn transpose(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Grid(g) => Ok(Value::Grid(Rc::new(g.transpose()))),
+        Value::Array2D(rows) => {
+            let cols = rows.first().map_or(0, Vec::len);
+            if rows.iter().any(|row| row.len() != cols) {
+                return Err("transpose: the rows differ in length".to_string());
+            }
+            let flipped = (0..cols)
+                .map(|c| rows.iter().map(|row| row[c].clone()).collect())
+                .collect();
+            Ok(Value::Array2D(Rc::new(flipped)))
+        }
+        other => Err(format!(
+            "transpose expects a 2d array, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn in_bounds(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let (dims, r, c) = match args.as_slice() {
+        [grid, Value::Point(r, c)] => (grid_dims("inBounds", grid)?, *r, *c),
+        [grid, Value::Number(r), Value::Number(c)] => (grid_dims("inBounds", grid)?, *r, *c),
+        _ => return Err("inBounds expects a 2d array and a cell position".to_string()),
+    };
+    Ok(Value::Bool(
+        r >= 0 && c >= 0 && (r as usize) < dims.0 && (c as usize) < dims.1,
+    ))
+}
+
+fn find2d(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Array2D(rows), needle] => {
+            for (r, row) in rows.iter().enumerate() {
+                for (c, cell) in row.iter().enumerate() {
+                    if cell == needle {
+                        return Ok(Value::Point(r as i64, c as i64));
+                    }
+                }
+            }
+            Ok(Value::Point(-1, -1))
+        }
+        [Value::Grid(g), Value::Str(needle)] => {
+            let mut chars = needle.chars();
+            let (needle, rest) = (chars.next(), chars.next());
+            for r in 0..g.rows() {
+                for c in 0..g.cols() {
+                    if rest.is_none() && needle == Some(g.get(r, c)) {
+                        return Ok(Value::Point(r as i64, c as i64));
+                    }
+                }
+            }
+            Ok(Value::Point(-1, -1))
+        }
+        _ => Err("find2d expects a 2d array and a value".to_string()),
+    }
+}
+
+fn graph(_interp: &mut Interpreter, _args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Graph(Rc::new(Graph::default())))
+}
+
+fn add_edge(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Graph(g), from, to] => {
+            let mut g = (**g).clone();
+            g.add_edge(from.clone(), to.clone());
+            Ok(Value::Graph(Rc::new(g)))
+        }
+        _ => Err("addEdge expects a graph and 2 node values".to_string()),
+    }
+}
+
+fn nodes(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Graph(g) => Ok(Value::array(g.nodes().to_vec())),
+        _ => Err("nodes expects a graph".to_string()),
+    }
+}
+
+fn connected_components(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Graph(g) => Ok(Value::Array1D(
+            g.connected_components()
+                .into_iter()
+                .map(Value::array)
+                .collect(),
+        )),
+        _ => Err("connectedComponents expects a graph".to_string()),
+    }
+}
+
+fn topo_sort(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Graph(g) => g
+            .topo_sort()
+            .map(Value::array)
+            .ok_or_else(|| "topoSort: graph has a cycle".to_string()),
+        _ => Err("topoSort expects a graph".to_string()),
+    }
+}
+
+fn max_flow(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Graph(g), s, t] => {
+            if values_equal(s, t) {
+                return Err("maxFlow: source and sink must differ".to_string());
+            }
+            g.max_flow(s, t)
+                .map(Value::Number)
+                .ok_or_else(|| format!("maxFlow: {s} or {t} is not in the graph"))
+        }
+        _ => Err("maxFlow expects a graph and 2 node values".to_string()),
+    }
+}
+
+fn min_cut(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Graph(g) => {
+            let (near, far) = g
+                .min_cut()
+                .ok_or_else(|| "minCut: graph needs at least 2 nodes".to_string())?;
+            Ok(Value::Array1D(vec![Value::array(near), Value::array(far)]))
+        }
+        _ => Err("minCut expects a graph".to_string()),
+    }
+}
+
+fn deadline(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Number(ms), block @ Value::FnRef(_), fallback] => {
+            let ms = u64::try_from(*ms).map_err(|_| "deadline: budget must be >= 0".to_string())?;
+            interp.eval_with_deadline(std::time::Duration::from_millis(ms), block, fallback)
+        }
+        _ => Err("deadline expects a millisecond budget, a function and a fallback".to_string()),
+    }
+}
+
+fn hash(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Number(stable_hash(&args[0]) as i64))
+}
+
+fn bitset(_interp: &mut Interpreter, _args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Bitset(Rc::new(BitSet::default())))
+}
+
+/// The bit index from a `set`/`get` argument, which must be non-negative.
+fn bit_index(builtin: &str, value: &Value) -> Result<usize, String> {
+    match value {
+        Value::Number(n) if *n >= 0 => Ok(*n as usize),
+        Value::Number(n) => Err(format!("{builtin}: bit index {n} is negative")),
+        other => Err(format!(
+            "{builtin} expects a bit index number, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn set(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Bitset(bits), i] => {
+            let i = bit_index("set", i)?;
+            let mut bits = (**bits).clone();
+            bits.set(i);
+            Ok(Value::Bitset(Rc::new(bits)))
+        }
+        [source] => {
+            let mut set = SetVal::default();
+            match source {
+                Value::Set(_) => return Ok(source.clone()),
+                Value::Array1D(items) => set.items.extend(items.iter().cloned()),
+                Value::NumArray(nums) => set.items.extend(nums.iter().map(|&n| Value::Number(n))),
+                Value::Range(r) => set.items.extend(r.iter().map(Value::Number)),
+                other => {
+                    return Err(format!(
+                        "set expects an array or a bitset, got {}",
+                        other.type_name()
+                    ))
+                }
+            }
+            Ok(Value::Set(Rc::new(set)))
+        }
+        _ => Err("set expects an array, or a bitset and a bit index".to_string()),
+    }
+}
+
+fn set_arg<'a>(builtin: &str, value: &'a Value) -> Result<&'a SetVal, String> {
+    match value {
+        Value::Set(set) => Ok(set),
+        other => Err(format!(
+            "{builtin} expects a set, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn add(_interp: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, String> {
+    let item = args.pop().expect("arity checked");
+    if let Value::RangeSet(rs) = &args[0] {
+        let (start, end) = interval_arg("add", &item)?;
+        let mut rs = (**rs).clone();
+        rs.insert(start, end);
+        return Ok(Value::RangeSet(Rc::new(rs)));
+    }
+    let mut set = set_arg("add", &args[0])?.clone();
+    set.items.insert(item);
+    Ok(Value::Set(Rc::new(set)))
+}
+
+fn remove(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    if let Value::RangeSet(rs) = &args[0] {
+        let (start, end) = interval_arg("remove", &args[1])?;
+        let mut rs = (**rs).clone();
+        rs.remove(start, end);
+        return Ok(Value::RangeSet(Rc::new(rs)));
+    }
+    let mut set = set_arg("remove", &args[0])?.clone();
+    set.items.remove(&args[1]);
+    Ok(Value::Set(Rc::new(set)))
+}
+
+/// The `[start, end)` bounds of a step-1 range argument.
+fn interval_arg(builtin: &str, value: &Value) -> Result<(i64, i64), String> {
+    match value {
+        Value::Range(r) if r.step == 1 => Ok((r.start, r.start + r.len)),
+        Value::Range(_) => Err(format!("{builtin}: a range set takes step-1 ranges")),
+        other => Err(format!(
+            "{builtin}: expected a range like [a..b], got a {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn range_set(_interp: &mut Interpreter, _args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::RangeSet(Rc::new(RangeSet::default())))
+}
+
+fn covered(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::RangeSet(rs) => rs.covered().map(Value::Number),
+        other => Err(format!("covered expects a range set, got {}", other.type_name())),
+    }
+}
+
+fn spans(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::RangeSet(rs) => Ok(Value::Array1D(
+            rs.intervals()
+                .iter()
+                .map(|&(start, end)| Value::Range(crate::interpreter::RangeVal::new(start, end)))
+                .collect(),
+        )),
+        other => Err(format!("spans expects a range set, got {}", other.type_name())),
+    }
+}
+
+/// Applies an element-wise combining operation to two set arguments.
+fn set_op(
+    builtin: &str,
+    args: &[Value],
+    op: impl Fn(&SetVal, &SetVal) -> SetVal,
+) -> Result<Value, String> {
+    match args {
+        [Value::Set(a), Value::Set(b)] => Ok(Value::Set(Rc::new(op(a, b)))),
+        _ => Err(format!("{builtin} expects two sets")),
+    }
+}
+
+fn union(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    set_op("union", &args, |a, b| SetVal {
+        items: a.items.union(&b.items).cloned().collect(),
+    })
+}
+
+fn intersect(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    if let Some((a, b)) = array_operands(&args) {
+        return Ok(filter_by_counts(a, b, true));
+    }
+    set_op("intersect", &args, |a, b| SetVal {
+        items: a.items.intersection(&b.items).cloned().collect(),
+    })
+}
+
+fn difference(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    if let Some((a, b)) = array_operands(&args) {
+        return Ok(filter_by_counts(a, b, false));
+    }
+    set_op("difference", &args, |a, b| SetVal {
+        items: a.items.difference(&b.items).cloned().collect(),
+    })
+}
+
+/// Both arguments as plain element lists, when both are 1d arrays.
+fn array_operands(args: &[Value]) -> Option<(Vec<Value>, Vec<Value>)> {
+    let elements = |value: &Value| match value {
+        Value::Array1D(items) => Some(items.clone()),
+        Value::NumArray(nums) => Some(unpack(nums)),
+        _ => None,
+    };
+    Some((elements(&args[0])?, elements(&args[1])?))
+}
+
+/// The order-preserving, multiset-aware array forms of `intersect` and
+/// `difference`: each element of `b` cancels at most one equal element of
+/// `a`, and surviving elements keep their order in `a`.
+fn filter_by_counts(a: Vec<Value>, b: Vec<Value>, keep_matched: bool) -> Value {
+    let mut counts = MapVal::default();
+    for item in b {
+        match counts.entries.entry(item) {
+            std::collections::hash_map::Entry::Occupied(mut entry) => {
+                if let Value::Number(n) = entry.get_mut() {
+                    *n += 1;
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(Value::Number(1));
+            }
+        }
+    }
+    let mut out = Vec::new();
+    for item in a {
+        let matched = match counts.entries.get_mut(&item) {
+            Some(Value::Number(n)) if *n > 0 => {
+                *n -= 1;
+                true
+            }
+            _ => false,
+        };
+        if matched == keep_matched {
+            out.push(item);
+        }
+    }
+    Value::array(out)
+}
+
+fn get(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Bitset(bits) => Ok(Value::Bool(bits.get(bit_index("get", &args[1])?))),
+        Value::Cache(cache) => cache
+            .borrow_mut()
+            .get(&args[1])
+            .ok_or_else(|| format!("get: key {} is not in the cache", args[1])),
+        other => Err(format!(
+            "get expects a bitset or a cache, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+/// `getOr` routes through the same indexing the `x[i]` syntax uses, so it
+/// accepts exactly the containers and index kinds indexing does — it just
+/// swaps any failure for the default.
+fn get_or(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let mut args = args.into_iter();
+    let (base, index, default) = (
+        args.next().unwrap(),
+        args.next().unwrap(),
+        args.next().unwrap(),
+    );
+    Ok(interp.index_value(base, index).unwrap_or(default))
+}
+
+fn cache(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args[0] {
+        Value::Number(n) if n > 0 => Ok(Value::Cache(Rc::new(RefCell::new(LruCache::new(
+            n as usize,
+        ))))),
+        Value::Number(n) => Err(format!("cache: capacity {n} must be positive")),
+        ref other => Err(format!(
+            "cache expects a capacity number, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn put(_interp: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, String> {
+    let value = args.pop().expect("arity checked");
+    let key = args.pop().expect("arity checked");
+    match &args[0] {
+        Value::Cache(c) => {
+            c.borrow_mut().put(key, value);
+            Ok(args.pop().expect("arity checked"))
+        }
+        Value::Map(map) => {
+            let mut map = (**map).clone();
+            map.entries.insert(key, value);
+            Ok(Value::Map(Rc::new(map)))
+        }
+        other => Err(format!(
+            "put expects a cache or a map, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn map_arg<'a>(builtin: &str, value: &'a Value) -> Result<&'a MapVal, String> {
+    match value {
+        Value::Map(map) => Ok(map),
+        other => Err(format!(
+            "{builtin} expects a map, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+/// Values in a deterministic order: by value when mutually comparable, by
+/// rendered text otherwise. Map keys and set elements come out through this,
+/// since their hash iteration order is unspecified.
+fn deterministic_order(mut values: Vec<Value>) -> Vec<Value> {
+    if sort_values(&mut values, Value::clone).is_err() {
+        values.sort_by_key(|value| value.to_string());
+    }
+    values
+}
+
+/// A map's keys in a deterministic order.
+fn sorted_keys(map: &MapVal) -> Vec<Value> {
+    deterministic_order(map.entries.keys().cloned().collect())
+}
+
+fn keys(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::array(sorted_keys(map_arg("keys", &args[0])?)))
+}
+
+fn values(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let map = map_arg("values", &args[0])?;
+    let values = sorted_keys(map)
+        .iter()
+        .map(|key| map.entries[key].clone())
+        .collect();
+    Ok(Value::array(values))
+}
+
+fn has(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Map(map) => Ok(Value::Bool(map.entries.contains_key(&args[1]))),
+        Value::Set(set) => Ok(Value::Bool(set.items.contains(&args[1]))),
+        Value::RangeSet(rs) => match &args[1] {
+            Value::Number(n) => Ok(Value::Bool(rs.contains(*n))),
+            other => Err(format!("has: a range set holds numbers, not {}", other.type_name())),
+        },
+        other => Err(format!(
+            "has expects a map or a set, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn del(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let mut map = map_arg("del", &args[0])?.clone();
+    map.entries.remove(&args[1]);
+    Ok(Value::Map(Rc::new(map)))
+}
+
+fn count(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Bitset(bits) => Ok(Value::Number(bits.count() as i64)),
+        other => Err(format!("count expects a bitset, got {}", other.type_name())),
+    }
+}
+
+/// Applies a word-wise combining operation to two bitset arguments.
+fn bit_op(
+    builtin: &str,
+    args: &[Value],
+    op: impl Fn(u64, u64) -> u64,
+) -> Result<Value, String> {
+    match args {
+        [Value::Bitset(a), Value::Bitset(b)] => Ok(Value::Bitset(Rc::new(a.combine(b, op)))),
+        _ => Err(format!("{builtin} expects two bitsets")),
+    }
+}
+
+fn and(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    bit_op("and", &args, |a, b| a & b)
+}
+
+fn or(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    bit_op("or", &args, |a, b| a | b)
+}
+
+fn xor(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    bit_op("xor", &args, |a, b| a ^ b)
+}
+
+/// The function-name string from a memo builtin's argument, also accepting a
+/// bare `f` function reference.
+fn memo_fn_name(builtin: &str, arg: &Value) -> Result<String, String> {
+    match arg {
+        Value::Str(name) => Ok(name.clone()),
+        Value::FnRef(name) => Ok(name.to_string()),
+        other => Err(format!(
+            "{builtin} expects a function name string, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn memo_stats(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let name = memo_fn_name("memoStats", &args[0])?;
+    let (hits, misses, entries) = interp
+        .memo_stats(&name)
+        .map_err(|e| format!("memoStats: {e}"))?;
+    Ok(Value::array(vec![
+        Value::Number(hits as i64),
+        Value::Number(misses as i64),
+        Value::Number(entries as i64),
+    ]))
+}
+
+fn memo_clear(interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let name = memo_fn_name("memoClear", &args[0])?;
+    let freed = interp
+        .memo_clear(&name)
+        .map_err(|e| format!("memoClear: {e}"))?;
+    Ok(Value::Number(freed as i64))
+}
+
+fn neighbors(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Graph(g), node] => {
+            let neighbors = g
+                .neighbors_of(node)
+                .ok_or_else(|| format!("neighbors: {node} is not in the graph"))?;
+            Ok(Value::array(neighbors))
+        }
+        [Value::Array2D(rows), Value::Number(r), Value::Number(c)] => {
+            let mut out = Vec::new();
+            for (dr, dc) in [(-1i64, 0i64), (1, 0), (0, -1), (0, 1)] {
+                let (nr, nc) = (r + dr, c + dc);
+                if nr >= 0
+                    && nc >= 0
+                    && (nr as usize) < rows.len()
+                    && (nc as usize) < rows[nr as usize].len()
+                {
+                    out.push(Value::Point(nr, nc));
+                }
+            }
+            Ok(Value::Array1D(out))
+        }
+        [Value::Grid(g), Value::Number(r), Value::Number(c)] => {
+            let mut out = Vec::new();
+            for (dr, dc) in [(-1i64, 0i64), (1, 0), (0, -1), (0, 1)] {
+                let (nr, nc) = (r + dr, c + dc);
+                if nr >= 0 && nc >= 0 && (nr as usize) < g.rows() && (nc as usize) < g.cols() {
+                    out.push(Value::Point(nr, nc));
+                }
+            }
+            Ok(Value::Array1D(out))
+        }
+        _ => Err("neighbors expects a 2d array and 2 numbers".to_string()),
+    }
+}
+
+fn neighbors4(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    neighbor_points("neighbors4", &args, &[(-1, 0), (1, 0), (0, -1), (0, 1)])
+}
+
+fn neighbors8(_interp: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    #[rustfmt::skip]
+    let deltas = [
+        (-1, -1), (-1, 0), (-1, 1),
+        (0, -1), (0, 1),
+        (1, -1), (1, 0), (1, 1),
+    ];
+    neighbor_points("neighbors8", &args, &deltas)
+}
+
+/// Shared bounds checking for `neighbors4` / `neighbors8`. The first argument
+/// is either a grid or a `(rows, cols)` dimension point.
+fn neighbor_points(builtin: &str, args: &[Value], deltas: &[(i64, i64)]) -> Result<Value, String> {
+    let (bounds, r, c) = match args {
+        [bounds, Value::Point(r, c)] => (bounds, *r, *c),
+        [bounds, Value::Number(r), Value::Number(c)] => (bounds, *r, *c),
+        _ => {
+            return Err(format!(
+                "{builtin} expects a grid or dimensions and a cell position"
+            ))
+        }
+    };
+    let (rows, cols) = match bounds {
+        Value::Point(rows, cols) => {
+            if *rows < 0 || *cols < 0 {
+                return Err(format!("{builtin}: dimensions must not be negative"));
+            }
+            (*rows as usize, *cols as usize)
+        }
+        other => grid_dims(builtin, other)?,
+    };
+    let mut out = Vec::new();
+    for (dr, dc) in deltas {
+        let (nr, nc) = (r + dr, c + dc);
+        if nr >= 0 && nc >= 0 && (nr as usize) < rows && (nc as usize) < cols {
+            out.push(Value::Point(nr, nc));
+        }
+    }
+    Ok(Value::Array1D(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_finds_registered_builtins() {
+        assert!(is_builtin("len"));
+        assert!(is_builtin("sortBy"));
+        assert!(!is_builtin("nope"));
+    }
+
+    #[test]
+    fn arity_is_checked_from_the_spec() {
+        let mut interp = Interpreter::new();
+        let err = lookup("len")
+            .unwrap()
+            .call(&mut interp, vec![])
+            .unwrap_err();
+        assert!(err.contains("len expects 1 argument(s), got 0"), "{err}");
+    }
+}
@@ -0,0 +1,81 @@
+//! Structured errors carrying a phase and a source position.
+//!
+//! Everything the crate reports to a caller is an [`XmasError`]: which phase
+//! failed, where in the source when that is known, and the message itself.
+//! The [`Display`](std::fmt::Display) form is `line L, col C: message`, the
+//! same text the crate produced when errors were bare strings, so existing
+//! callers that only print the error are unaffected; callers that want the
+//! position — like the CLI's caret diagnostics — read `span` directly
+//! instead of re-parsing the message.
+
+use std::fmt;
+
+/// A position in the program source, 1-based.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Which phase produced an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    Lex,
+    Parse,
+    Runtime,
+}
+
+/// An error from lexing, parsing or running a program.
+///
+/// Lex and parse errors point at the offending token. Runtime errors carry
+/// the line of the statement that was executing when the error arose
+/// (column 1), since the AST records positions per statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct XmasError {
+    pub kind: ErrorKind,
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl XmasError {
+    /// An error with no position.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> XmasError {
+        XmasError {
+            kind,
+            span: None,
+            message: message.into(),
+        }
+    }
+
+    /// Attaches a position to the error.
+    pub fn at(mut self, line: usize, col: usize) -> XmasError {
+        self.span = Some(Span { line, col });
+        self
+    }
+}
+
+impl fmt::Display for XmasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(Span { line, col }) => {
+                write!(f, "line {line}, col {col}: {}", self.message)
+            }
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for XmasError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_matches_the_historical_string_form() {
+        let err = XmasError::new(ErrorKind::Parse, "unexpected token RBrace").at(3, 7);
+        assert_eq!(err.to_string(), "line 3, col 7: unexpected token RBrace");
+        let err = XmasError::new(ErrorKind::Runtime, "undefined variable: x");
+        assert_eq!(err.to_string(), "undefined variable: x");
+    }
+}
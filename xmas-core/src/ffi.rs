@@ -0,0 +1,132 @@
+//! C ABI embedding layer, behind the `ffi` feature.
+//!
+//! Non-Rust tooling can run a program and read back the formatted result:
+//!
+//! ```c
+//! char out[256];
+//! if (xmas_run(source, input, out, sizeof out) != 0) {
+//!     xmas_last_error(out, sizeof out);  /* fetch the error message */
+//! }
+//! ```
+//!
+//! All strings are NUL-terminated UTF-8. `input` may be NULL.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+
+use crate::run_source;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(message: &str) {
+    let sanitized = message.replace('\0', " ");
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(sanitized).unwrap_or_default());
+}
+
+/// Copies `text` into `buf` (capacity `len`), truncating if needed, always
+/// NUL-terminating. Returns the untruncated length.
+unsafe fn fill_buffer(text: &str, buf: *mut c_char, len: usize) -> usize {
+    if buf.is_null() || len == 0 {
+        return text.len();
+    }
+    let bytes = text.as_bytes();
+    let copy = bytes.len().min(len - 1);
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf.cast(), copy);
+    *buf.add(copy) = 0;
+    text.len()
+}
+
+/// Runs `source` with optional `input`, writing the formatted result value to
+/// `out_buf`. Returns 0 on success, 1 if the program set no result, and -1 on
+/// error (retrieve it with [`xmas_last_error`]).
+///
+/// # Safety
+///
+/// `source` must be a valid NUL-terminated string; `input` must be the same
+/// or NULL; `out_buf` must point to at least `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn xmas_run(
+    source: *const c_char,
+    input: *const c_char,
+    out_buf: *mut c_char,
+    out_len: usize,
+) -> c_int {
+    if source.is_null() {
+        set_last_error("source must not be NULL");
+        return -1;
+    }
+    let source = match CStr::from_ptr(source).to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error("source is not valid UTF-8");
+            return -1;
+        }
+    };
+    let input = if input.is_null() {
+        None
+    } else {
+        match CStr::from_ptr(input).to_str() {
+            Ok(s) => Some(s),
+            Err(_) => {
+                set_last_error("input is not valid UTF-8");
+                return -1;
+            }
+        }
+    };
+    match run_source(source, input) {
+        Ok(Some(value)) => {
+            fill_buffer(&value.to_string(), out_buf, out_len);
+            0
+        }
+        Ok(None) => {
+            fill_buffer("", out_buf, out_len);
+            1
+        }
+        Err(err) => {
+            set_last_error(&err.to_string());
+            -1
+        }
+    }
+}
+
+/// Copies the most recent error message on this thread into `buf`,
+/// truncating if needed. Returns the untruncated message length.
+///
+/// # Safety
+///
+/// `buf` must point to at least `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn xmas_last_error(buf: *mut c_char, len: usize) -> usize {
+    LAST_ERROR.with(|e| {
+        let error = e.borrow();
+        fill_buffer(&error.to_string_lossy(), buf, len)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_a_program_over_the_c_abi() {
+        let source = CString::new("_ = 6 * 7").unwrap();
+        let mut out = [0 as c_char; 64];
+        let rc = unsafe { xmas_run(source.as_ptr(), std::ptr::null(), out.as_mut_ptr(), 64) };
+        assert_eq!(rc, 0);
+        let result = unsafe { CStr::from_ptr(out.as_ptr()) };
+        assert_eq!(result.to_str().unwrap(), "42");
+    }
+
+    #[test]
+    fn surfaces_errors() {
+        let source = CString::new("_ = 1 / 0").unwrap();
+        let mut out = [0 as c_char; 64];
+        let rc = unsafe { xmas_run(source.as_ptr(), std::ptr::null(), out.as_mut_ptr(), 64) };
+        assert_eq!(rc, -1);
+        unsafe { xmas_last_error(out.as_mut_ptr(), 64) };
+        let error = unsafe { CStr::from_ptr(out.as_ptr()) };
+        assert!(error.to_str().unwrap().contains("division by zero"));
+    }
+}
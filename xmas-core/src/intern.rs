@@ -0,0 +1,82 @@
+//! Identifier interning.
+//!
+//! Identifiers become small copyable [`Symbol`] ids at lex time, so the
+//! interpreter's variable and function maps hash and compare a `u32` instead
+//! of a `String` on every access. The interner is thread-local: tokens,
+//! programs and interpreters all live on the thread that lexed them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// An interned identifier. Copyable; equality and hashing use the id.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    names: Vec<Rc<str>>,
+    ids: HashMap<Rc<str>, u32>,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl Symbol {
+    /// Interns `name`; the same spelling always yields the same id.
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(&id) = interner.ids.get(name) {
+                return Symbol(id);
+            }
+            let id = u32::try_from(interner.names.len()).expect("interner overflow");
+            let name: Rc<str> = Rc::from(name);
+            interner.names.push(Rc::clone(&name));
+            interner.ids.insert(name, id);
+            Symbol(id)
+        })
+    }
+
+    /// The symbol's dense id, usable as a direct index into per-symbol
+    /// storage.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The symbol with dense id `index`; the inverse of [`Symbol::index`]
+    /// for walking per-symbol storage.
+    pub(crate) fn from_index(index: usize) -> Symbol {
+        Symbol(u32::try_from(index).expect("interner overflow"))
+    }
+
+    /// The identifier's spelling. Cheap — a refcount bump, not a copy.
+    pub fn name(self) -> Rc<str> {
+        INTERNER.with(|interner| Rc::clone(&interner.borrow().names[self.0 as usize]))
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Symbol({})", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_spelling_same_symbol() {
+        assert_eq!(Symbol::intern("abc"), Symbol::intern("abc"));
+        assert_ne!(Symbol::intern("abc"), Symbol::intern("abd"));
+        assert_eq!(&*Symbol::intern("abc").name(), "abc");
+    }
+}
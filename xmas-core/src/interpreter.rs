@@ -0,0 +1,3068 @@
+//! Tree-walking interpreter for xmas programs.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::ast::{BinOp, Block, Expr, FnBody, Pattern, Stmt, UnaryOp};
+use crate::builtins;
+use crate::error::{ErrorKind, XmasError};
+use crate::intern::Symbol;
+use crate::snapshot;
+use crate::visit;
+
+/// Receives serialized checkpoint text; the CLI points this at a file.
+pub type CheckpointSink = Box<dyn FnMut(&str) -> Result<(), String>>;
+
+/// The error message `deadline()` uses to unwind out of an over-budget
+/// block; distinctive so it can't be confused with an ordinary failure.
+const DEADLINE_EXCEEDED: &str = "deadline: time budget exceeded";
+
+/// A runtime value.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(i64),
+    Str(String),
+    Bool(bool),
+    Array1D(Vec<Value>),
+    /// An all-number array stored packed, so arithmetic-heavy pipelines skip
+    /// one enum tag and one heap box per element. A representation detail,
+    /// not a type: it equals, prints and behaves like the `Array1D` spelling
+    /// the same numbers, and operations that introduce a non-number fall
+    /// back to the general form.
+    NumArray(Vec<i64>),
+    /// Rows are shared copy-on-write: cloning a grid bumps a refcount, and
+    /// indexed writes clone the rows only when they are actually shared.
+    Array2D(Rc<Vec<Vec<Value>>>),
+    /// A rectangular character grid packed into one flat buffer — one
+    /// `char` per cell instead of a `Value::Str` allocation each. Like
+    /// [`Value::NumArray`], a representation detail rather than a type: it
+    /// equals, prints and behaves like the `Array2D` of 1-char strings
+    /// spelling the same cells, and writes that store anything else fall
+    /// back to the general form.
+    Grid(Rc<CharGrid>),
+    /// A lazy range; never materialized, so `[1..1000000000]` is cheap to
+    /// query with `len`, `contains`, indexing and `reverse`.
+    Range(RangeVal),
+    /// A grid coordinate `(row, col)`. Compares equal to the two-element
+    /// array `[row, col]` and is accepted anywhere `[r, c]` indexes a grid.
+    Point(i64, i64),
+    /// A 3D coordinate `(x, y, z)`, for puzzles that leave the plane.
+    /// Compares equal to the three-element array `[x, y, z]`.
+    Point3(i64, i64, i64),
+    /// A grid keyed by point with a default for unset cells, for coordinate
+    /// spaces far too large to materialize. Shared copy-on-write like
+    /// [`Value::Array2D`].
+    Sparse(Rc<SparseGrid>),
+    /// A directed graph over arbitrary node values, built with `addEdge`.
+    /// Shared copy-on-write like [`Value::Array2D`].
+    Graph(Rc<Graph>),
+    /// A packed set of bit indices, for visited-state tracking over index
+    /// spaces where an array of booleans is too heavy. Shared copy-on-write
+    /// like [`Value::Array2D`].
+    Bitset(Rc<BitSet>),
+    /// Sorted, disjoint half-open i64 intervals, built with `rangeSet()`
+    /// and `add(rs, [a..b])`. Covers huge spans in memory proportional to
+    /// the number of intervals, not cells. Shared copy-on-write like
+    /// [`Value::Array2D`].
+    RangeSet(Rc<RangeSet>),
+    /// A capacity-capped map evicting its least-recently-used entry, for
+    /// search memo tables too large to keep whole. Unlike the other
+    /// containers this shares one mutable store: copies are the same cache,
+    /// so `put` doesn't defeat the point by duplicating it.
+    Cache(Rc<RefCell<LruCache>>),
+    /// Key-value entries over arbitrary hashable keys, written
+    /// `{"a": 1, "b": 2}`. Shared copy-on-write like [`Value::Array2D`].
+    Map(Rc<MapVal>),
+    /// Distinct values with O(1) membership, built with `set(arr)`. Shared
+    /// copy-on-write like [`Value::Array2D`].
+    Set(Rc<SetVal>),
+    /// A reference to a user-defined function, for builtins that take one.
+    FnRef(Symbol),
+}
+
+/// Backing store of a [`Value::Cache`]: a map holding at most `capacity`
+/// entries, dropping the least recently used one when full. Recency is a
+/// queue of stamped keys; stale stamps are discarded lazily as they surface,
+/// so `put` and `get` stay amortized O(1).
+#[derive(Debug)]
+pub struct LruCache {
+    capacity: usize,
+    entries: HashMap<Value, (Value, u64)>,
+    recency: VecDeque<(Value, u64)>,
+    clock: u64,
+}
+
+impl LruCache {
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: VecDeque::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn put(&mut self, key: Value, value: Value) {
+        self.clock += 1;
+        self.recency.push_back((key.clone(), self.clock));
+        self.entries.insert(key, (value, self.clock));
+        if self.entries.len() > self.capacity {
+            self.evict();
+        }
+        self.compact();
+    }
+
+    /// Looks `key` up, refreshing its recency on a hit.
+    pub fn get(&mut self, key: &Value) -> Option<Value> {
+        let (value, stamp) = self.entries.get_mut(key)?;
+        self.clock += 1;
+        *stamp = self.clock;
+        let value = value.clone();
+        self.recency.push_back((key.clone(), self.clock));
+        self.compact();
+        Some(value)
+    }
+
+    pub fn contains(&self, key: &Value) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes the least recently used entry: the first queued key whose
+    /// stamp is still the one recorded for it.
+    fn evict(&mut self) {
+        while let Some((key, stamp)) = self.recency.pop_front() {
+            if self.entries.get(&key).is_some_and(|(_, live)| *live == stamp) {
+                self.entries.remove(&key);
+                return;
+            }
+        }
+    }
+
+    /// Rebuilds the recency queue from its live entries, so repeated hits on
+    /// the same keys can't grow it without bound.
+    fn compact(&mut self) {
+        if self.recency.len() <= 2 * self.entries.len().max(self.capacity) {
+            return;
+        }
+        self.recency.retain(|(key, stamp)| {
+            self.entries.get(key).is_some_and(|(_, live)| live == stamp)
+        });
+    }
+}
+
+/// Backing store of a [`Value::Bitset`]: one bit per index, 64 to a word,
+/// growing on demand. Trailing zero words are always trimmed so equal sets
+/// have equal word vectors.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn set(&mut self, i: usize) {
+        let word = i / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (i % 64);
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        self.words
+            .get(i / 64)
+            .is_some_and(|word| word & (1 << (i % 64)) != 0)
+    }
+
+    /// How many bits are set.
+    pub fn count(&self) -> u64 {
+        self.words.iter().map(|word| u64::from(word.count_ones())).sum()
+    }
+
+    /// Combines two sets word by word; `op` sees 0 for the shorter set's
+    /// missing words.
+    pub fn combine(&self, other: &BitSet, op: impl Fn(u64, u64) -> u64) -> BitSet {
+        let len = self.words.len().max(other.words.len());
+        let word = |set: &BitSet, i| set.words.get(i).copied().unwrap_or(0);
+        let mut words: Vec<u64> = (0..len).map(|i| op(word(self, i), word(other, i))).collect();
+        while words.last() == Some(&0) {
+            words.pop();
+        }
+        BitSet { words }
+    }
+
+    fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    pub(crate) fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub(crate) fn from_words(words: Vec<u64>) -> BitSet {
+        BitSet { words }
+    }
+}
+
+/// A lazy arithmetic sequence: `start`, `start + step`, ... for `len`
+/// elements. `[a..b]` produces `start = a, len = b - a, step = 1`.
+/// Backing store of a [`Value::Grid`]: row-major cells with a fixed width.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CharGrid {
+    rows: usize,
+    cols: usize,
+    cells: Vec<char>,
+}
+
+impl CharGrid {
+    /// Packs multi-line text, or `None` when the lines differ in width (a
+    /// ragged grid stays in the general `Array2D` form).
+    pub(crate) fn from_text(text: &str) -> Option<CharGrid> {
+        let mut lines = text.lines();
+        let first: Vec<char> = lines.next().unwrap_or("").chars().collect();
+        let cols = first.len();
+        let mut cells = first;
+        let mut rows = usize::from(!cells.is_empty() || !text.is_empty());
+        for line in lines {
+            let before = cells.len();
+            cells.extend(line.chars());
+            if cells.len() - before != cols {
+                return None;
+            }
+            rows += 1;
+        }
+        Some(CharGrid { rows, cols, cells })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, r: usize, c: usize) -> char {
+        self.cells[r * self.cols + c]
+    }
+
+    pub(crate) fn row_values(&self, r: usize) -> Vec<Value> {
+        self.cells[r * self.cols..(r + 1) * self.cols]
+            .iter()
+            .map(|c| Value::Str(c.to_string()))
+            .collect()
+    }
+
+    /// The grid materialized in the general `Array2D` representation.
+    pub(crate) fn to_rows(&self) -> Vec<Vec<Value>> {
+        (0..self.rows).map(|r| self.row_values(r)).collect()
+    }
+
+    pub(crate) fn transpose(&self) -> CharGrid {
+        let cells = (0..self.cols)
+            .flat_map(|c| (0..self.rows).map(move |r| self.get(r, c)))
+            .collect();
+        CharGrid {
+            rows: self.cols,
+            cols: self.rows,
+            cells,
+        }
+    }
+}
+
+/// Backing store of a [`Value::RangeSet`]: sorted, disjoint, half-open
+/// `[start, end)` intervals. Inserts merge overlapping and touching
+/// intervals; removals split them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RangeSet {
+    intervals: Vec<(i64, i64)>,
+}
+
+impl RangeSet {
+    pub fn insert(&mut self, mut start: i64, mut end: i64) {
+        if start >= end {
+            return;
+        }
+        let mut merged = Vec::with_capacity(self.intervals.len() + 1);
+        let mut placed = false;
+        for &(s, e) in &self.intervals {
+            if e < start || s > end {
+                if s > end && !placed {
+                    merged.push((start, end));
+                    placed = true;
+                }
+                merged.push((s, e));
+            } else {
+                start = start.min(s);
+                end = end.max(e);
+            }
+        }
+        if !placed {
+            merged.push((start, end));
+        }
+        self.intervals = merged;
+    }
+
+    pub fn remove(&mut self, start: i64, end: i64) {
+        if start >= end {
+            return;
+        }
+        let mut kept = Vec::with_capacity(self.intervals.len() + 1);
+        for &(s, e) in &self.intervals {
+            if e <= start || s >= end {
+                kept.push((s, e));
+                continue;
+            }
+            if s < start {
+                kept.push((s, start));
+            }
+            if e > end {
+                kept.push((end, e));
+            }
+        }
+        self.intervals = kept;
+    }
+
+    pub fn contains(&self, n: i64) -> bool {
+        self.intervals.iter().any(|&(s, e)| s <= n && n < e)
+    }
+
+    /// How many integers the intervals cover, or an error when the total
+    /// exceeds i64.
+    pub fn covered(&self) -> Result<i64, String> {
+        let total: i128 = self.intervals.iter().map(|&(s, e)| (e - s) as i128).sum();
+        i64::try_from(total).map_err(|_| "covered: count overflows".to_string())
+    }
+
+    pub fn intervals(&self) -> &[(i64, i64)] {
+        &self.intervals
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RangeVal {
+    pub start: i64,
+    pub len: i64,
+    pub step: i64,
+}
+
+impl RangeVal {
+    /// The half-open range `[lo..hi]`.
+    pub fn new(lo: i64, hi: i64) -> Self {
+        RangeVal {
+            start: lo,
+            len: (hi - lo).max(0),
+            step: 1,
+        }
+    }
+
+    /// The i-th element, supporting negative (from-the-end) indices.
+    pub fn get(&self, i: i64) -> Result<i64, String> {
+        let idx = resolve_index(i, self.len.max(0) as usize)? as i64;
+        Ok(self.start + idx * self.step)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let offset = value - self.start;
+        if self.step == 0 || offset % self.step != 0 {
+            return false;
+        }
+        let idx = offset / self.step;
+        (0..self.len).contains(&idx)
+    }
+
+    /// The same elements in the opposite order, still lazy.
+    pub fn reversed(&self) -> Self {
+        RangeVal {
+            start: self.start + (self.len - 1).max(0) * self.step,
+            len: self.len,
+            step: -self.step,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        let (start, step) = (self.start, self.step);
+        (0..self.len).map(move |i| start + i * step)
+    }
+}
+
+/// Backing store of a [`Value::Map`]: arbitrary hashable keys to values.
+/// Safe to key hash tables by even though a `Value` can hold a cache, since a
+/// cache hashes and compares by identity, which mutation can't disturb.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapVal {
+    pub entries: HashMap<Value, Value>,
+}
+
+/// Backing store of a [`Value::Set`]: distinct hashable values. Safe to key
+/// hash tables by for the same reason [`MapVal`] is.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SetVal {
+    pub items: HashSet<Value>,
+}
+
+/// Backing store of a [`Value::Sparse`] grid: explicitly set cells plus the
+/// value every other coordinate reads as.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseGrid {
+    pub cells: HashMap<(i64, i64), Value>,
+    pub default: Value,
+}
+
+impl SparseGrid {
+    /// The bounding box of the set cells as `((min_r, min_c), (max_r,
+    /// max_c))`, or `None` when no cell has been set.
+    pub fn bounds(&self) -> Option<((i64, i64), (i64, i64))> {
+        let mut keys = self.cells.keys();
+        let (mut min, mut max) = {
+            let &first = keys.next()?;
+            (first, first)
+        };
+        for &(r, c) in keys {
+            min = (min.0.min(r), min.1.min(c));
+            max = (max.0.max(r), max.1.max(c));
+        }
+        Some((min, max))
+    }
+}
+
+/// Residual edge capacities, keyed by `(from, to)` node ids.
+type Residual = HashMap<(usize, usize), i64>;
+
+/// Backing store of a [`Value::Graph`]: nodes in insertion order with
+/// out-edges as node indices, so every traversal is deterministic.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Graph {
+    nodes: Vec<Value>,
+    ids: HashMap<Value, usize>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    /// The id of `node`, registering it if new.
+    fn id_of(&mut self, node: Value) -> usize {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.ids.insert(node.clone(), id);
+        self.nodes.push(node);
+        self.edges.push(Vec::new());
+        id
+    }
+
+    /// Registers `node` without any edges.
+    pub(crate) fn add_node(&mut self, node: Value) {
+        self.id_of(node);
+    }
+
+    /// Adds the directed edge `from -> to`, registering either endpoint if
+    /// it's new.
+    pub fn add_edge(&mut self, from: Value, to: Value) {
+        let from = self.id_of(from);
+        let to = self.id_of(to);
+        self.edges[from].push(to);
+    }
+
+    pub fn nodes(&self) -> &[Value] {
+        &self.nodes
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.iter().map(Vec::len).sum()
+    }
+
+    /// Every directed edge as a `(from, to)` pair of node indices.
+    pub(crate) fn edge_pairs(&self) -> Vec<(usize, usize)> {
+        self.edges
+            .iter()
+            .enumerate()
+            .flat_map(|(from, outs)| outs.iter().map(move |&to| (from, to)))
+            .collect()
+    }
+
+    /// The out-neighbors of `node`, or `None` when it isn't in the graph.
+    pub fn neighbors_of(&self, node: &Value) -> Option<Vec<Value>> {
+        let &id = self.ids.get(node)?;
+        Some(self.edges[id].iter().map(|&to| self.nodes[to].clone()).collect())
+    }
+
+    /// The connected components, ignoring edge direction. Components come
+    /// out ordered by their first-inserted node, nodes in insertion order.
+    pub fn connected_components(&self) -> Vec<Vec<Value>> {
+        let mut undirected = vec![Vec::new(); self.nodes.len()];
+        for (from, outs) in self.edges.iter().enumerate() {
+            for &to in outs {
+                undirected[from].push(to);
+                undirected[to].push(from);
+            }
+        }
+        let mut component = vec![usize::MAX; self.nodes.len()];
+        let mut count = 0;
+        for start in 0..self.nodes.len() {
+            if component[start] != usize::MAX {
+                continue;
+            }
+            let mut stack = vec![start];
+            component[start] = count;
+            while let Some(node) = stack.pop() {
+                for &next in &undirected[node] {
+                    if component[next] == usize::MAX {
+                        component[next] = count;
+                        stack.push(next);
+                    }
+                }
+            }
+            count += 1;
+        }
+        let mut components = vec![Vec::new(); count];
+        for (id, &c) in component.iter().enumerate() {
+            components[c].push(self.nodes[id].clone());
+        }
+        components
+    }
+
+    /// Maximum `s -> t` flow by Edmonds-Karp, with one unit of capacity per
+    /// edge (parallel edges stack). `None` when either node is missing.
+    pub fn max_flow(&self, s: &Value, t: &Value) -> Option<i64> {
+        let &s = self.ids.get(s)?;
+        let &t = self.ids.get(t)?;
+        let (mut cap, adj) = self.capacities(false);
+        Some(edmonds_karp(self.nodes.len(), &mut cap, &adj, s, t).0)
+    }
+
+    /// The global minimum cut ignoring edge direction: the two node sets on
+    /// either side of the fewest-edges partition. `None` for graphs with
+    /// fewer than 2 nodes.
+    pub fn min_cut(&self) -> Option<(Vec<Value>, Vec<Value>)> {
+        let n = self.nodes.len();
+        if n < 2 {
+            return None;
+        }
+        // Unit capacities make s-t max flow cheap, so the global cut is just
+        // the best of the n - 1 cuts separating node 0 from each other node.
+        let (cap, adj) = self.capacities(true);
+        let mut best: Option<(i64, Vec<bool>)> = None;
+        for t in 1..n {
+            let mut cap = cap.clone();
+            let (flow, reachable) = edmonds_karp(n, &mut cap, &adj, 0, t);
+            if best.as_ref().is_none_or(|(min, _)| flow < *min) {
+                best = Some((flow, reachable));
+            }
+        }
+        let (_, reachable) = best?;
+        let (mut near, mut far) = (Vec::new(), Vec::new());
+        for (id, node) in self.nodes.iter().enumerate() {
+            if reachable[id] {
+                near.push(node.clone());
+            } else {
+                far.push(node.clone());
+            }
+        }
+        Some((near, far))
+    }
+
+    /// Residual capacities and adjacency (including reverse edges) for the
+    /// flow algorithms; `undirected` gives every edge capacity both ways.
+    fn capacities(&self, undirected: bool) -> (Residual, Vec<Vec<usize>>) {
+        let mut cap = Residual::new();
+        let mut adj = vec![Vec::new(); self.nodes.len()];
+        for (from, outs) in self.edges.iter().enumerate() {
+            for &to in outs {
+                if !cap.contains_key(&(from, to)) && !cap.contains_key(&(to, from)) {
+                    adj[from].push(to);
+                    adj[to].push(from);
+                }
+                *cap.entry((from, to)).or_insert(0) += 1;
+                cap.entry((to, from)).or_insert(0);
+                if undirected {
+                    *cap.get_mut(&(to, from)).unwrap() += 1;
+                }
+            }
+        }
+        (cap, adj)
+    }
+
+    /// Kahn's algorithm; ready nodes are taken in insertion order, so the
+    /// result is deterministic. `None` when the graph has a cycle.
+    pub fn topo_sort(&self) -> Option<Vec<Value>> {
+        let mut in_degree = vec![0usize; self.nodes.len()];
+        for outs in &self.edges {
+            for &to in outs {
+                in_degree[to] += 1;
+            }
+        }
+        let mut ready: Vec<usize> = (0..self.nodes.len())
+            .filter(|&id| in_degree[id] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while !ready.is_empty() {
+            // Smallest insertion id first.
+            let node = ready.remove(0);
+            order.push(self.nodes[node].clone());
+            for &to in &self.edges[node] {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    let at = ready.partition_point(|&r| r < to);
+                    ready.insert(at, to);
+                }
+            }
+        }
+        (order.len() == self.nodes.len()).then_some(order)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{n}"),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Array1D(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match item {
+                        Value::Str(s) => write!(f, "\"{s}\"")?,
+                        other => write!(f, "{other}")?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Value::NumArray(nums) => {
+                write!(f, "[")?;
+                for (i, n) in nums.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{n}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Array2D(rows) => {
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", Value::Array1D(row.clone()))?;
+                }
+                Ok(())
+            }
+            Value::Grid(g) => {
+                for r in 0..g.rows() {
+                    if r > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", Value::Array1D(g.row_values(r)))?;
+                }
+                Ok(())
+            }
+            Value::Range(r) => {
+                if r.step == 1 {
+                    write!(f, "[{}..{}]", r.start, r.start + r.len)
+                } else {
+                    write!(f, "[range start {} step {} len {}]", r.start, r.step, r.len)
+                }
+            }
+            Value::Point(r, c) => write!(f, "({r}, {c})"),
+            Value::Point3(x, y, z) => write!(f, "({x}, {y}, {z})"),
+            Value::Sparse(grid) => write!(
+                f,
+                "[sparse grid: {} cells, default {}]",
+                grid.cells.len(),
+                grid.default
+            ),
+            Value::Graph(graph) => write!(
+                f,
+                "[graph: {} nodes, {} edges]",
+                graph.nodes().len(),
+                graph.edge_count()
+            ),
+            Value::Bitset(bits) => write!(f, "[bitset: {} bits set]", bits.count()),
+            Value::RangeSet(rs) => {
+                let spans: Vec<String> = rs
+                    .intervals()
+                    .iter()
+                    .map(|(s, e)| format!("[{s}..{e}]"))
+                    .collect();
+                write!(f, "{{{}}}", spans.join(", "))
+            }
+            Value::Cache(cache) => {
+                let cache = cache.borrow();
+                write!(f, "[cache: {} of {} entries]", cache.len(), cache.capacity())
+            }
+            Value::Map(map) => {
+                // Entry iteration order is unspecified, so render sorted for
+                // deterministic output.
+                let mut entries: Vec<String> = map
+                    .entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let render = |v: &Value| match v {
+                            Value::Str(s) => format!("\"{s}\""),
+                            other => other.to_string(),
+                        };
+                        format!("{}: {}", render(key), render(value))
+                    })
+                    .collect();
+                entries.sort();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Value::Set(set) => {
+                // Sorted for the same reason map entries are.
+                let mut items: Vec<String> = set
+                    .items
+                    .iter()
+                    .map(|item| match item {
+                        Value::Str(s) => format!("\"{s}\""),
+                        other => other.to_string(),
+                    })
+                    .collect();
+                items.sort();
+                write!(f, "{{{}}}", items.join(", "))
+            }
+            Value::FnRef(name) => write!(f, "<fn {name}>"),
+        }
+    }
+}
+
+impl Value {
+    /// A short name for the value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Array1D(_) | Value::NumArray(_) => "array",
+            Value::Array2D(_) | Value::Grid(_) => "2d array",
+            Value::Range(..) => "range",
+            Value::Point(..) => "point",
+            Value::Point3(..) => "3d point",
+            Value::Sparse(_) => "sparse grid",
+            Value::Graph(_) => "graph",
+            Value::Bitset(_) => "bitset",
+            Value::Cache(_) => "cache",
+            Value::Map(_) => "map",
+            Value::Set(_) => "set",
+            Value::RangeSet(_) => "range set",
+            Value::FnRef(_) => "function",
+        }
+    }
+
+    /// Rough size of the value in bytes, for the memory limit.
+    fn approx_size(&self) -> usize {
+        match self {
+            Value::Number(_) | Value::Bool(_) | Value::Range(..) | Value::Point(..) => 16,
+            Value::Point3(..) => 24,
+            Value::RangeSet(rs) => 16 + 16 * rs.intervals().len(),
+            Value::Grid(g) => 24 + 4 * g.rows() * g.cols(),
+            Value::Str(s) => 24 + s.len(),
+            Value::FnRef(_) => 16,
+            Value::Array1D(items) => 24 + items.iter().map(Value::approx_size).sum::<usize>(),
+            Value::NumArray(nums) => 24 + 8 * nums.len(),
+            Value::Array2D(rows) => {
+                24 + rows
+                    .iter()
+                    .map(|row| 24 + row.iter().map(Value::approx_size).sum::<usize>())
+                    .sum::<usize>()
+            }
+            Value::Sparse(grid) => {
+                48 + grid
+                    .cells
+                    .values()
+                    .map(|cell| 16 + cell.approx_size())
+                    .sum::<usize>()
+            }
+            Value::Graph(graph) => {
+                48 + graph
+                    .nodes()
+                    .iter()
+                    .map(|node| 16 + node.approx_size())
+                    .sum::<usize>()
+                    + 8 * graph.edge_count()
+            }
+            Value::Bitset(bits) => 24 + 8 * bits.word_count(),
+            Value::Cache(cache) => {
+                48 + cache
+                    .borrow()
+                    .entries
+                    .iter()
+                    .map(|(k, (v, _))| 24 + k.approx_size() + v.approx_size())
+                    .sum::<usize>()
+            }
+            Value::Map(map) => {
+                48 + map
+                    .entries
+                    .iter()
+                    .map(|(k, v)| 24 + k.approx_size() + v.approx_size())
+                    .sum::<usize>()
+            }
+            Value::Set(set) => {
+                48 + set
+                    .items
+                    .iter()
+                    .map(|item| 16 + item.approx_size())
+                    .sum::<usize>()
+            }
+        }
+    }
+
+    /// Wraps `items` in the most compact array representation: packed when
+    /// every element is a number, general otherwise.
+    pub fn array(items: Vec<Value>) -> Value {
+        if items.iter().all(|item| matches!(item, Value::Number(_))) {
+            let nums = items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Number(n) => n,
+                    _ => unreachable!(),
+                })
+                .collect();
+            Value::NumArray(nums)
+        } else {
+            Value::Array1D(items)
+        }
+    }
+}
+
+/// A packed number array's elements as general values.
+pub(crate) fn unpack(nums: &[i64]) -> Vec<Value> {
+    nums.iter().copied().map(Value::Number).collect()
+}
+
+// Equality and hashing are written out by hand because `NumArray` is
+// invisible to programs: a packed number array must compare equal to — and
+// hash like — the `Array1D` spelling the same numbers, or memoization keys
+// and `==` would leak the representation.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Array1D(a), Value::Array1D(b)) => a == b,
+            (Value::NumArray(a), Value::NumArray(b)) => a == b,
+            (Value::Array1D(items), Value::NumArray(nums))
+            | (Value::NumArray(nums), Value::Array1D(items)) => {
+                items.len() == nums.len()
+                    && items
+                        .iter()
+                        .zip(nums)
+                        .all(|(item, n)| matches!(item, Value::Number(m) if m == n))
+            }
+            (Value::Array2D(a), Value::Array2D(b)) => a == b,
+            (Value::Grid(a), Value::Grid(b)) => a == b,
+            (Value::Grid(g), Value::Array2D(rows)) | (Value::Array2D(rows), Value::Grid(g)) => {
+                g.rows() == rows.len()
+                    && (0..g.rows()).all(|r| {
+                        rows[r].len() == g.cols()
+                            && rows[r].iter().enumerate().all(|(c, cell)| {
+                                matches!(cell, Value::Str(s) if s.chars().eq([g.get(r, c)]))
+                            })
+                    })
+            }
+            (Value::Range(a), Value::Range(b)) => a == b,
+            (Value::Point(r1, c1), Value::Point(r2, c2)) => (r1, c1) == (r2, c2),
+            (Value::Point3(x1, y1, z1), Value::Point3(x2, y2, z2)) => {
+                (x1, y1, z1) == (x2, y2, z2)
+            }
+            (Value::Sparse(a), Value::Sparse(b)) => a == b,
+            (Value::Graph(a), Value::Graph(b)) => a == b,
+            (Value::Bitset(a), Value::Bitset(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Set(a), Value::Set(b)) => a == b,
+            (Value::RangeSet(a), Value::RangeSet(b)) => a == b,
+            // Caches share one store, so equality is identity.
+            (Value::Cache(a), Value::Cache(b)) => Rc::ptr_eq(a, b),
+            (Value::FnRef(a), Value::FnRef(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl std::hash::Hash for Value {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match self {
+            Value::Number(n) => {
+                0u8.hash(state);
+                n.hash(state);
+            }
+            Value::Str(s) => {
+                1u8.hash(state);
+                s.hash(state);
+            }
+            Value::Bool(b) => {
+                2u8.hash(state);
+                b.hash(state);
+            }
+            // Both array forms use tag 3 and hash each element the way a
+            // `Number` hashes, so equal arrays hash equally.
+            Value::Array1D(items) => {
+                3u8.hash(state);
+                items.len().hash(state);
+                for item in items {
+                    item.hash(state);
+                }
+            }
+            Value::NumArray(nums) => {
+                3u8.hash(state);
+                nums.len().hash(state);
+                for n in nums {
+                    0u8.hash(state);
+                    n.hash(state);
+                }
+            }
+            Value::Array2D(rows) => {
+                4u8.hash(state);
+                rows.len().hash(state);
+                for row in rows.iter() {
+                    row.len().hash(state);
+                    for item in row {
+                        item.hash(state);
+                    }
+                }
+            }
+            // Hashes exactly like the `Array2D` of 1-char strings it
+            // represents, so either form works as the same map key.
+            Value::Grid(g) => {
+                4u8.hash(state);
+                g.rows().hash(state);
+                for r in 0..g.rows() {
+                    g.cols().hash(state);
+                    for c in 0..g.cols() {
+                        1u8.hash(state);
+                        g.get(r, c).to_string().hash(state);
+                    }
+                }
+            }
+            Value::Range(r) => {
+                5u8.hash(state);
+                r.hash(state);
+            }
+            Value::FnRef(name) => {
+                6u8.hash(state);
+                name.hash(state);
+            }
+            Value::Point(r, c) => {
+                7u8.hash(state);
+                r.hash(state);
+                c.hash(state);
+            }
+            Value::RangeSet(rs) => {
+                15u8.hash(state);
+                rs.intervals().hash(state);
+            }
+            Value::Point3(x, y, z) => {
+                14u8.hash(state);
+                x.hash(state);
+                y.hash(state);
+                z.hash(state);
+            }
+            Value::Sparse(grid) => {
+                // Cell iteration order is unspecified, so combine per-entry
+                // hashes with a commutative operation.
+                use std::hash::Hasher;
+                let mut combined = 0u64;
+                for entry in &grid.cells {
+                    let mut hasher = StableHasher::default();
+                    entry.hash(&mut hasher);
+                    combined = combined.wrapping_add(hasher.finish());
+                }
+                8u8.hash(state);
+                grid.cells.len().hash(state);
+                combined.hash(state);
+                grid.default.hash(state);
+            }
+            Value::Graph(graph) => {
+                9u8.hash(state);
+                graph.nodes().len().hash(state);
+                for node in graph.nodes() {
+                    node.hash(state);
+                }
+                graph.edge_count().hash(state);
+            }
+            Value::Bitset(bits) => {
+                10u8.hash(state);
+                bits.hash(state);
+            }
+            // Identity equality admits any constant hash.
+            Value::Cache(_) => 11u8.hash(state),
+            Value::Map(map) => {
+                // Entry order is unspecified, combined commutatively like a
+                // sparse grid's cells.
+                use std::hash::Hasher;
+                let mut combined = 0u64;
+                for entry in &map.entries {
+                    let mut hasher = StableHasher::default();
+                    entry.hash(&mut hasher);
+                    combined = combined.wrapping_add(hasher.finish());
+                }
+                12u8.hash(state);
+                map.entries.len().hash(state);
+                combined.hash(state);
+            }
+            Value::Set(set) => {
+                // Commutative for the same reason the map hash is.
+                use std::hash::Hasher;
+                let mut combined = 0u64;
+                for item in &set.items {
+                    let mut hasher = StableHasher::default();
+                    item.hash(&mut hasher);
+                    combined = combined.wrapping_add(hasher.finish());
+                }
+                13u8.hash(state);
+                set.items.len().hash(state);
+                combined.hash(state);
+            }
+        }
+    }
+}
+
+/// FNV-1a, with every integer written as little-endian bytes so the result
+/// is the same on every platform and run. `hash()` exposes it to programs as
+/// a fingerprint for cycle detection, so unlike [`std::hash::DefaultHasher`]
+/// it must never change behind their backs.
+pub(crate) struct StableHasher(u64);
+
+impl StableHasher {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(Self::OFFSET)
+    }
+}
+
+impl std::hash::Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write(&[i]);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // Lengths hash through here; pin the width so 32-bit platforms agree.
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The stable fingerprint of a value, for the `hash` builtin.
+pub(crate) fn stable_hash(value: &Value) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = StableHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// What happens when i64 arithmetic overflows.
+///
+/// The default is [`OverflowMode::Checked`]: a silent wraparound in a puzzle
+/// solution produces a plausible-looking wrong answer, so overflow is an
+/// error unless a program opts out with the `wrapping()` or `saturating()`
+/// builtins.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowMode {
+    #[default]
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+/// How a statement finished: normally, or unwinding out of a labeled loop.
+#[derive(Clone, Debug, PartialEq)]
+enum Flow {
+    Normal,
+    Break(Option<Symbol>),
+    Continue,
+    Return(Value),
+}
+
+/// Decides whether a `break` stops at this loop or keeps unwinding. An
+/// unlabeled `break` (and a `continue`, which the loop itself absorbs at
+/// the top of each iteration) always stops at the innermost loop.
+fn loop_exit(flow: Flow, label: Option<Symbol>) -> Flow {
+    match flow {
+        Flow::Break(None) | Flow::Continue => Flow::Normal,
+        Flow::Break(Some(target)) if Some(target) == label => Flow::Normal,
+        other => other,
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Function {
+    params: Vec<Symbol>,
+    body: FnBody,
+    memoized: bool,
+    doc: Option<String>,
+    /// The scope chain where the function was defined; `None` for the global
+    /// scope. Captured by reference, so a global defined after the function
+    /// is still visible to its body.
+    env: Option<Rc<RefCell<Env>>>,
+}
+
+/// One lexical scope: the parameter bindings of a call, linked to the scope
+/// the callee was defined in. Globals stay in [`Slots`] below the whole
+/// chain, so top-level code keeps its direct-indexed fast path and each call
+/// only allocates a handful of bindings.
+#[derive(Debug)]
+struct Env {
+    bindings: Vec<(Symbol, Value)>,
+    parent: Option<Rc<RefCell<Env>>>,
+}
+
+impl Env {
+    fn get(&self, name: Symbol) -> Option<Value> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(bound, _)| *bound == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+/// Where an indexed-assignment root was taken from, so the updated value
+/// can be put back in the same slot.
+enum RootSlot {
+    Global,
+    Local(Rc<RefCell<Env>>, usize),
+}
+
+/// Variable storage indexed directly by symbol id.
+///
+/// Symbol ids are dense, so resolution already happened at intern time:
+/// every read and write is plain vector indexing with no hashing, which is
+/// what tight simulation loops spend most of their time doing. Slots grow on
+/// demand as the interner hands out new ids.
+#[derive(Default)]
+struct Slots(Vec<Option<Value>>);
+
+impl Slots {
+    fn get(&self, name: Symbol) -> Option<&Value> {
+        self.0.get(name.index()).and_then(Option::as_ref)
+    }
+
+    fn insert(&mut self, name: Symbol, value: Value) {
+        let index = name.index();
+        if index >= self.0.len() {
+            self.0.resize_with(index + 1, || None);
+        }
+        self.0[index] = Some(value);
+    }
+
+    fn remove(&mut self, name: Symbol) -> Option<Value> {
+        self.0.get_mut(name.index()).and_then(Option::take)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct FnProfile {
+    calls: u64,
+    total: Duration,
+}
+
+/// Interpreter state: global variables, function definitions and puzzle input.
+pub struct Interpreter {
+    variables: Slots,
+    /// The innermost lexical scope, `None` when executing top-level code.
+    env: Option<Rc<RefCell<Env>>>,
+    functions: HashMap<Symbol, Function>,
+    input: Option<String>,
+    /// Where the input came from, when the embedder says; surfaced through
+    /// `input.meta`.
+    input_name: Option<String>,
+    /// The input converted to its grid value, built once per `set_input`.
+    /// Cheap to hand out repeatedly because `Array2D` rows sit behind an
+    /// `Rc`.
+    input_grid: Option<Value>,
+    debug: bool,
+    debug_events: u64,
+    max_debug_events: Option<u64>,
+    steps: u64,
+    max_steps: Option<u64>,
+    /// How deep user-function calls may nest before erroring; a guard rail
+    /// well inside the native stack, since every interpreted call costs real
+    /// Rust frames too.
+    max_depth: usize,
+    /// The soonest `deadline()` budget currently in force, if any.
+    deadline: Option<Instant>,
+    /// Statements between checkpoints, and where the text goes.
+    checkpoint: Option<(u64, CheckpointSink)>,
+    max_memory: Option<usize>,
+    memory_used: usize,
+    memo_cache: HashMap<(Symbol, Vec<Value>), Value>,
+    /// Per-function (hits, misses) counters for the memo cache.
+    memo_stats: HashMap<Symbol, (u64, u64)>,
+    profile: Option<HashMap<Symbol, FnProfile>>,
+    trace: Option<Box<dyn std::io::Write>>,
+    call_stack: Vec<Symbol>,
+    folded: Option<HashMap<String, u64>>,
+    /// Executed-statement counts per source line, for the heatmap listing.
+    heatmap: Option<HashMap<usize, u64>>,
+    /// When set, `animate()` draws frames to the terminal with this pause
+    /// between them; when unset it is a no-op.
+    animate_delay: Option<Duration>,
+    overflow: OverflowMode,
+    /// When set, out-of-range slice bounds error instead of clamping.
+    strict_slices: bool,
+    /// The incoming value of the pipeline stage currently being evaluated,
+    /// read by `_` inside a `|>` right-hand side.
+    pipe_value: Option<Value>,
+    /// Recoverable oddities noticed while running — lossy `~` conversions,
+    /// clamped slices, ragged grids — kept for the embedder to surface.
+    warnings: Vec<String>,
+    /// The source line of the statement currently executing, so runtime
+    /// errors can be reported with a position.
+    current_line: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            variables: Slots::default(),
+            env: None,
+            functions: HashMap::new(),
+            input: None,
+            input_name: None,
+            input_grid: None,
+            debug: false,
+            debug_events: 0,
+            max_debug_events: None,
+            steps: 0,
+            max_steps: None,
+            max_depth: 200,
+            deadline: None,
+            checkpoint: None,
+            max_memory: None,
+            memory_used: 0,
+            memo_cache: HashMap::new(),
+            memo_stats: HashMap::new(),
+            profile: None,
+            trace: None,
+            call_stack: Vec::new(),
+            folded: None,
+            heatmap: None,
+            animate_delay: None,
+            overflow: OverflowMode::default(),
+            strict_slices: false,
+            pipe_value: None,
+            warnings: Vec::new(),
+            current_line: 0,
+        }
+    }
+
+    /// Provides the puzzle input, available to programs as `input`.
+    pub fn set_input(&mut self, input: String) {
+        self.input = Some(input);
+        self.input_grid = None;
+    }
+
+    /// Records where the input came from (typically a file path), reported
+    /// by `input.meta` as `file`.
+    pub fn set_input_name(&mut self, name: String) {
+        self.input_name = Some(name);
+    }
+
+    /// Enables printing of every executed statement to stderr.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    /// Stops emitting debug lines (with a notice) after `limit` events, so
+    /// debugging a program on real input doesn't flood stderr.
+    pub fn set_max_debug_events(&mut self, limit: u64) {
+        self.max_debug_events = Some(limit);
+    }
+
+    /// Aborts execution after `limit` statements.
+    pub fn set_max_steps(&mut self, limit: u64) {
+        self.max_steps = Some(limit);
+    }
+
+    /// Saves the serializable globals through `sink` every `every`
+    /// statements, so a long simulation can be resumed after a crash.
+    pub fn set_checkpoint(&mut self, every: u64, sink: CheckpointSink) {
+        self.checkpoint = Some((every.max(1), sink));
+    }
+
+    /// Sets how deep recursion may go before a clean stack-overflow error
+    /// (default: 200 calls).
+    pub fn set_max_depth(&mut self, limit: usize) {
+        self.max_depth = limit;
+    }
+
+    /// Aborts execution when global variables exceed roughly `limit` bytes.
+    pub fn set_max_memory(&mut self, limit: usize) {
+        self.max_memory = Some(limit);
+    }
+
+    /// Makes `animate()` clear the screen and draw its grid argument, pausing
+    /// `delay` between frames. Off by default, so `animate()` calls cost
+    /// nothing in scripts and tests.
+    pub fn set_animate(&mut self, delay: Duration) {
+        self.animate_delay = Some(delay);
+    }
+
+    pub(crate) fn animation_delay(&self) -> Option<Duration> {
+        self.animate_delay
+    }
+
+    /// Sets what i64 overflow does: error (the default), wrap, or saturate.
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode) {
+        self.overflow = mode;
+    }
+
+    /// Makes out-of-range slice bounds an error instead of clamping to fit.
+    pub fn set_strict_slices(&mut self, strict: bool) {
+        self.strict_slices = strict;
+    }
+
+    /// Records every executed statement, with its source line and resulting
+    /// value, to the given writer. Independent of the debug flag.
+    pub fn set_trace(&mut self, sink: Box<dyn std::io::Write>) {
+        self.trace = Some(sink);
+    }
+
+    /// Enables per-function call counting and timing.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(HashMap::new());
+    }
+
+    /// Enables folded stack sampling: every executed statement counts one
+    /// sample against the current call stack.
+    pub fn enable_folded_profiling(&mut self) {
+        self.folded = Some(HashMap::new());
+    }
+
+    /// Enables per-line execution counting for [`Self::heatmap_report`].
+    pub fn enable_heatmap(&mut self) {
+        self.heatmap = Some(HashMap::new());
+    }
+
+    /// Renders the source annotated with how many times each line executed,
+    /// so a hot loop stands out at a glance. Drains the counts, so each file
+    /// in a multi-file run gets its own listing.
+    pub fn heatmap_report(&mut self, source: &str) -> Option<String> {
+        let counts = self.heatmap.take()?;
+        self.heatmap = Some(HashMap::new());
+        let width = counts
+            .values()
+            .map(|count| count.to_string().len())
+            .max()
+            .unwrap_or(1)
+            .max("count".len());
+        let mut out = format!("{:>width$} | source\n", "count");
+        for (number, text) in source.lines().enumerate() {
+            match counts.get(&(number + 1)) {
+                Some(count) => out.push_str(&format!("{count:>width$} | {text}\n")),
+                None => out.push_str(&format!("{:>width$} | {text}\n", "")),
+            }
+        }
+        Some(out)
+    }
+
+    /// Renders collected samples in the folded format consumed by inferno
+    /// and speedscope: one `stack;of;frames count` line per unique stack.
+    pub fn folded_report(&self) -> Option<String> {
+        let folded = self.folded.as_ref()?;
+        let mut entries: Vec<_> = folded.iter().collect();
+        entries.sort();
+        let mut out = String::new();
+        for (stack, count) in entries {
+            out.push_str(&format!("{stack} {count}\n"));
+        }
+        Some(out)
+    }
+
+    /// Records a recoverable oddity. Repeats are dropped so a clamped slice
+    /// inside a loop reports once, not once per iteration.
+    fn warn(&mut self, message: String) {
+        if !self.warnings.contains(&message) {
+            self.warnings.push(message);
+        }
+    }
+
+    /// The warnings collected while running, in first-occurrence order.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The program result: the value of `_`, if set.
+    pub fn result(&self) -> Option<Value> {
+        self.variables.get(Symbol::intern("_")).cloned()
+    }
+
+    /// Named results: `_part1` and `_part2`, in that order, so a single file
+    /// can answer both halves of a puzzle.
+    pub fn named_results(&self) -> Vec<(&'static str, Value)> {
+        ["part1", "part2"]
+            .iter()
+            .filter_map(|part| {
+                self.variables
+                    .get(Symbol::intern(&format!("_{part}")))
+                    .map(|value| (*part, value.clone()))
+            })
+            .collect()
+    }
+
+    /// Sets a global variable before (or between) runs.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.variables.insert(Symbol::intern(name), value);
+    }
+
+    /// Every global variable with its name, for checkpointing.
+    pub fn globals(&self) -> Vec<(Rc<str>, Value)> {
+        self.variables
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let value = slot.as_ref()?.clone();
+                Some((Symbol::from_index(index).name(), value))
+            })
+            .collect()
+    }
+
+    /// Reads a global variable.
+    pub fn get_var(&self, name: &str) -> Option<&Value> {
+        self.variables.get(Symbol::intern(name))
+    }
+
+    /// Runs a parsed program to completion. Errors carry the line of the
+    /// statement that was executing when they arose.
+    pub fn run(&mut self, program: &Block) -> Result<(), XmasError> {
+        let flow = self.eval_block(program).map_err(|message| {
+            let err = XmasError::new(ErrorKind::Runtime, message);
+            match self.current_line {
+                0 => err,
+                line => err.at(line, 1),
+            }
+        })?;
+        match flow {
+            Flow::Normal => Ok(()),
+            Flow::Break(label) => Err(XmasError::new(
+                ErrorKind::Runtime,
+                match label {
+                    Some(label) => format!("break {label}: no enclosing loop with that label"),
+                    None => "break outside of a loop".to_string(),
+                },
+            )),
+            Flow::Continue => Err(XmasError::new(
+                ErrorKind::Runtime,
+                "continue outside of a loop".to_string(),
+            )),
+            Flow::Return(_) => Err(XmasError::new(
+                ErrorKind::Runtime,
+                "return outside of a function".to_string(),
+            )),
+        }
+    }
+
+    /// Renders the flat profiler report, sorted by cumulative time.
+    pub fn profile_report(&self) -> Option<String> {
+        let profile = self.profile.as_ref()?;
+        let mut entries: Vec<_> = profile.iter().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.1.total));
+        let mut out = String::from("function            calls      total\n");
+        for (name, p) in entries {
+            out.push_str(&format!(
+                "{:<18} {:>7} {:>9.3}ms\n",
+                name,
+                p.calls,
+                p.total.as_secs_f64() * 1000.0
+            ));
+        }
+        Some(out)
+    }
+
+    fn eval_block(&mut self, block: &Block) -> Result<Flow, String> {
+        for (line, stmt) in block {
+            match self.eval_stmt(*line, stmt)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn eval_stmt(&mut self, line: usize, stmt: &Stmt) -> Result<Flow, String> {
+        self.current_line = line;
+        self.steps += 1;
+        if let Some(max) = self.max_steps {
+            if self.steps > max {
+                return Err(format!("execution exceeded step limit of {max}"));
+            }
+        }
+        self.check_deadline()?;
+        if let Some((every, _)) = self.checkpoint {
+            if self.steps.is_multiple_of(every) {
+                self.write_checkpoint()?;
+            }
+        }
+        self.folded_sample();
+        if let Some(heatmap) = &mut self.heatmap {
+            *heatmap.entry(line).or_default() += 1;
+        }
+        if self.debug {
+            self.debug_events += 1;
+            match self.max_debug_events {
+                Some(max) if self.debug_events > max => {
+                    eprintln!("[debug] stopping after {max} events (--debug-max-events)");
+                    self.debug = false;
+                }
+                _ => eprintln!("[debug] line {line}: {}", describe_stmt(stmt)),
+            }
+        }
+        if self.trace.is_some() {
+            let text = describe_stmt(stmt);
+            self.trace_event(line, &text);
+        }
+        match stmt {
+            Stmt::Assign { name, value } => {
+                let value = self.eval_expr(value)?;
+                self.trace_value(line, *name, &value);
+                self.set_var(*name, value)?;
+            }
+            Stmt::GlobalAssign { name, value } => {
+                let value = self.eval_expr(value)?;
+                self.trace_value(line, *name, &value);
+                self.assign_global(*name, value)?;
+            }
+            Stmt::IndexAssign { target, value } => {
+                let rhs = self.eval_expr(value)?;
+                let mut indices = Vec::with_capacity(target.indices.len());
+                for index in &target.indices {
+                    indices.push(self.eval_expr(index)?);
+                }
+                self.assign_indexed(line, target.name, &indices, rhs)?;
+            }
+            Stmt::AssignOp { target, op, value } => {
+                let rhs = self.eval_expr(value)?;
+                if target.indices.is_empty() {
+                    let name = target.name;
+                    let lhs = self
+                        .lookup(name)
+                        .ok_or_else(|| format!("undefined variable: {name}"))?;
+                    let result = self.evaluate_binary_op(*op, lhs, rhs)?;
+                    self.trace_value(line, name, &result);
+                    self.set_var(name, result)?;
+                } else {
+                    let mut indices = Vec::with_capacity(target.indices.len());
+                    for index in &target.indices {
+                        indices.push(self.eval_expr(index)?);
+                    }
+                    self.assign_op_indexed(line, target.name, &indices, *op, rhs)?;
+                }
+            }
+            Stmt::FnDef {
+                name,
+                params,
+                body,
+                memoized,
+                doc,
+            } => {
+                self.functions.insert(
+                    *name,
+                    Function {
+                        params: params.clone(),
+                        body: body.clone(),
+                        memoized: *memoized,
+                        doc: doc.clone(),
+                        env: self.env.clone(),
+                    },
+                );
+            }
+            Stmt::If {
+                cond,
+                then_block,
+                else_block,
+            } => {
+                let cond = self.eval_expr(cond)?;
+                let flow = if self.is_truthy(&cond) {
+                    self.eval_block(then_block)?
+                } else {
+                    self.eval_block(else_block)?
+                };
+                return Ok(flow);
+            }
+            Stmt::While { cond, body, label } => loop {
+                let cond_value = self.eval_expr(cond)?;
+                if !self.is_truthy(&cond_value) {
+                    break;
+                }
+                match self.eval_block(body)? {
+                    Flow::Normal | Flow::Continue => {}
+                    flow => return Ok(loop_exit(flow, *label)),
+                }
+            },
+            Stmt::For {
+                var,
+                iter,
+                init,
+                body,
+                label,
+            } => {
+                if let Some((name, value)) = init {
+                    let value = self.eval_expr(value)?;
+                    self.set_var(*name, value)?;
+                }
+                let items = self.eval_expr(iter)?;
+                // Ranges iterate lazily; everything else materializes.
+                if let Value::Range(r) = items {
+                    for n in r.iter() {
+                        self.set_var(*var, Value::Number(n))?;
+                        match self.eval_block(body)? {
+                            Flow::Normal | Flow::Continue => {}
+                            flow => return Ok(loop_exit(flow, *label)),
+                        }
+                    }
+                } else {
+                    for item in self.iterate(items)? {
+                        self.set_var(*var, item)?;
+                        match self.eval_block(body)? {
+                            Flow::Normal | Flow::Continue => {}
+                            flow => return Ok(loop_exit(flow, *label)),
+                        }
+                    }
+                }
+            }
+            Stmt::Break { label } => return Ok(Flow::Break(*label)),
+            Stmt::Continue => return Ok(Flow::Continue),
+            Stmt::Return(value) => return Ok(Flow::Return(self.eval_expr(value)?)),
+            Stmt::Match { subject, arms } => {
+                let value = self.eval_expr(subject)?;
+                for arm in arms {
+                    let mut bindings = Vec::new();
+                    if self.match_pattern(&arm.pattern, &value, &mut bindings)? {
+                        for (name, bound) in bindings {
+                            self.trace_value(line, name, &bound);
+                            self.set_var(name, bound)?;
+                        }
+                        return self.eval_block(&arm.body);
+                    }
+                }
+                return Err(format!("match: no arm matched {value}"));
+            }
+            Stmt::Expr(expr) => {
+                let value = self.eval_expr(expr)?;
+                if self.trace.is_some() {
+                    self.trace_event(line, &format!("=> {value}"));
+                }
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Counts one sample against the current call stack, if folded stack
+    /// profiling is enabled.
+    fn folded_sample(&mut self) {
+        if let Some(folded) = self.folded.as_mut() {
+            let mut stack = String::from("main");
+            for frame in &self.call_stack {
+                stack.push(';');
+                stack.push_str(&frame.name());
+            }
+            *folded.entry(stack).or_insert(0) += 1;
+        }
+    }
+
+    fn trace_event(&mut self, line: usize, text: &str) {
+        if let Some(sink) = self.trace.as_mut() {
+            // A failing trace write shouldn't abort the program.
+            let _ = writeln!(sink, "line {line}: {text}");
+        }
+    }
+
+    fn trace_value(&mut self, line: usize, name: Symbol, value: &Value) {
+        if self.trace.is_some() {
+            self.trace_event(line, &format!("{name} = {value}"));
+        }
+    }
+
+    /// Applies `op` in place to an indexed element, as in `grid[r][c] += 1`.
+    fn assign_indexed(
+        &mut self,
+        line: usize,
+        name: Symbol,
+        indices: &[Value],
+        rhs: Value,
+    ) -> Result<(), String> {
+        let (slot, mut root) = self.take_root(name)?;
+        // Fast path: a single-index number write to a packed number array
+        // stays packed.
+        if let (Value::NumArray(nums), [Value::Number(index)], Value::Number(n)) =
+            (&mut root, indices, &rhs)
+        {
+            return match resolve_index(*index, nums.len()) {
+                Ok(idx) => {
+                    nums[idx] = *n;
+                    if self.trace.is_some() {
+                        self.trace_event(line, &format!("{name}[...] = {rhs}"));
+                    }
+                    self.put_root(slot, name, root);
+                    Ok(())
+                }
+                Err(e) => {
+                    self.put_root(slot, name, root);
+                    Err(e)
+                }
+            };
+        }
+        // Put the variable back before surfacing any indexing error.
+        match place_mut(&mut root, indices) {
+            Ok(place) => {
+                if self.trace.is_some() {
+                    self.trace_event(line, &format!("{name}[...] = {rhs}"));
+                }
+                *place = rhs;
+                self.put_root(slot, name, root);
+                Ok(())
+            }
+            Err(e) => {
+                self.put_root(slot, name, root);
+                Err(e)
+            }
+        }
+    }
+
+    fn assign_op_indexed(
+        &mut self,
+        line: usize,
+        name: Symbol,
+        indices: &[Value],
+        op: BinOp,
+        rhs: Value,
+    ) -> Result<(), String> {
+        let (slot, mut root) = self.take_root(name)?;
+        // Fast path: a single-index update to a packed number array writes
+        // in place instead of unpacking (the common `counts[i] += 1` shape).
+        if let (Value::NumArray(nums), [Value::Number(index)]) = (&mut root, indices) {
+            let result = resolve_index(*index, nums.len()).and_then(|idx| {
+                self.evaluate_binary_op(op, Value::Number(nums[idx]), rhs)
+                    .map(|value| (idx, value))
+            });
+            return match result {
+                Ok((idx, value)) => {
+                    if self.trace.is_some() {
+                        self.trace_event(line, &format!("{name}[...] = {value}"));
+                    }
+                    match value {
+                        Value::Number(n) => nums[idx] = n,
+                        other => {
+                            let mut items = unpack(nums);
+                            items[idx] = other;
+                            root = Value::Array1D(items);
+                        }
+                    }
+                    self.put_root(slot, name, root);
+                    Ok(())
+                }
+                Err(e) => {
+                    self.put_root(slot, name, root);
+                    Err(e)
+                }
+            };
+        }
+        // Compute the new element value, putting the variable back before
+        // surfacing any error.
+        let result = place_mut(&mut root, indices)
+            .map(|place| place.clone())
+            .and_then(|old| self.evaluate_binary_op(op, old, rhs));
+        match result {
+            Ok(value) => {
+                if self.trace.is_some() {
+                    self.trace_event(line, &format!("{name}[...] = {value}"));
+                }
+                *place_mut(&mut root, indices)? = value;
+                self.put_root(slot, name, root);
+                Ok(())
+            }
+            Err(e) => {
+                self.put_root(slot, name, root);
+                Err(e)
+            }
+        }
+    }
+
+    /// Assigns `name`, binding locally when a call scope is active: an
+    /// existing binding anywhere in the scope chain updates in place, and a
+    /// new name binds in the innermost scope. Only top-level assignment (or
+    /// `global x = ...`, which calls [`Interpreter::assign_global`] directly)
+    /// touches the globals.
+    fn set_var(&mut self, name: Symbol, value: Value) -> Result<(), String> {
+        if let Some((env, idx)) = self.local_slot(name) {
+            env.borrow_mut().bindings[idx].1 = value;
+            return Ok(());
+        }
+        if let Some(env) = &self.env {
+            env.borrow_mut().bindings.push((name, value));
+            return Ok(());
+        }
+        self.assign_global(name, value)
+    }
+
+    fn assign_global(&mut self, name: Symbol, value: Value) -> Result<(), String> {
+        if let Some(max) = self.max_memory {
+            let old = self.variables.get(name).map_or(0, Value::approx_size);
+            self.memory_used = self.memory_used - old + value.approx_size();
+            if self.memory_used > max {
+                return Err(format!("memory limit of {max} bytes exceeded"));
+            }
+        }
+        self.variables.insert(name, value);
+        Ok(())
+    }
+
+    /// Finds the scope holding a binding for `name`, if any call scope does.
+    fn local_slot(&self, name: Symbol) -> Option<(Rc<RefCell<Env>>, usize)> {
+        let mut scope = self.env.clone();
+        while let Some(env) = scope {
+            let idx = env
+                .borrow()
+                .bindings
+                .iter()
+                .rposition(|(bound, _)| *bound == name);
+            if let Some(idx) = idx {
+                return Some((env, idx));
+            }
+            let parent = env.borrow().parent.clone();
+            scope = parent;
+        }
+        None
+    }
+
+    /// Takes the value behind `name` out of its slot for an in-place
+    /// indexed update; [`Interpreter::put_root`] must put it back.
+    fn take_root(&mut self, name: Symbol) -> Result<(RootSlot, Value), String> {
+        if let Some((env, idx)) = self.local_slot(name) {
+            let value =
+                std::mem::replace(&mut env.borrow_mut().bindings[idx].1, Value::Bool(false));
+            return Ok((RootSlot::Local(env, idx), value));
+        }
+        self.variables
+            .remove(name)
+            .map(|value| (RootSlot::Global, value))
+            .ok_or_else(|| format!("undefined variable: {name}"))
+    }
+
+    fn put_root(&mut self, slot: RootSlot, name: Symbol, value: Value) {
+        match slot {
+            RootSlot::Global => self.variables.insert(name, value),
+            RootSlot::Local(env, idx) => env.borrow_mut().bindings[idx].1 = value,
+        }
+    }
+
+    /// Tests `value` against `pattern`, collecting variable bindings; the
+    /// caller applies them only once the whole pattern has matched.
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(Symbol, Value)>,
+    ) -> Result<bool, String> {
+        match pattern {
+            Pattern::Literal(literal) => {
+                let literal = self.eval_expr(literal)?;
+                Ok(values_equal(&literal, value))
+            }
+            Pattern::Binding(name) => {
+                bindings.push((*name, value.clone()));
+                Ok(true)
+            }
+            Pattern::Array(items, rest) => {
+                let elements = match value {
+                    Value::Array1D(elements) => elements.clone(),
+                    Value::NumArray(nums) => unpack(nums),
+                    _ => return Ok(false),
+                };
+                let long_enough = match rest {
+                    None => elements.len() == items.len(),
+                    Some(_) => elements.len() >= items.len(),
+                };
+                if !long_enough {
+                    return Ok(false);
+                }
+                for (item, element) in items.iter().zip(&elements) {
+                    if !self.match_pattern(item, element, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                if let Some(rest) = rest {
+                    bindings.push((*rest, Value::array(elements[items.len()..].to_vec())));
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    fn iterate(&self, value: Value) -> Result<Vec<Value>, String> {
+        match value {
+            Value::Array1D(items) => Ok(items),
+            Value::NumArray(nums) => Ok(unpack(&nums)),
+            Value::Grid(g) => Ok((0..g.rows())
+                .map(|r| Value::Array1D(g.row_values(r)))
+                .collect()),
+            Value::Array2D(rows) => Ok(Rc::unwrap_or_clone(rows)
+                .into_iter()
+                .map(Value::Array1D)
+                .collect()),
+            Value::Range(r) => Ok(r.iter().map(Value::Number).collect()),
+            Value::Str(s) => Ok(s.chars().map(|c| Value::Str(c.to_string())).collect()),
+            other => Err(format!("cannot iterate over {}", other.type_name())),
+        }
+    }
+
+    /// Serializes the checkpointable globals and hands them to the sink.
+    fn write_checkpoint(&mut self) -> Result<(), String> {
+        let globals: Vec<_> = self
+            .globals()
+            .into_iter()
+            .filter(|(_, value)| snapshot::serializable(value))
+            .collect();
+        let text = snapshot::serialize(&globals);
+        if let Some((_, sink)) = self.checkpoint.as_mut() {
+            sink(&text).map_err(|e| format!("checkpoint failed: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Errors with the sentinel message when the active `deadline()` budget
+    /// has run out. Checked per statement and per function call, the same
+    /// places the step limit can interrupt a runaway program.
+    fn check_deadline(&self) -> Result<(), String> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(DEADLINE_EXCEEDED.to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `block()` under a time budget; on timeout, `fallback` (called if
+    /// it is a function) supplies the result instead. Nested deadlines keep
+    /// whichever expires sooner.
+    pub(crate) fn eval_with_deadline(
+        &mut self,
+        budget: Duration,
+        block: &Value,
+        fallback: &Value,
+    ) -> Result<Value, String> {
+        let until = Instant::now() + budget;
+        let saved = self.deadline;
+        self.deadline = Some(saved.map_or(until, |sooner| sooner.min(until)));
+        let result = self.call_fn_value(block, Vec::new());
+        self.deadline = saved;
+        match result {
+            Err(message) if message == DEADLINE_EXCEEDED => match fallback {
+                Value::FnRef(_) => self.call_fn_value(fallback, Vec::new()),
+                other => Ok(other.clone()),
+            },
+            other => other,
+        }
+    }
+
+    /// Resolves a variable: the lexical scope chain first, then globals.
+    fn lookup(&self, name: Symbol) -> Option<Value> {
+        let mut scope = self.env.clone();
+        while let Some(env) = scope {
+            let env = env.borrow();
+            if let Some(value) = env.get(name) {
+                return Some(value);
+            }
+            scope = env.parent.clone();
+        }
+        self.variables.get(name).cloned()
+    }
+
+    pub(crate) fn eval_expr(&mut self, expr: &Expr) -> Result<Value, String> {
+        match expr {
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Str(s) => Ok(Value::Str(s.clone())),
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::Identifier(name) => {
+                if *name == Symbol::intern("_") {
+                    if let Some(value) = &self.pipe_value {
+                        return Ok(value.clone());
+                    }
+                }
+                if let Some(value) = self.lookup(*name) {
+                    Ok(value)
+                } else if self.functions.contains_key(name) {
+                    Ok(Value::FnRef(*name))
+                } else {
+                    Err(format!("undefined variable: {name}"))
+                }
+            }
+            Expr::Array(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(self.eval_expr(item)?);
+                }
+                Ok(Value::array(values))
+            }
+            Expr::Map(entries) => {
+                let mut map = MapVal::default();
+                for (key, value) in entries {
+                    let key = self.eval_expr(key)?;
+                    map.entries.insert(key, self.eval_expr(value)?);
+                }
+                Ok(Value::Map(Rc::new(map)))
+            }
+            Expr::Range(lo, hi) => {
+                let lo = self.eval_number(lo)?;
+                let hi = self.eval_number(hi)?;
+                Ok(Value::Range(RangeVal::new(lo, hi)))
+            }
+            Expr::Unary(op, operand) => {
+                let value = self.eval_expr(operand)?;
+                self.evaluate_unary_op(*op, value)
+            }
+            Expr::Binary(lhs, op, rhs) => {
+                // Short-circuit && and ||.
+                if matches!(op, BinOp::And | BinOp::Or) {
+                    let left = self.eval_expr(lhs)?;
+                    let left_truthy = self.is_truthy(&left);
+                    return match op {
+                        BinOp::And if !left_truthy => Ok(Value::Bool(false)),
+                        BinOp::Or if left_truthy => Ok(Value::Bool(true)),
+                        _ => {
+                            let right = self.eval_expr(rhs)?;
+                            Ok(Value::Bool(self.is_truthy(&right)))
+                        }
+                    };
+                }
+                let left = self.eval_expr(lhs)?;
+                let right = self.eval_expr(rhs)?;
+                self.evaluate_binary_op(*op, left, right)
+            }
+            Expr::Index(base, index) => {
+                let base = self.eval_expr(base)?;
+                let index = self.eval_expr(index)?;
+                self.index_value(base, index)
+            }
+            Expr::Slice(base, lo, hi) => {
+                let base = self.eval_expr(base)?;
+                let lo = match lo {
+                    Some(e) => Some(self.eval_number(e)?),
+                    None => None,
+                };
+                let hi = match hi {
+                    Some(e) => Some(self.eval_number(e)?),
+                    None => None,
+                };
+                self.slice_value(base, lo, hi)
+            }
+            Expr::Call(name, args, named) => self.eval_call(*name, args, named, None),
+            Expr::Pipe(lhs, rhs) => {
+                let left = self.eval_expr(lhs)?;
+                match rhs.as_ref() {
+                    Expr::Identifier(name) => self.eval_call(*name, &[], &[], Some(left)),
+                    // When a stage mentions `_`, the piped value goes where
+                    // the placeholder says instead of becoming an implicit
+                    // first argument.
+                    Expr::Call(name, args, named) if mentions_placeholder(args, named) => {
+                        let saved = self.pipe_value.replace(left);
+                        let result = self.eval_call(*name, args, named, None);
+                        self.pipe_value = saved;
+                        result
+                    }
+                    Expr::Call(name, args, named) => {
+                        self.eval_call(*name, args, named, Some(left))
+                    }
+                    _ => Err("the right side of |> must be a function name or call".to_string()),
+                }
+            }
+            Expr::Input => self.get_input_value(),
+            Expr::InputMeta => self.get_input_meta(),
+        }
+    }
+
+    /// Evaluates a call's arguments and dispatches to a user function or
+    /// builtin. `piped` is the value the `|>` operator threads in as the
+    /// first positional argument.
+    fn eval_call(
+        &mut self,
+        name: Symbol,
+        args: &[Expr],
+        named: &[(Symbol, Expr)],
+        piped: Option<Value>,
+    ) -> Result<Value, String> {
+        let mut values = Vec::with_capacity(args.len() + usize::from(piped.is_some()));
+        values.extend(piped);
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
+        let mut named_values = Vec::with_capacity(named.len());
+        for (arg_name, arg) in named {
+            named_values.push((*arg_name, self.eval_expr(arg)?));
+        }
+        if self.functions.contains_key(&name) {
+            let values = self.match_named_args(name, values, named_values)?;
+            self.call_function(name, values)
+        } else if named_values.is_empty() {
+            self.call_builtin(name, values)
+        } else {
+            Err(format!("{name} does not accept named arguments"))
+        }
+    }
+
+    fn eval_number(&mut self, expr: &Expr) -> Result<i64, String> {
+        match self.eval_expr(expr)? {
+            Value::Number(n) => Ok(n),
+            other => Err(format!("expected number, got {}", other.type_name())),
+        }
+    }
+
+    /// The help text for a user function or builtin: its signature plus any
+    /// `///` doc comment (or the builtin's registered doc line).
+    pub(crate) fn doc_for(&self, name: &str) -> Option<String> {
+        if let Some(function) = self.functions.get(&Symbol::intern(name)) {
+            let params: Vec<String> = function.params.iter().map(Symbol::to_string).collect();
+            let header = format!("fn {name}({})", params.join(", "));
+            return Some(match &function.doc {
+                Some(doc) => format!("{header}\n{doc}"),
+                None => header,
+            });
+        }
+        builtins::lookup(name).map(|spec| spec.doc.to_string())
+    }
+
+    /// Resolves `name` to a memoized function, for the memo introspection
+    /// builtins.
+    fn memo_fn(&self, name: &str) -> Result<Symbol, String> {
+        let symbol = Symbol::intern(name);
+        match self.functions.get(&symbol) {
+            None => Err(format!("no function named {name}")),
+            Some(function) if !function.memoized => Err(format!("{name} is not a memo fn")),
+            Some(_) => Ok(symbol),
+        }
+    }
+
+    /// Cache hits, misses and live entry count for the named memo fn.
+    pub(crate) fn memo_stats(&self, name: &str) -> Result<(u64, u64, usize), String> {
+        let symbol = self.memo_fn(name)?;
+        let (hits, misses) = self.memo_stats.get(&symbol).copied().unwrap_or_default();
+        let size = self.memo_cache.keys().filter(|(f, _)| *f == symbol).count();
+        Ok((hits, misses, size))
+    }
+
+    /// Drops the named memo fn's cached results and resets its counters,
+    /// returning how many entries were freed.
+    pub(crate) fn memo_clear(&mut self, name: &str) -> Result<usize, String> {
+        let symbol = self.memo_fn(name)?;
+        let before = self.memo_cache.len();
+        self.memo_cache.retain(|(f, _), _| *f != symbol);
+        self.memo_stats.remove(&symbol);
+        Ok(before - self.memo_cache.len())
+    }
+
+    /// Slots named arguments into their positions in the parameter list,
+    /// after any positional arguments, producing a plain argument vector.
+    fn match_named_args(
+        &self,
+        name: Symbol,
+        mut args: Vec<Value>,
+        named: Vec<(Symbol, Value)>,
+    ) -> Result<Vec<Value>, String> {
+        let params = &self
+            .functions
+            .get(&name)
+            .ok_or_else(|| format!("undefined function: {name}"))?
+            .params;
+        if args.len() + named.len() > params.len() {
+            return Err(format!(
+                "{name} expects {} argument(s), got {}",
+                params.len(),
+                args.len() + named.len()
+            ));
+        }
+        let positional = args.len();
+        let mut slots: Vec<Option<Value>> = vec![None; params.len() - positional];
+        for (arg_name, value) in named {
+            let index = params
+                .iter()
+                .position(|param| *param == arg_name)
+                .ok_or_else(|| format!("{name} has no parameter named {arg_name}"))?;
+            if index < positional {
+                return Err(format!(
+                    "{name}: parameter {arg_name} already given positionally"
+                ));
+            }
+            if slots[index - positional].replace(value).is_some() {
+                return Err(format!("{name}: duplicate argument {arg_name}"));
+            }
+        }
+        for (slot, param) in slots.into_iter().zip(&params[positional..]) {
+            match slot {
+                Some(value) => args.push(value),
+                None => return Err(format!("{name}: missing argument {param}")),
+            }
+        }
+        Ok(args)
+    }
+
+    /// Calls a user-defined function by saving and restoring any globals the
+    /// parameters shadow.
+    fn call_function(&mut self, name: Symbol, args: Vec<Value>) -> Result<Value, String> {
+        let function = self
+            .functions
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| format!("undefined function: {name}"))?;
+        if args.len() != function.params.len() {
+            return Err(format!(
+                "{name} expects {} argument(s), got {}",
+                function.params.len(),
+                args.len()
+            ));
+        }
+        if function.memoized {
+            let key = (name, args.clone());
+            if let Some(cached) = self.memo_cache.get(&key) {
+                let cached = cached.clone();
+                self.memo_stats.entry(name).or_default().0 += 1;
+                return Ok(cached);
+            }
+            self.memo_stats.entry(name).or_default().1 += 1;
+        }
+        let started = self.profile.is_some().then(Instant::now);
+
+        // The call scope holds the parameter bindings and chains to the
+        // scope the function was defined in, not the caller's: a body that
+        // names an identifier it doesn't bind sees the definition site's
+        // variables (lexical scoping), never the caller's locals.
+        let bindings = function.params.iter().copied().zip(args.iter().cloned()).collect();
+        let call_env = Rc::new(RefCell::new(Env {
+            bindings,
+            parent: function.env.clone(),
+        }));
+        if self.call_stack.len() >= self.max_depth {
+            return Err(format!(
+                "stack overflow: recursion deeper than {} calls (raise with --max-depth)",
+                self.max_depth
+            ));
+        }
+        self.check_deadline()?;
+        let caller_env = self.env.replace(call_env);
+        // `_` inside the body is the callee's own, never a pipeline stage's.
+        let caller_pipe = self.pipe_value.take();
+        self.call_stack.push(name);
+        // Count one sample per call to make calls show up in the stacks.
+        self.folded_sample();
+        let result = match &function.body {
+            FnBody::Expr(expr) => self.eval_expr(expr),
+            // A block body runs its statements in the call scope; its
+            // result is the `_` the block assigns.
+            FnBody::Block(block) => self.eval_block(block).and_then(|flow| match flow {
+                Flow::Normal => self
+                    .env
+                    .as_ref()
+                    .and_then(|env| env.borrow().get(Symbol::intern("_")))
+                    .ok_or_else(|| format!("{name}: the function body must assign _")),
+                Flow::Break(Some(label)) => {
+                    Err(format!("break {label}: no enclosing loop with that label"))
+                }
+                Flow::Break(None) => Err("break outside of a loop".to_string()),
+                Flow::Continue => Err("continue outside of a loop".to_string()),
+                Flow::Return(value) => Ok(value),
+            }),
+        };
+        self.call_stack.pop();
+        self.pipe_value = caller_pipe;
+        self.env = caller_env;
+        let result = result?;
+
+        if let (Some(profile), Some(started)) = (self.profile.as_mut(), started) {
+            let entry = profile.entry(name).or_default();
+            entry.calls += 1;
+            entry.total += started.elapsed();
+        }
+        if function.memoized {
+            self.memo_cache.insert((name, args), result.clone());
+        }
+        Ok(result)
+    }
+
+    /// Calls a function through a [`Value::FnRef`], for builtins that take
+    /// function arguments.
+    pub(crate) fn call_fn_value(&mut self, func: &Value, args: Vec<Value>) -> Result<Value, String> {
+        match func {
+            Value::FnRef(name) => self.call_function(*name, args),
+            other => Err(format!("expected function, got {}", other.type_name())),
+        }
+    }
+
+    fn call_builtin(&mut self, name: Symbol, args: Vec<Value>) -> Result<Value, String> {
+        match builtins::lookup(&name.name()) {
+            Some(spec) => spec.call(self, args),
+            None => Err(format!("unknown function: {name}")),
+        }
+    }
+
+    fn evaluate_unary_op(&mut self, op: UnaryOp, value: Value) -> Result<Value, String> {
+        match op {
+            UnaryOp::Neg => match value {
+                Value::Number(n) => Ok(Value::Number(self.arith(BinOp::Sub, 0, n)?)),
+                other => Err(format!("cannot negate {}", other.type_name())),
+            },
+            UnaryOp::Not => Ok(Value::Bool(!self.is_truthy(&value))),
+            UnaryOp::ToNum => {
+                let result = to_number(&value)?;
+                if let (Value::Str(s), Value::Number(n)) = (&value, &result) {
+                    if s.trim() != n.to_string() {
+                        self.warn(format!("~ read \"{s}\" as {n}"));
+                    }
+                }
+                Ok(result)
+            }
+            UnaryOp::ToStr => Ok(Value::Str(value.to_string())),
+        }
+    }
+
+    /// Applies a numeric operator under the current [`OverflowMode`].
+    fn arith(&self, op: BinOp, a: i64, b: i64) -> Result<i64, String> {
+        let checked = match op {
+            BinOp::Add => a.checked_add(b),
+            BinOp::Sub => a.checked_sub(b),
+            BinOp::Mul => a.checked_mul(b),
+            _ => a.checked_div(b),
+        };
+        if let Some(value) = checked {
+            return Ok(value);
+        }
+        match self.overflow {
+            OverflowMode::Checked => Err(format!(
+                "number overflow evaluating {a} {} {b}",
+                op.symbol()
+            )),
+            OverflowMode::Wrapping => Ok(match op {
+                BinOp::Add => a.wrapping_add(b),
+                BinOp::Sub => a.wrapping_sub(b),
+                BinOp::Mul => a.wrapping_mul(b),
+                _ => a.wrapping_div(b),
+            }),
+            OverflowMode::Saturating => Ok(match op {
+                BinOp::Add => a.saturating_add(b),
+                BinOp::Sub => a.saturating_sub(b),
+                BinOp::Mul => a.saturating_mul(b),
+                // The only overflowing division is i64::MIN / -1.
+                _ => i64::MAX,
+            }),
+        }
+    }
+
+    fn evaluate_binary_op(&mut self, op: BinOp, lhs: Value, rhs: Value) -> Result<Value, String> {
+        match (op, &lhs, &rhs) {
+            (
+                BinOp::Add | BinOp::Sub | BinOp::Mul,
+                Value::Number(a),
+                Value::Number(b),
+            ) => Ok(Value::Number(self.arith(op, *a, *b)?)),
+            (BinOp::Div, Value::Number(a), Value::Number(b)) => {
+                if *b == 0 {
+                    Err("division by zero".to_string())
+                } else {
+                    Ok(Value::Number(self.arith(op, *a, *b)?))
+                }
+            }
+            (BinOp::Mod, Value::Number(a), Value::Number(b)) => {
+                if *b == 0 {
+                    Err("modulo by zero".to_string())
+                } else {
+                    Ok(Value::Number(a.rem_euclid(*b)))
+                }
+            }
+            (BinOp::Mul, Value::Str(s), Value::Number(n))
+            | (BinOp::Mul, Value::Number(n), Value::Str(s)) => {
+                let count = repeat_count(*n)?;
+                Ok(Value::Str(s.repeat(count)))
+            }
+            (BinOp::Mul, Value::Array1D(items), Value::Number(n))
+            | (BinOp::Mul, Value::Number(n), Value::Array1D(items)) => {
+                let count = repeat_count(*n)?;
+                let mut out = Vec::with_capacity(items.len() * count);
+                for _ in 0..count {
+                    out.extend(items.iter().cloned());
+                }
+                Ok(Value::Array1D(out))
+            }
+            (BinOp::Mul, Value::NumArray(nums), Value::Number(n))
+            | (BinOp::Mul, Value::Number(n), Value::NumArray(nums)) => {
+                let count = repeat_count(*n)?;
+                Ok(Value::NumArray(nums.repeat(count)))
+            }
+            (BinOp::Add, Value::Point(r1, c1), Value::Point(r2, c2)) => {
+                Ok(Value::Point(self.arith(op, *r1, *r2)?, self.arith(op, *c1, *c2)?))
+            }
+            (BinOp::Sub, Value::Point(r1, c1), Value::Point(r2, c2)) => {
+                Ok(Value::Point(self.arith(op, *r1, *r2)?, self.arith(op, *c1, *c2)?))
+            }
+            (BinOp::Mul, Value::Point(r, c), Value::Number(n))
+            | (BinOp::Mul, Value::Number(n), Value::Point(r, c)) => {
+                Ok(Value::Point(self.arith(op, *r, *n)?, self.arith(op, *c, *n)?))
+            }
+            (BinOp::Add | BinOp::Sub, Value::Point3(x1, y1, z1), Value::Point3(x2, y2, z2)) => {
+                Ok(Value::Point3(
+                    self.arith(op, *x1, *x2)?,
+                    self.arith(op, *y1, *y2)?,
+                    self.arith(op, *z1, *z2)?,
+                ))
+            }
+            (BinOp::Mul, Value::Point3(x, y, z), Value::Number(n))
+            | (BinOp::Mul, Value::Number(n), Value::Point3(x, y, z)) => Ok(Value::Point3(
+                self.arith(op, *x, *n)?,
+                self.arith(op, *y, *n)?,
+                self.arith(op, *z, *n)?,
+            )),
+            // A string concatenated with an array takes it as the chars it
+            // spells, so `"ab" + ["c"]` matches `["a", "b"] + ["c"]` joined.
+            (BinOp::Add, Value::Str(a), b) => match spelled(b) {
+                Some(s) => Ok(Value::Str(format!("{a}{s}"))),
+                None => Ok(Value::Str(format!("{a}{b}"))),
+            },
+            (BinOp::Add, Value::Array1D(a), Value::Str(s)) => {
+                let mut items = a.clone();
+                items.extend(s.chars().map(|c| Value::Str(c.to_string())));
+                Ok(Value::Array1D(items))
+            }
+            (BinOp::Add, Value::NumArray(a), Value::Str(s)) => {
+                let mut items = unpack(a);
+                items.extend(s.chars().map(|c| Value::Str(c.to_string())));
+                Ok(Value::Array1D(items))
+            }
+            (BinOp::Add, Value::Array1D(a), Value::Array1D(b)) => {
+                let mut items = a.clone();
+                items.extend(b.iter().cloned());
+                Ok(Value::Array1D(items))
+            }
+            (BinOp::Add, Value::NumArray(a), Value::NumArray(b)) => {
+                let mut nums = a.clone();
+                nums.extend_from_slice(b);
+                Ok(Value::NumArray(nums))
+            }
+            (BinOp::Add, Value::Array1D(a), Value::NumArray(b)) => {
+                let mut items = a.clone();
+                items.extend(unpack(b));
+                Ok(Value::Array1D(items))
+            }
+            (BinOp::Add, Value::NumArray(a), Value::Array1D(b)) => {
+                let mut items = unpack(a);
+                items.extend(b.iter().cloned());
+                Ok(Value::Array1D(items))
+            }
+            (BinOp::Eq | BinOp::NotEq, _, _) => {
+                let equal = values_equal(&lhs, &rhs);
+                Ok(Value::Bool(if op == BinOp::Eq { equal } else { !equal }))
+            }
+            (BinOp::Lt | BinOp::LtEq | BinOp::Gt | BinOp::GtEq, _, _) => {
+                let ord = compare_values(&lhs, &rhs)?;
+                let result = match op {
+                    BinOp::Lt => ord == std::cmp::Ordering::Less,
+                    BinOp::LtEq => ord != std::cmp::Ordering::Greater,
+                    BinOp::Gt => ord == std::cmp::Ordering::Greater,
+                    _ => ord != std::cmp::Ordering::Less,
+                };
+                Ok(Value::Bool(result))
+            }
+            _ => Err(format!(
+                "unsupported operands for {}: {} and {}",
+                op.symbol(),
+                lhs.type_name(),
+                rhs.type_name()
+            )),
+        }
+    }
+
+    pub(crate) fn index_value(&self, base: Value, index: Value) -> Result<Value, String> {
+        // Maps accept any hashable value as a key.
+        if let Value::Map(map) = &base {
+            return map
+                .entries
+                .get(&index)
+                .cloned()
+                .ok_or_else(|| format!("key {index} is not in the map"));
+        }
+        // A point indexes a grid directly: `grid[pos]` is `grid[r][c]`.
+        if let Value::Point(r, c) = index {
+            return match base {
+                Value::Array2D(rows) => {
+                    let r = resolve_index(r, rows.len())?;
+                    let row = &rows[r];
+                    let c = resolve_index(c, row.len())?;
+                    Ok(row[c].clone())
+                }
+                Value::Grid(g) => {
+                    let r = resolve_index(r, g.rows())?;
+                    let c = resolve_index(c, g.cols())?;
+                    Ok(Value::Str(g.get(r, c).to_string()))
+                }
+                Value::Sparse(grid) => Ok(grid
+                    .cells
+                    .get(&(r, c))
+                    .unwrap_or(&grid.default)
+                    .clone()),
+                other => Err(format!("cannot index {} with a point", other.type_name())),
+            };
+        }
+        let i = match index {
+            Value::Number(n) => n,
+            other => return Err(format!("index must be a number, got {}", other.type_name())),
+        };
+        match base {
+            Value::Array1D(items) => {
+                let idx = resolve_index(i, items.len())?;
+                Ok(items[idx].clone())
+            }
+            Value::NumArray(nums) => {
+                let idx = resolve_index(i, nums.len())?;
+                Ok(Value::Number(nums[idx]))
+            }
+            Value::Array2D(rows) => {
+                let idx = resolve_index(i, rows.len())?;
+                Ok(Value::Array1D(rows[idx].clone()))
+            }
+            Value::Grid(g) => {
+                let idx = resolve_index(i, g.rows())?;
+                Ok(Value::Array1D(g.row_values(idx)))
+            }
+            Value::Range(r) => Ok(Value::Number(r.get(i)?)),
+            Value::Point(r, c) => {
+                let idx = resolve_index(i, 2)?;
+                Ok(Value::Number(if idx == 0 { r } else { c }))
+            }
+            Value::Point3(x, y, z) => {
+                let idx = resolve_index(i, 3)?;
+                Ok(Value::Number([x, y, z][idx]))
+            }
+            Value::Str(s) => {
+                let chars: Vec<char> = s.chars().collect();
+                let idx = resolve_index(i, chars.len())?;
+                Ok(Value::Str(chars[idx].to_string()))
+            }
+            other => Err(format!("cannot index {}", other.type_name())),
+        }
+    }
+
+    /// Slices with clamping: out-of-range bounds are pulled into range, with
+    /// a warning so the silent truncation stays visible. Under
+    /// [`Self::set_strict_slices`] they error instead.
+    fn slice_value(
+        &mut self,
+        base: Value,
+        lo: Option<i64>,
+        hi: Option<i64>,
+    ) -> Result<Value, String> {
+        let mut clamped = false;
+        let mut seen_len = 0usize;
+        let mut bounds = |len: usize, lo: Option<i64>, hi: Option<i64>| -> (usize, usize) {
+            seen_len = len;
+            let mut resolve = |i: i64| -> usize {
+                let i = if i < 0 { i + len as i64 } else { i };
+                clamped |= i < 0 || i > len as i64;
+                i.clamp(0, len as i64) as usize
+            };
+            let lo = lo.map_or(0, &mut resolve);
+            let hi = hi.map_or(len, resolve);
+            clamped |= hi < lo;
+            (lo, hi.max(lo))
+        };
+        let describe = |i: Option<i64>| i.map(|i| i.to_string()).unwrap_or_default();
+        let result = match base {
+            Value::Array1D(items) => {
+                let (lo, hi) = bounds(items.len(), lo, hi);
+                Ok(Value::Array1D(items[lo..hi].to_vec()))
+            }
+            Value::NumArray(nums) => {
+                let (lo, hi) = bounds(nums.len(), lo, hi);
+                Ok(Value::NumArray(nums[lo..hi].to_vec()))
+            }
+            Value::Str(s) => {
+                let chars: Vec<char> = s.chars().collect();
+                let (lo, hi) = bounds(chars.len(), lo, hi);
+                Ok(Value::Str(chars[lo..hi].iter().collect()))
+            }
+            Value::Array2D(rows) => {
+                let (lo, hi) = bounds(rows.len(), lo, hi);
+                Ok(Value::Array2D(Rc::new(rows[lo..hi].to_vec())))
+            }
+            Value::Grid(g) => {
+                let (lo, hi) = bounds(g.rows(), lo, hi);
+                Ok(Value::Array2D(Rc::new(g.to_rows()[lo..hi].to_vec())))
+            }
+            other => Err(format!("cannot slice {}", other.type_name())),
+        };
+        if clamped {
+            if self.strict_slices {
+                return Err(format!(
+                    "slice [{}:{}] is out of range for length {seen_len}",
+                    describe(lo),
+                    describe(hi)
+                ));
+            }
+            self.warn(format!(
+                "slice [{}:{}] was clamped to fit",
+                describe(lo),
+                describe(hi)
+            ));
+        }
+        result
+    }
+
+    /// Truthiness, as used by conditions, `&&`/`||` and `!`.
+    ///
+    /// The rule is uniform: containers (strings, arrays, ranges, sparse
+    /// grids) are truthy when non-empty; numbers when non-zero; values that
+    /// aren't containers or numbers (points, functions) are always truthy.
+    pub(crate) fn is_truthy(&self, value: &Value) -> bool {
+        match value {
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0,
+            Value::Str(s) => !s.is_empty(),
+            Value::Array1D(items) => !items.is_empty(),
+            Value::NumArray(nums) => !nums.is_empty(),
+            Value::Array2D(rows) => !rows.is_empty(),
+            Value::Grid(g) => g.rows() > 0,
+            Value::Range(r) => r.len > 0,
+            Value::Point(..) | Value::Point3(..) => true,
+            Value::Sparse(grid) => !grid.cells.is_empty(),
+            Value::Graph(graph) => !graph.nodes().is_empty(),
+            Value::Bitset(bits) => bits.count() > 0,
+            Value::Cache(cache) => !cache.borrow().is_empty(),
+            Value::Map(map) => !map.entries.is_empty(),
+            Value::Set(set) => !set.items.is_empty(),
+            Value::RangeSet(rs) => !rs.intervals().is_empty(),
+            Value::FnRef(_) => true,
+        }
+    }
+
+    /// Converts the raw input text into its grid value: one row per line, one
+    /// single-character string per cell. Converted once and cached, so a
+    /// program that mentions `input` many times pays for the grid once.
+    fn get_input_value(&mut self) -> Result<Value, String> {
+        if let Some(grid) = &self.input_grid {
+            return Ok(grid.clone());
+        }
+        let input = self
+            .input
+            .as_ref()
+            .ok_or_else(|| "no input provided (use -i <file>)".to_string())?;
+        let input = input.clone();
+        let grid = self.grid_value(&input);
+        self.input_grid = Some(grid.clone());
+        Ok(grid)
+    }
+
+    /// Builds the `input.meta` map: `file`, `lines`, `width` and
+    /// `trailingNewline`. The file name is empty unless the embedder set
+    /// one, since the core never touches the filesystem itself.
+    fn get_input_meta(&self) -> Result<Value, String> {
+        let input = self
+            .input
+            .as_ref()
+            .ok_or_else(|| "no input provided (use -i <file>)".to_string())?;
+        let mut meta = MapVal::default();
+        let entry = |key: &str| Value::Str(key.to_string());
+        meta.entries.insert(
+            entry("file"),
+            Value::Str(self.input_name.clone().unwrap_or_default()),
+        );
+        meta.entries.insert(
+            entry("lines"),
+            Value::Number(input.lines().count() as i64),
+        );
+        meta.entries.insert(
+            entry("width"),
+            Value::Number(
+                input
+                    .lines()
+                    .map(|line| line.chars().count())
+                    .max()
+                    .unwrap_or(0) as i64,
+            ),
+        );
+        meta.entries
+            .insert(entry("trailingNewline"), Value::Bool(input.ends_with('\n')));
+        Ok(Value::Map(Rc::new(meta)))
+    }
+}
+
+/// Splits multi-line text into a 2d grid of one-character strings, the same
+/// shape `input` takes for grid puzzles.
+impl Interpreter {
+    /// [`grid_from_str`], plus a warning when the rows come out ragged —
+    /// usually a sign of stray whitespace in the input.
+    pub(crate) fn grid_value(&mut self, text: &str) -> Value {
+        let grid = grid_from_str(text);
+        if let Value::Array2D(rows) = &grid {
+            let widths: Vec<usize> = rows.iter().map(Vec::len).collect();
+            if let (Some(&min), Some(&max)) = (widths.iter().min(), widths.iter().max()) {
+                if min != max {
+                    self.warn(format!(
+                        "grid rows have differing widths ({min} to {max})"
+                    ));
+                }
+            }
+        }
+        grid
+    }
+}
+
+pub(crate) fn grid_from_str(text: &str) -> Value {
+    if let Some(grid) = CharGrid::from_text(text) {
+        return Value::Grid(Rc::new(grid));
+    }
+    let rows = text
+        .lines()
+        .map(|line| line.chars().map(|c| Value::Str(c.to_string())).collect())
+        .collect();
+    Value::Array2D(Rc::new(rows))
+}
+
+/// Edmonds-Karp on a residual capacity map: the maximum `s -> t` flow and
+/// which nodes stay reachable from `s` in the final residual graph (i.e. the
+/// source side of a minimum cut).
+fn edmonds_karp(
+    n: usize,
+    cap: &mut Residual,
+    adj: &[Vec<usize>],
+    s: usize,
+    t: usize,
+) -> (i64, Vec<bool>) {
+    let mut flow = 0;
+    loop {
+        // Shortest augmenting path by BFS.
+        let mut parent = vec![usize::MAX; n];
+        parent[s] = s;
+        let mut queue = VecDeque::from([s]);
+        while let Some(u) = queue.pop_front() {
+            for &v in &adj[u] {
+                if parent[v] == usize::MAX && cap.get(&(u, v)).copied().unwrap_or(0) > 0 {
+                    parent[v] = u;
+                    queue.push_back(v);
+                }
+            }
+        }
+        if parent[t] == usize::MAX {
+            let reachable = parent.iter().map(|&p| p != usize::MAX).collect();
+            return (flow, reachable);
+        }
+        let mut bottleneck = i64::MAX;
+        let mut v = t;
+        while v != s {
+            let u = parent[v];
+            bottleneck = bottleneck.min(cap[&(u, v)]);
+            v = u;
+        }
+        let mut v = t;
+        while v != s {
+            let u = parent[v];
+            *cap.get_mut(&(u, v)).unwrap() -= bottleneck;
+            *cap.get_mut(&(v, u)).unwrap() += bottleneck;
+            v = u;
+        }
+        flow += bottleneck;
+    }
+}
+
+/// Walks an index chain to a mutable element reference.
+fn place_mut<'a>(root: &'a mut Value, indices: &[Value]) -> Result<&'a mut Value, String> {
+    let mut current = root;
+    let mut i = 0;
+    while i < indices.len() {
+        // Writing through an index may store a non-number, so a packed array
+        // (or packed char grid) unpacks before handing out a mutable element.
+        if let Value::NumArray(nums) = current {
+            *current = Value::Array1D(unpack(nums));
+        }
+        if let Value::Grid(g) = current {
+            *current = Value::Array2D(Rc::new(g.to_rows()));
+        }
+        // Maps are keyed by arbitrary values; the update must find an
+        // existing entry to read.
+        if let Value::Map(map) = current {
+            let key = &indices[i];
+            let map = Rc::make_mut(map);
+            current = map
+                .entries
+                .get_mut(key)
+                .ok_or_else(|| format!("key {key} is not in the map"))?;
+            i += 1;
+            continue;
+        }
+        let index = match &indices[i] {
+            Value::Number(n) => *n,
+            Value::Point(r, c) => {
+                current = match current {
+                    Value::Array2D(rows) => {
+                        let rows = Rc::make_mut(rows);
+                        let r = resolve_index(*r, rows.len())?;
+                        let row = &mut rows[r];
+                        let c = resolve_index(*c, row.len())?;
+                        &mut row[c]
+                    }
+                    Value::Sparse(grid) => {
+                        // An unset cell materializes as the default so the
+                        // update has something to read.
+                        let grid = Rc::make_mut(grid);
+                        let default = grid.default.clone();
+                        grid.cells.entry((*r, *c)).or_insert(default)
+                    }
+                    other => {
+                        return Err(format!("cannot index {} with a point", other.type_name()))
+                    }
+                };
+                i += 1;
+                continue;
+            }
+            other => return Err(format!("index must be a number, got {}", other.type_name())),
+        };
+        current = match current {
+            Value::Array1D(items) => {
+                let idx = resolve_index(index, items.len())?;
+                i += 1;
+                &mut items[idx]
+            }
+            Value::Array2D(rows) => {
+                let rows = Rc::make_mut(rows);
+                let r = resolve_index(index, rows.len())?;
+                if i + 1 >= indices.len() {
+                    return Err("cannot assign to a whole row of a 2d array".to_string());
+                }
+                let row = &mut rows[r];
+                let c = match &indices[i + 1] {
+                    Value::Number(n) => resolve_index(*n, row.len())?,
+                    other => {
+                        return Err(format!("index must be a number, got {}", other.type_name()))
+                    }
+                };
+                i += 2;
+                &mut row[c]
+            }
+            other => return Err(format!("cannot index {}", other.type_name())),
+        };
+    }
+    Ok(current)
+}
+
+/// Structural equality, as used by `==` and `!=`.
+///
+/// Coercion rules:
+/// - numbers and bools compare numerically (`true` is 1, `false` is 0);
+/// - arrays (1D and 2D) compare element by element, and a 2D array equals a
+///   1D array of rows with the same elements;
+/// - a lazy range equals the array it would materialize to;
+/// - everything else must match in type and value.
+pub fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Bool(b)) | (Value::Bool(b), Value::Number(a)) => {
+            *a == i64::from(*b)
+        }
+        (Value::Array1D(a), Value::Array1D(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| values_equal(x, y))
+        }
+        (Value::NumArray(a), Value::NumArray(b)) => a == b,
+        (Value::Sparse(a), Value::Sparse(b)) => a == b,
+        (Value::Point(r1, c1), Value::Point(r2, c2)) => (r1, c1) == (r2, c2),
+        (Value::Point3(x1, y1, z1), Value::Point3(x2, y2, z2)) => (x1, y1, z1) == (x2, y2, z2),
+        (Value::Point(r, c), other) | (other, Value::Point(r, c)) => {
+            values_equal(&Value::NumArray(vec![*r, *c]), other)
+        }
+        (Value::Point3(x, y, z), other) | (other, Value::Point3(x, y, z)) => {
+            values_equal(&Value::NumArray(vec![*x, *y, *z]), other)
+        }
+        (Value::NumArray(nums), other) | (other, Value::NumArray(nums)) => {
+            values_equal(&Value::Array1D(unpack(nums)), other)
+        }
+        (Value::Grid(a), Value::Grid(b)) => a == b,
+        (Value::Grid(g), other) | (other, Value::Grid(g)) => {
+            values_equal(&Value::Array2D(Rc::new(g.to_rows())), other)
+        }
+        (Value::Array2D(a), Value::Array2D(b)) => {
+            a.len() == b.len()
+                && a.iter().zip(b.iter()).all(|(ra, rb)| {
+                    ra.len() == rb.len() && ra.iter().zip(rb).all(|(x, y)| values_equal(x, y))
+                })
+        }
+        (Value::Array2D(rows), Value::Array1D(items))
+        | (Value::Array1D(items), Value::Array2D(rows)) => {
+            rows.len() == items.len()
+                && rows
+                    .iter()
+                    .zip(items.iter())
+                    .all(|(row, item)| values_equal(&Value::Array1D(row.clone()), item))
+        }
+        (Value::Range(r), other) | (other, Value::Range(r)) => {
+            if let Value::Range(r2) = other {
+                return r == r2;
+            }
+            let materialized: Vec<Value> = r.iter().map(Value::Number).collect();
+            values_equal(&Value::Array1D(materialized), other)
+        }
+        _ => a == b,
+    }
+}
+
+pub(crate) fn repeat_count(n: i64) -> Result<usize, String> {
+    usize::try_from(n).map_err(|_| format!("repeat count must be non-negative, got {n}"))
+}
+
+fn resolve_index(i: i64, len: usize) -> Result<usize, String> {
+    let idx = if i < 0 { i + len as i64 } else { i };
+    if idx < 0 || idx as usize >= len {
+        Err(format!("index {i} out of bounds for length {len}"))
+    } else {
+        Ok(idx as usize)
+    }
+}
+
+/// Ordering for `<`, `<=`, `>` and `>=`. The coercion table is deliberately
+/// small: numbers and booleans share one number line (`false` is 0 and `true`
+/// is 1, the same reading `==` and `~` use), strings compare
+/// lexicographically, and every other pairing errors rather than invent an
+/// order.
+///
+/// |        | number  | bool    | string        |
+/// |--------|---------|---------|---------------|
+/// | number | numeric | numeric | error         |
+/// | bool   | numeric | numeric | error         |
+/// | string | error   | error   | lexicographic |
+pub(crate) fn compare_values(a: &Value, b: &Value) -> Result<std::cmp::Ordering, String> {
+    let as_number = |v: &Value| match v {
+        Value::Number(n) => Some(*n),
+        Value::Bool(b) => Some(i64::from(*b)),
+        _ => None,
+    };
+    match (a, b) {
+        (Value::Str(a), Value::Str(b)) => Ok(a.cmp(b)),
+        _ => match (as_number(a), as_number(b)) {
+            (Some(a), Some(b)) => Ok(a.cmp(&b)),
+            _ => Err(format!(
+                "cannot compare {} with {}",
+                a.type_name(),
+                b.type_name()
+            )),
+        },
+    }
+}
+
+pub(crate) fn to_number(value: &Value) -> Result<Value, String> {
+    match value {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::Bool(b) => Ok(Value::Number(i64::from(*b))),
+        other => {
+            let Some(s) = spelled(other) else {
+                return Err(format!("cannot convert {} to a number", other.type_name()));
+            };
+            s.trim()
+                .parse::<i64>()
+                .map(Value::Number)
+                .map_err(|_| format!("cannot convert \"{s}\" to a number"))
+        }
+    }
+}
+
+/// The string a value spells as a char sequence: a string is itself, and an
+/// array is its elements joined. This is the shared reading behind the
+/// string/char-array unification — `~`, `+` and friends all go through it so
+/// the two representations cannot drift apart.
+pub(crate) fn spelled(value: &Value) -> Option<String> {
+    match value {
+        Value::Str(s) => Some(s.to_string()),
+        Value::Array1D(items) => Some(items.iter().map(Value::to_string).collect()),
+        Value::NumArray(nums) => Some(nums.iter().map(i64::to_string).collect()),
+        _ => None,
+    }
+}
+
+/// Whether any argument of a pipeline stage mentions the `_` placeholder.
+fn mentions_placeholder(args: &[Expr], named: &[(Symbol, Expr)]) -> bool {
+    use visit::Visitor;
+
+    struct Finder(bool);
+    impl visit::Visitor for Finder {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if matches!(expr, Expr::Identifier(name) if *name == Symbol::intern("_")) {
+                self.0 = true;
+            }
+            visit::walk_expr(self, expr);
+        }
+    }
+    let mut finder = Finder(false);
+    for arg in args.iter().chain(named.iter().map(|(_, arg)| arg)) {
+        finder.visit_expr(arg);
+    }
+    finder.0
+}
+
+fn describe_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Assign { name, .. } => format!("{name} = ..."),
+        Stmt::GlobalAssign { name, .. } => format!("global {name} = ..."),
+        Stmt::IndexAssign { target, .. } => {
+            let indices = "[...]".repeat(target.indices.len());
+            format!("{}{indices} = ...", target.name)
+        }
+        Stmt::AssignOp { target, op, .. } => {
+            let indices = "[...]".repeat(target.indices.len());
+            format!("{}{indices} {}= ...", target.name, op.symbol())
+        }
+        Stmt::FnDef { name, params, .. } => {
+            let params: Vec<String> = params.iter().map(Symbol::to_string).collect();
+            format!("fn {name}({})", params.join(", "))
+        }
+        Stmt::If { .. } => "if".to_string(),
+        Stmt::While { .. } => "while".to_string(),
+        Stmt::For { var, .. } => format!("for {var}"),
+        Stmt::Break { label: Some(label) } => format!("break {label}"),
+        Stmt::Break { label: None } => "break".to_string(),
+        Stmt::Continue => "continue".to_string(),
+        Stmt::Return(_) => "return ...".to_string(),
+        Stmt::Match { arms, .. } => format!("match ({} arms)", arms.len()),
+        Stmt::Expr(_) => "expr".to_string(),
+    }
+}
@@ -0,0 +1,377 @@
+//! Tokenizer for xmas source code.
+//!
+//! Newlines separate statements, so the lexer emits [`Token::Newline`] — but
+//! only at bracket depth zero, so expressions can span lines inside `(...)`
+//! and `[...]`.
+
+use crate::error::{ErrorKind, XmasError};
+use crate::intern::Symbol;
+
+/// A single token with its source position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Number(i64),
+    Str(String),
+    Ident(Symbol),
+
+    // Keywords
+    True,
+    False,
+    If,
+    Else,
+    For,
+    While,
+    In,
+    Fn,
+    Memo,
+    Input,
+    Break,
+    Continue,
+    Return,
+    Alias,
+    Match,
+    Global,
+
+    // Operators
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Eq,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    EqEq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    AndAnd,
+    OrOr,
+    Bang,
+    Tilde,
+    Dollar,
+    PipeOp,
+
+    // Punctuation
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Comma,
+    Dot,
+    DotDot,
+    /// `...`, the rest marker in match patterns.
+    Ellipsis,
+    Colon,
+    /// `=>`, separating a match pattern from its arm.
+    FatArrow,
+
+    /// A `/// ...` doc comment line; the parser attaches it to the next
+    /// function definition.
+    Doc(String),
+
+    Newline,
+    Eof,
+}
+
+/// Converts `source` into a token stream, or an error pointing at the
+/// offending character.
+pub fn lex(source: &str) -> Result<Vec<SpannedToken>, XmasError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut col = 1;
+    // Bracket depth of ( and [; newlines inside brackets are not significant.
+    let mut depth = 0usize;
+
+    macro_rules! push {
+        ($tok:expr, $len:expr) => {{
+            tokens.push(SpannedToken {
+                token: $tok,
+                line,
+                col,
+            });
+            i += $len;
+            col += $len;
+        }};
+    }
+
+    // Skip a leading `#!...` line so scripts can use `#!/usr/bin/env xmas`.
+    if source.starts_with("#!") {
+        while i < chars.len() && chars[i] != '\n' {
+            i += 1;
+        }
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\r' => {
+                i += 1;
+                col += 1;
+            }
+            '\n' => {
+                if depth == 0 {
+                    tokens.push(SpannedToken {
+                        token: Token::Newline,
+                        line,
+                        col,
+                    });
+                }
+                i += 1;
+                line += 1;
+                col = 1;
+            }
+            '/' if chars.get(i + 1) == Some(&'/') && chars.get(i + 2) == Some(&'/') => {
+                let start = i + 3;
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                let text: String = chars[start.min(i)..i].iter().collect();
+                tokens.push(SpannedToken {
+                    token: Token::Doc(text.trim().to_string()),
+                    line,
+                    col,
+                });
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '0'..='9' => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let value = text
+                    .parse::<i64>()
+                    .map_err(|_| {
+                        XmasError::new(ErrorKind::Lex, format!("number too large: {text}"))
+                            .at(line, col)
+                    })?;
+                tokens.push(SpannedToken {
+                    token: Token::Number(value),
+                    line,
+                    col,
+                });
+                col += i - start;
+            }
+            '"' => {
+                let start_line = line;
+                let start_col = col;
+                i += 1;
+                col += 1;
+                let mut text = String::new();
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(XmasError::new(ErrorKind::Lex, "unterminated string")
+                                .at(start_line, start_col))
+                        }
+                        Some('"') => {
+                            i += 1;
+                            col += 1;
+                            break;
+                        }
+                        Some('\\') => {
+                            let escaped = match chars.get(i + 1) {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some('\\') => '\\',
+                                Some('"') => '"',
+                                other => {
+                                    return Err(XmasError::new(
+                                        ErrorKind::Lex,
+                                        format!(
+                                            "unknown escape \\{}",
+                                            other.map(|c| c.to_string()).unwrap_or_default()
+                                        ),
+                                    )
+                                    .at(line, col))
+                                }
+                            };
+                            text.push(escaped);
+                            i += 2;
+                            col += 2;
+                        }
+                        Some(&ch) => {
+                            text.push(ch);
+                            i += 1;
+                            col += 1;
+                        }
+                    }
+                }
+                tokens.push(SpannedToken {
+                    token: Token::Str(text),
+                    line: start_line,
+                    col: start_col,
+                });
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.as_str() {
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    "if" => Token::If,
+                    "else" => Token::Else,
+                    "for" => Token::For,
+                    "while" => Token::While,
+                    "in" => Token::In,
+                    "fn" => Token::Fn,
+                    "memo" => Token::Memo,
+                    "input" => Token::Input,
+                    "break" => Token::Break,
+                    "continue" => Token::Continue,
+                    "return" => Token::Return,
+                    "alias" => Token::Alias,
+                    "match" => Token::Match,
+                    "global" => Token::Global,
+                    _ => Token::Ident(Symbol::intern(&word)),
+                };
+                tokens.push(SpannedToken { token, line, col });
+                col += i - start;
+            }
+            '+' if chars.get(i + 1) == Some(&'=') => push!(Token::PlusEq, 2),
+            '+' => push!(Token::Plus, 1),
+            '-' if chars.get(i + 1) == Some(&'=') => push!(Token::MinusEq, 2),
+            '-' => push!(Token::Minus, 1),
+            '*' if chars.get(i + 1) == Some(&'=') => push!(Token::StarEq, 2),
+            '*' => push!(Token::Star, 1),
+            '/' if chars.get(i + 1) == Some(&'=') => push!(Token::SlashEq, 2),
+            '/' => push!(Token::Slash, 1),
+            '%' => push!(Token::Percent, 1),
+            '=' if chars.get(i + 1) == Some(&'=') => push!(Token::EqEq, 2),
+            '=' if chars.get(i + 1) == Some(&'>') => push!(Token::FatArrow, 2),
+            '=' => push!(Token::Eq, 1),
+            '!' if chars.get(i + 1) == Some(&'=') => push!(Token::NotEq, 2),
+            '!' => push!(Token::Bang, 1),
+            '<' if chars.get(i + 1) == Some(&'=') => push!(Token::LtEq, 2),
+            '<' => push!(Token::Lt, 1),
+            '>' if chars.get(i + 1) == Some(&'=') => push!(Token::GtEq, 2),
+            '>' => push!(Token::Gt, 1),
+            '&' if chars.get(i + 1) == Some(&'&') => push!(Token::AndAnd, 2),
+            '|' if chars.get(i + 1) == Some(&'|') => push!(Token::OrOr, 2),
+            '|' if chars.get(i + 1) == Some(&'>') => push!(Token::PipeOp, 2),
+            '~' => push!(Token::Tilde, 1),
+            '$' => push!(Token::Dollar, 1),
+            '(' => {
+                depth += 1;
+                push!(Token::LParen, 1)
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                push!(Token::RParen, 1)
+            }
+            '[' => {
+                depth += 1;
+                push!(Token::LBracket, 1)
+            }
+            ']' => {
+                depth = depth.saturating_sub(1);
+                push!(Token::RBracket, 1)
+            }
+            '{' => push!(Token::LBrace, 1),
+            '}' => push!(Token::RBrace, 1),
+            ',' => push!(Token::Comma, 1),
+            '.' if chars.get(i + 1) == Some(&'.') && chars.get(i + 2) == Some(&'.') => {
+                push!(Token::Ellipsis, 3)
+            }
+            '.' if chars.get(i + 1) == Some(&'.') => push!(Token::DotDot, 2),
+            '.' => push!(Token::Dot, 1),
+            ':' => push!(Token::Colon, 1),
+            other => {
+                return Err(XmasError::new(
+                    ErrorKind::Lex,
+                    format!("unexpected character '{other}'"),
+                )
+                .at(line, col))
+            }
+        }
+    }
+
+    tokens.push(SpannedToken {
+        token: Token::Eof,
+        line,
+        col,
+    });
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<Token> {
+        lex(source).unwrap().into_iter().map(|t| t.token).collect()
+    }
+
+    #[test]
+    fn lexes_assignment() {
+        assert_eq!(
+            kinds("x = 42"),
+            vec![
+                Token::Ident(Symbol::intern("x")),
+                Token::Eq,
+                Token::Number(42),
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_string_escapes() {
+        assert_eq!(
+            kinds(r#""a\nb""#),
+            vec![Token::Str("a\nb".into()), Token::Eof]
+        );
+    }
+
+    #[test]
+    fn newlines_inside_brackets_are_not_tokens() {
+        let toks = kinds("[1,\n2]");
+        assert!(!toks.contains(&Token::Newline));
+    }
+
+    #[test]
+    fn skips_leading_shebang_line() {
+        let toks = kinds("#!/usr/bin/env xmas\nx = 1");
+        assert_eq!(
+            toks,
+            vec![
+                Token::Newline,
+                Token::Ident(Symbol::intern("x")),
+                Token::Eq,
+                Token::Number(1),
+                Token::Eof
+            ]
+        );
+        // `#` anywhere else is still an error.
+        assert!(lex("x = #1").is_err());
+    }
+
+    #[test]
+    fn reports_position_of_bad_character() {
+        let err = lex("x = @").unwrap_err();
+        assert!(err.to_string().contains("line 1, col 5"), "{err}");
+    }
+}
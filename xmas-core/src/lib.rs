@@ -0,0 +1,40 @@
+//! The xmas language: a tiny scripting language for Advent of Code solutions.
+//!
+//! A program is a sequence of statements. The special variable `_` holds the
+//! program result, which the CLI prints after execution. Puzzle input is
+//! exposed through the `input` keyword as a 2D grid of one-character strings.
+//!
+//! This crate contains only the language — lexer, parser, AST and
+//! interpreter — with no filesystem or process dependencies, so it can be
+//! embedded in other tools. File handling and flag parsing live in the
+//! `xmas-cli` crate.
+
+pub mod ast;
+pub mod builtins;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod intern;
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+pub mod regex;
+pub mod snapshot;
+pub mod test_support;
+pub mod visit;
+
+use error::XmasError;
+use interpreter::{Interpreter, Value};
+
+/// Lexes, parses and runs `source` with optional puzzle `input`, returning
+/// the value of `_` if the program set one.
+pub fn run_source(source: &str, input: Option<&str>) -> Result<Option<Value>, XmasError> {
+    let tokens = lexer::lex(source)?;
+    let program = parser::parse(tokens, source)?;
+    let mut interp = Interpreter::new();
+    if let Some(input) = input {
+        interp.set_input(input.to_string());
+    }
+    interp.run(&program)?;
+    Ok(interp.result())
+}
@@ -0,0 +1,1110 @@
+//! Recursive-descent parser for xmas.
+//!
+//! Operator precedence, loosest to tightest; binary operators are
+//! left-associative:
+//!
+//! | level | operators            |
+//! |-------|----------------------|
+//! | 1     | `\|>`                |
+//! | 2     | `\|\|`               |
+//! | 3     | `&&`                 |
+//! | 4     | `==` `!=` `<` `<=` `>` `>=` |
+//! | 5     | `+` `-`              |
+//! | 6     | `*` `/` `%`          |
+//! | 7     | unary `-` `!` `~`    |
+//! | 8     | indexing, slicing, calls |
+//!
+//! The right-hand side of `\|>` must name a function or be a call; the
+//! interpreter threads the piped value in as its first argument, so
+//! `x \|> f \|> g(2)` means `g(f(x), 2)`.
+
+use std::collections::HashMap;
+
+use crate::ast::{AssignTarget, BinOp, Block, Expr, FnBody, MatchArm, Pattern, Stmt, UnaryOp};
+use crate::error::{ErrorKind, XmasError};
+use crate::intern::Symbol;
+use crate::lexer::{SpannedToken, Token};
+use crate::visit::{fold_expr, Folder};
+
+/// Positional arguments followed by `name = expr` named arguments.
+type CallArgs = (Vec<Expr>, Vec<(Symbol, Expr)>);
+
+fn compound_op(token: &Token) -> BinOp {
+    match token {
+        Token::PlusEq => BinOp::Add,
+        Token::MinusEq => BinOp::Sub,
+        Token::StarEq => BinOp::Mul,
+        _ => BinOp::Div,
+    }
+}
+
+/// Parses a token stream into a program. The `source` parameter is unused
+/// today but kept in the signature so error reporting can grow source
+/// excerpts without touching every caller.
+pub fn parse(mut tokens: Vec<SpannedToken>, _source: &str) -> Result<Block, XmasError> {
+    // `peek`/`advance` rely on a trailing Eof; the lexer always provides one,
+    // but callers constructing token streams by hand might not.
+    if tokens.last().map(|t| &t.token) != Some(&Token::Eof) {
+        let (line, col) = tokens.last().map_or((1, 1), |t| (t.line, t.col));
+        tokens.push(SpannedToken {
+            token: Token::Eof,
+            line,
+            col,
+        });
+    }
+    let mut parser = Parser {
+        tokens,
+        current: 0,
+        depth: 0,
+        aliases: HashMap::new(),
+    };
+    parser.parse_program()
+}
+
+/// Parses a token stream as one expression, as the `eval` builtin needs.
+/// Anything left over after the expression is an error.
+pub fn parse_expression(mut tokens: Vec<SpannedToken>, _source: &str) -> Result<Expr, XmasError> {
+    if tokens.last().map(|t| &t.token) != Some(&Token::Eof) {
+        let (line, col) = tokens.last().map_or((1, 1), |t| (t.line, t.col));
+        tokens.push(SpannedToken {
+            token: Token::Eof,
+            line,
+            col,
+        });
+    }
+    let mut parser = Parser {
+        tokens,
+        current: 0,
+        depth: 0,
+        aliases: HashMap::new(),
+    };
+    while parser.check(&Token::Newline) {
+        parser.advance();
+    }
+    let expr = parser.parse_expr()?;
+    while parser.check(&Token::Newline) {
+        parser.advance();
+    }
+    if !parser.check(&Token::Eof) {
+        return Err(parser.error_at("expected a single expression"));
+    }
+    Ok(expr)
+}
+
+/// How deeply expressions and statements may nest before parsing bails out,
+/// so pathological inputs like `((((...` error instead of blowing the stack.
+/// Kept low enough that the recursion fits comfortably in a 2 MB thread
+/// stack even in debug builds; real programs nest nowhere near this deep.
+const MAX_DEPTH: usize = 100;
+
+struct Parser {
+    tokens: Vec<SpannedToken>,
+    current: usize,
+    depth: usize,
+    /// `alias` templates, expanded into the AST where they are used.
+    aliases: HashMap<Symbol, (Vec<Symbol>, Expr)>,
+}
+
+/// Replaces an alias's parameters with the argument expressions from its use
+/// site. Only the parameters are touched, so names the alias body shares
+/// with the surrounding program mean what they mean there.
+struct Substitute {
+    params: Vec<Symbol>,
+    args: Vec<Expr>,
+}
+
+impl Folder for Substitute {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        if let Expr::Identifier(name) = &expr {
+            if let Some(i) = self.params.iter().position(|param| param == name) {
+                return self.args[i].clone();
+            }
+        }
+        fold_expr(self, expr)
+    }
+}
+
+impl Parser {
+    fn parse_program(&mut self) -> Result<Block, XmasError> {
+        let mut stmts = Vec::new();
+        self.skip_newlines();
+        while !self.check(&Token::Eof) {
+            if let Some(stmt) = self.parse_stmt()? {
+                stmts.push(stmt);
+            }
+            self.skip_newlines();
+        }
+        Ok(stmts)
+    }
+
+    fn parse_stmt(&mut self) -> Result<Option<(usize, Stmt)>, XmasError> {
+        self.enter()?;
+        let result = self.parse_stmt_inner();
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_stmt_inner(&mut self) -> Result<Option<(usize, Stmt)>, XmasError> {
+        // Collect leading `///` lines; they document a following fn def and
+        // are ignored before anything else.
+        let mut doc_lines = Vec::new();
+        while let Token::Doc(text) = &self.peek().token {
+            doc_lines.push(text.clone());
+            self.advance();
+            self.skip_newlines();
+        }
+        let doc = (!doc_lines.is_empty()).then(|| doc_lines.join("\n"));
+        let line = self.peek().line;
+        let stmt = match &self.peek().token {
+            // Alias definitions live entirely in the parser: the template is
+            // recorded and no statement reaches the interpreter.
+            Token::Alias => {
+                self.parse_alias()?;
+                self.expect_stmt_end()?;
+                return Ok(None);
+            }
+            Token::Fn | Token::Memo => self.parse_fn_def(doc)?,
+            Token::Global => {
+                self.advance();
+                let name = self.expect_ident()?;
+                self.expect(&Token::Eq)?;
+                let value = self.parse_expr()?;
+                Stmt::GlobalAssign { name, value }
+            }
+            Token::If => self.parse_if()?,
+            Token::Match => self.parse_match()?,
+            Token::While => self.parse_while(None)?,
+            Token::For => self.parse_for(None)?,
+            Token::Break => {
+                self.advance();
+                let label = match &self.peek().token {
+                    Token::Ident(_) => Some(self.expect_ident()?),
+                    _ => None,
+                };
+                Stmt::Break { label }
+            }
+            Token::Continue => {
+                self.advance();
+                Stmt::Continue
+            }
+            Token::Return => {
+                self.advance();
+                Stmt::Return(self.parse_expr()?)
+            }
+            Token::Ident(_) => {
+                // Assignment if an `=`/`+=`/... follows the name (or an
+                // indexed target like `grid[r][c]`); otherwise an expression
+                // statement.
+                match self.tokens.get(self.current + 1).map(|t| &t.token) {
+                    Some(Token::Eq) => {
+                        let name = self.expect_ident()?;
+                        self.advance(); // =
+                        let value = self.parse_expr()?;
+                        Stmt::Assign { name, value }
+                    }
+                    Some(op @ (Token::PlusEq | Token::MinusEq | Token::StarEq | Token::SlashEq)) => {
+                        let op = compound_op(op);
+                        let name = self.expect_ident()?;
+                        self.advance();
+                        let value = self.parse_expr()?;
+                        Stmt::AssignOp {
+                            target: AssignTarget {
+                                name,
+                                indices: Vec::new(),
+                            },
+                            op,
+                            value,
+                        }
+                    }
+                    Some(Token::LBracket) => match self.try_parse_indexed_assign()? {
+                        Some(stmt) => stmt,
+                        None => Stmt::Expr(self.parse_expr()?),
+                    },
+                    // `label: for (...)` / `label: while (...)`
+                    Some(Token::Colon) => {
+                        let label = self.expect_ident()?;
+                        self.advance(); // :
+                        match &self.peek().token {
+                            Token::For => self.parse_for(Some(label))?,
+                            Token::While => self.parse_while(Some(label))?,
+                            other => {
+                                let message =
+                                    format!("expected a loop after label, found {other:?}");
+                                return Err(self.error_at(message));
+                            }
+                        }
+                    }
+                    _ => Stmt::Expr(self.parse_expr()?),
+                }
+            }
+            _ => Stmt::Expr(self.parse_expr()?),
+        };
+        self.expect_stmt_end()?;
+        Ok(Some((line, stmt)))
+    }
+
+    /// Parses `alias name = expr` or `alias name(a, b) = expr`, recording
+    /// the template for expansion at use sites.
+    fn parse_alias(&mut self) -> Result<(), XmasError> {
+        self.expect(&Token::Alias)?;
+        let name = self.expect_ident()?;
+        let mut params = Vec::new();
+        if self.check(&Token::LParen) {
+            self.advance();
+            if !self.check(&Token::RParen) {
+                loop {
+                    params.push(self.expect_ident()?);
+                    if !self.check(&Token::Comma) {
+                        break;
+                    }
+                    self.advance();
+                }
+            }
+            self.expect(&Token::RParen)?;
+        }
+        self.expect(&Token::Eq)?;
+        // Uses of earlier aliases in the body expand here, so aliases nest.
+        let body = self.parse_expr()?;
+        self.aliases.insert(name, (params, body));
+        Ok(())
+    }
+
+    /// Tries to parse `name[i]...[j] = expr` or `name[i]...[j] op= expr`,
+    /// rewinding and returning `None` if the index chain isn't followed by
+    /// an assignment operator.
+    fn try_parse_indexed_assign(&mut self) -> Result<Option<Stmt>, XmasError> {
+        let start = self.current;
+        let name = self.expect_ident()?;
+        let mut indices = Vec::new();
+        while self.check(&Token::LBracket) {
+            self.advance();
+            match self.parse_expr() {
+                Ok(index) => indices.push(index),
+                Err(_) => {
+                    self.current = start;
+                    return Ok(None);
+                }
+            }
+            if !self.check(&Token::RBracket) {
+                self.current = start;
+                return Ok(None);
+            }
+            self.advance();
+        }
+        let op = match &self.peek().token {
+            Token::Eq => None,
+            op @ (Token::PlusEq | Token::MinusEq | Token::StarEq | Token::SlashEq) => {
+                Some(compound_op(op))
+            }
+            _ => {
+                self.current = start;
+                return Ok(None);
+            }
+        };
+        self.advance();
+        let value = self.parse_expr()?;
+        let target = AssignTarget { name, indices };
+        Ok(Some(match op {
+            None => Stmt::IndexAssign { target, value },
+            Some(op) => Stmt::AssignOp { target, op, value },
+        }))
+    }
+
+    fn parse_fn_def(&mut self, doc: Option<String>) -> Result<Stmt, XmasError> {
+        let memoized = if self.check(&Token::Memo) {
+            self.advance();
+            true
+        } else {
+            false
+        };
+        self.expect(&Token::Fn)?;
+        let name = self.expect_ident()?;
+        self.expect(&Token::LParen)?;
+        let mut params = Vec::new();
+        if !self.check(&Token::RParen) {
+            loop {
+                params.push(self.expect_ident()?);
+                if !self.check(&Token::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        self.expect(&Token::RParen)?;
+        let body = if self.check(&Token::LBrace) {
+            FnBody::Block(self.parse_block()?)
+        } else {
+            self.expect(&Token::Eq)?;
+            FnBody::Expr(self.parse_expr()?)
+        };
+        Ok(Stmt::FnDef {
+            name,
+            params,
+            body,
+            memoized,
+            doc,
+        })
+    }
+
+    fn parse_if(&mut self) -> Result<Stmt, XmasError> {
+        self.expect(&Token::If)?;
+        self.expect(&Token::LParen)?;
+        let cond = self.parse_expr()?;
+        self.expect(&Token::RParen)?;
+        let then_block = self.parse_block()?;
+        let else_block = if self.check_skipping_newlines(&Token::Else) {
+            self.skip_newlines();
+            self.advance(); // else
+            if self.check(&Token::If) {
+                let line = self.peek().line;
+                self.enter()?;
+                let chained = self.parse_if();
+                self.depth -= 1;
+                vec![(line, chained?)]
+            } else {
+                self.parse_block()?
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(Stmt::If {
+            cond,
+            then_block,
+            else_block,
+        })
+    }
+
+    fn parse_while(&mut self, label: Option<Symbol>) -> Result<Stmt, XmasError> {
+        self.expect(&Token::While)?;
+        self.expect(&Token::LParen)?;
+        let cond = self.parse_expr()?;
+        self.expect(&Token::RParen)?;
+        let body = self.parse_block()?;
+        Ok(Stmt::While { cond, body, label })
+    }
+
+    fn parse_for(&mut self, label: Option<Symbol>) -> Result<Stmt, XmasError> {
+        self.expect(&Token::For)?;
+        self.expect(&Token::LParen)?;
+        let var = self.expect_ident()?;
+        self.expect(&Token::In)?;
+        let iter = self.parse_expr()?;
+        let init = if self.check(&Token::Comma) {
+            self.advance();
+            let name = self.expect_ident()?;
+            self.expect(&Token::Eq)?;
+            Some((name, self.parse_expr()?))
+        } else {
+            None
+        };
+        self.expect(&Token::RParen)?;
+        let body = self.parse_block()?;
+        Ok(Stmt::For {
+            var,
+            iter,
+            init,
+            body,
+            label,
+        })
+    }
+
+    /// Parses `match (expr) { pattern => { ... } ... }`.
+    fn parse_match(&mut self) -> Result<Stmt, XmasError> {
+        self.expect(&Token::Match)?;
+        self.expect(&Token::LParen)?;
+        let subject = self.parse_expr()?;
+        self.expect(&Token::RParen)?;
+        self.expect(&Token::LBrace)?;
+        self.skip_newlines();
+        let mut arms = Vec::new();
+        while !self.check(&Token::RBrace) {
+            if self.check(&Token::Eof) {
+                return Err(self.error_at("unexpected end of input inside match"));
+            }
+            let pattern = self.parse_pattern()?;
+            self.expect(&Token::FatArrow)?;
+            arms.push(MatchArm {
+                pattern,
+                body: self.parse_block()?,
+            });
+            self.skip_newlines();
+        }
+        self.advance(); // }
+        Ok(Stmt::Match { subject, arms })
+    }
+
+    /// Parses a match pattern: a literal, a binding name, or an array shape
+    /// like `[a, "->", b]` or `[head, ...tail]`.
+    fn parse_pattern(&mut self) -> Result<Pattern, XmasError> {
+        self.enter()?;
+        let result = self.parse_pattern_inner();
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_pattern_inner(&mut self) -> Result<Pattern, XmasError> {
+        match self.peek().token.clone() {
+            Token::Number(n) => {
+                self.advance();
+                Ok(Pattern::Literal(Expr::Number(n)))
+            }
+            Token::Minus => {
+                self.advance();
+                match self.peek().token {
+                    Token::Number(n) => {
+                        self.advance();
+                        Ok(Pattern::Literal(Expr::Number(-n)))
+                    }
+                    _ => Err(self.error_at("expected a number after - in pattern")),
+                }
+            }
+            Token::Str(text) => {
+                self.advance();
+                Ok(Pattern::Literal(Expr::Str(text)))
+            }
+            Token::True => {
+                self.advance();
+                Ok(Pattern::Literal(Expr::Bool(true)))
+            }
+            Token::False => {
+                self.advance();
+                Ok(Pattern::Literal(Expr::Bool(false)))
+            }
+            Token::Ident(name) => {
+                self.advance();
+                Ok(Pattern::Binding(name))
+            }
+            Token::LBracket => {
+                self.advance();
+                let mut items = Vec::new();
+                let mut rest = None;
+                if !self.check(&Token::RBracket) {
+                    loop {
+                        // `...rest` swallows the remaining elements, so it
+                        // must come last.
+                        if self.check(&Token::Ellipsis) {
+                            self.advance();
+                            rest = Some(self.expect_ident()?);
+                            break;
+                        }
+                        items.push(self.parse_pattern()?);
+                        if !self.check(&Token::Comma) {
+                            break;
+                        }
+                        self.advance();
+                    }
+                }
+                self.expect(&Token::RBracket)?;
+                Ok(Pattern::Array(items, rest))
+            }
+            other => Err(self.error_at(format!("expected a pattern, found {other:?}"))),
+        }
+    }
+
+    fn parse_block(&mut self) -> Result<Block, XmasError> {
+        self.expect(&Token::LBrace)?;
+        let mut stmts = Vec::new();
+        self.skip_newlines();
+        while !self.check(&Token::RBrace) {
+            if self.check(&Token::Eof) {
+                return Err(self.error_at("unexpected end of input inside block"));
+            }
+            if let Some(stmt) = self.parse_stmt()? {
+                stmts.push(stmt);
+            }
+            self.skip_newlines();
+        }
+        self.advance(); // }
+        Ok(stmts)
+    }
+
+    // Expressions, lowest to highest precedence.
+
+    fn parse_expr(&mut self) -> Result<Expr, XmasError> {
+        self.enter()?;
+        let result = self.parse_pipe();
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_pipe(&mut self) -> Result<Expr, XmasError> {
+        let mut expr = self.parse_or()?;
+        while self.check(&Token::PipeOp) {
+            self.advance();
+            let rhs = self.parse_or()?;
+            expr = Expr::Pipe(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, XmasError> {
+        let mut expr = self.parse_and()?;
+        while self.check(&Token::OrOr) {
+            self.advance();
+            let rhs = self.parse_and()?;
+            expr = Expr::Binary(Box::new(expr), BinOp::Or, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, XmasError> {
+        let mut expr = self.parse_comparison()?;
+        while self.check(&Token::AndAnd) {
+            self.advance();
+            let rhs = self.parse_comparison()?;
+            expr = Expr::Binary(Box::new(expr), BinOp::And, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, XmasError> {
+        let mut expr = self.parse_term()?;
+        loop {
+            let op = match self.peek().token {
+                Token::EqEq => BinOp::Eq,
+                Token::NotEq => BinOp::NotEq,
+                Token::Lt => BinOp::Lt,
+                Token::LtEq => BinOp::LtEq,
+                Token::Gt => BinOp::Gt,
+                Token::GtEq => BinOp::GtEq,
+                _ => break,
+            };
+            self.advance();
+            let rhs = self.parse_term()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, XmasError> {
+        let mut expr = self.parse_factor()?;
+        loop {
+            let op = match self.peek().token {
+                Token::Plus => BinOp::Add,
+                Token::Minus => BinOp::Sub,
+                _ => break,
+            };
+            self.advance();
+            let rhs = self.parse_factor()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expr, XmasError> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            let op = match self.peek().token {
+                Token::Star => BinOp::Mul,
+                Token::Slash => BinOp::Div,
+                Token::Percent => BinOp::Mod,
+                _ => break,
+            };
+            self.advance();
+            let rhs = self.parse_unary()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, XmasError> {
+        let op = match self.peek().token {
+            Token::Minus => UnaryOp::Neg,
+            Token::Bang => UnaryOp::Not,
+            Token::Tilde => UnaryOp::ToNum,
+            Token::Dollar => UnaryOp::ToStr,
+            _ => return self.parse_postfix(),
+        };
+        self.advance();
+        self.enter()?;
+        let operand = self.parse_unary();
+        self.depth -= 1;
+        Ok(Expr::Unary(op, Box::new(operand?)))
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, XmasError> {
+        let mut expr = self.parse_primary()?;
+        while self.check(&Token::LBracket) {
+            self.advance();
+            // `arr[:hi]`
+            if self.check(&Token::Colon) {
+                self.advance();
+                let hi = if self.check(&Token::RBracket) {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expr()?))
+                };
+                self.expect(&Token::RBracket)?;
+                expr = Expr::Slice(Box::new(expr), None, hi);
+                continue;
+            }
+            let first = self.parse_expr()?;
+            if self.check(&Token::Colon) {
+                self.advance();
+                let hi = if self.check(&Token::RBracket) {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expr()?))
+                };
+                self.expect(&Token::RBracket)?;
+                expr = Expr::Slice(Box::new(expr), Some(Box::new(first)), hi);
+            } else {
+                self.expect(&Token::RBracket)?;
+                expr = Expr::Index(Box::new(expr), Box::new(first));
+            }
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, XmasError> {
+        let token = self.peek().token.clone();
+        match token {
+            Token::Number(n) => {
+                self.advance();
+                Ok(Expr::Number(n))
+            }
+            Token::Str(s) => {
+                self.advance();
+                Ok(Expr::Str(s))
+            }
+            Token::True => {
+                self.advance();
+                Ok(Expr::Bool(true))
+            }
+            Token::False => {
+                self.advance();
+                Ok(Expr::Bool(false))
+            }
+            // `match` is a keyword for the statement form, but in expression
+            // position `match(...)` is the regex builtin call.
+            Token::Match => {
+                self.advance();
+                let (args, named) = self.parse_call_args()?;
+                Ok(Expr::Call(Symbol::intern("match"), args, named))
+            }
+            Token::Input => {
+                self.advance();
+                if self.check(&Token::Dot) {
+                    self.advance();
+                    match self.peek().token.clone() {
+                        Token::Ident(field) if field == Symbol::intern("meta") => {
+                            self.advance();
+                            Ok(Expr::InputMeta)
+                        }
+                        other => {
+                            Err(self.error_at(format!("input has no field {other:?}; try meta")))
+                        }
+                    }
+                } else {
+                    Ok(Expr::Input)
+                }
+            }
+            // Builtins are not keywords: any `identifier(...)` parses as a
+            // call, and the interpreter resolves the name at call time.
+            Token::Ident(name) => {
+                let (line, col) = (self.peek().line, self.peek().col);
+                self.advance();
+                if self.check(&Token::LParen) {
+                    let (args, named) = self.parse_call_args()?;
+                    if let Some((params, body)) = self.aliases.get(&name) {
+                        if !named.is_empty() {
+                            return Err(XmasError::new(
+                                ErrorKind::Parse,
+                                format!("alias {name} does not accept named arguments"),
+                            )
+                            .at(line, col));
+                        }
+                        if args.len() != params.len() {
+                            return Err(XmasError::new(
+                                ErrorKind::Parse,
+                                format!(
+                                    "alias {name} expects {} argument(s), got {}",
+                                    params.len(),
+                                    args.len()
+                                ),
+                            )
+                            .at(line, col));
+                        }
+                        let mut substitute = Substitute {
+                            params: params.clone(),
+                            args,
+                        };
+                        return Ok(substitute.fold_expr(body.clone()));
+                    }
+                    Ok(Expr::Call(name, args, named))
+                } else if let Some((params, body)) = self.aliases.get(&name) {
+                    if params.is_empty() {
+                        Ok(body.clone())
+                    } else {
+                        Err(XmasError::new(
+                            ErrorKind::Parse,
+                            format!("alias {name} expects {} argument(s)", params.len()),
+                        )
+                        .at(line, col))
+                    }
+                } else {
+                    Ok(Expr::Identifier(name))
+                }
+            }
+            Token::LParen => {
+                self.advance();
+                let expr = self.parse_expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            Token::LBracket => {
+                // Disambiguate `[a..b]` from `[a, b]` by trying a range
+                // parse and rewinding on failure — backtracking by index, so
+                // no tokens are cloned.
+                let start = self.current;
+                match self.try_parse_range() {
+                    Ok(range) => return Ok(range),
+                    Err(_) => self.current = start,
+                }
+                self.advance(); // [
+                let mut items = Vec::new();
+                if !self.check(&Token::RBracket) {
+                    loop {
+                        items.push(self.parse_expr()?);
+                        if !self.check(&Token::Comma) {
+                            break;
+                        }
+                        self.advance();
+                    }
+                }
+                self.expect(&Token::RBracket)?;
+                Ok(Expr::Array(items))
+            }
+            Token::LBrace => {
+                self.advance(); // {
+                let mut entries = Vec::new();
+                if !self.check(&Token::RBrace) {
+                    loop {
+                        let key = self.parse_expr()?;
+                        self.expect(&Token::Colon)?;
+                        entries.push((key, self.parse_expr()?));
+                        if !self.check(&Token::Comma) {
+                            break;
+                        }
+                        self.advance();
+                    }
+                }
+                self.expect(&Token::RBrace)?;
+                Ok(Expr::Map(entries))
+            }
+            other => {
+                let message = format!("unexpected token {other:?}");
+                Err(self.error_at(message))
+            }
+        }
+    }
+
+    fn try_parse_range(&mut self) -> Result<Expr, XmasError> {
+        self.expect(&Token::LBracket)?;
+        let lo = self.parse_or()?;
+        self.expect(&Token::DotDot)?;
+        let hi = self.parse_or()?;
+        self.expect(&Token::RBracket)?;
+        Ok(Expr::Range(Box::new(lo), Box::new(hi)))
+    }
+
+    /// Parses `(a, b, key = c)`: positional arguments, then `name = expr`
+    /// named arguments. Positional arguments may not follow named ones.
+    fn parse_call_args(&mut self) -> Result<CallArgs, XmasError> {
+        self.expect(&Token::LParen)?;
+        let mut args = Vec::new();
+        let mut named = Vec::new();
+        if !self.check(&Token::RParen) {
+            loop {
+                // `name =` (a single `=`, not `==`) marks a named argument.
+                let is_named = matches!(self.peek().token, Token::Ident(_))
+                    && self.tokens.get(self.current + 1).map(|t| &t.token) == Some(&Token::Eq);
+                if is_named {
+                    let name = self.expect_ident()?;
+                    self.advance(); // =
+                    named.push((name, self.parse_expr()?));
+                } else {
+                    if !named.is_empty() {
+                        return Err(self.error_at("positional argument after named argument"));
+                    }
+                    args.push(self.parse_expr()?);
+                }
+                if !self.check(&Token::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        self.expect(&Token::RParen)?;
+        Ok((args, named))
+    }
+
+    // Token-stream helpers.
+
+    /// Tracks nesting depth across the recursive-descent entry points,
+    /// erroring out instead of overflowing the stack on pathological input.
+    fn enter(&mut self) -> Result<(), XmasError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            Err(self.error_at(format!("nesting too deep (more than {MAX_DEPTH} levels)")))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// A parse error pointing at the current token.
+    fn error_at(&self, message: impl Into<String>) -> XmasError {
+        XmasError::new(ErrorKind::Parse, message).at(self.peek().line, self.peek().col)
+    }
+
+    fn peek(&self) -> &SpannedToken {
+        // In bounds: the stream ends with Eof and `advance` stops there.
+        &self.tokens[self.current]
+    }
+
+    fn advance(&mut self) -> &SpannedToken {
+        let current = self.current;
+        if self.tokens[current].token != Token::Eof {
+            self.current += 1;
+        }
+        &self.tokens[current]
+    }
+
+    fn check(&self, token: &Token) -> bool {
+        &self.peek().token == token
+    }
+
+    fn check_skipping_newlines(&self, token: &Token) -> bool {
+        let mut i = self.current;
+        while matches!(self.tokens.get(i).map(|t| &t.token), Some(Token::Newline)) {
+            i += 1;
+        }
+        self.tokens.get(i).map(|t| &t.token) == Some(token)
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), XmasError> {
+        if self.check(token) {
+            self.advance();
+            Ok(())
+        } else {
+            let message = format!("expected {:?}, found {:?}", token, self.peek().token);
+            Err(self.error_at(message))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<Symbol, XmasError> {
+        match self.peek().token.clone() {
+            Token::Ident(name) => {
+                self.advance();
+                Ok(name)
+            }
+            other => Err(self.error_at(format!("expected identifier, found {other:?}"))),
+        }
+    }
+
+    fn expect_stmt_end(&mut self) -> Result<(), XmasError> {
+        match self.peek().token {
+            Token::Newline => {
+                self.advance();
+                Ok(())
+            }
+            Token::Eof | Token::RBrace => Ok(()),
+            _ => {
+                let message = format!("expected end of statement, found {:?}", self.peek().token);
+                Err(self.error_at(message))
+            }
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.check(&Token::Newline) {
+            self.advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+
+    fn parse_src(source: &str) -> Block {
+        parse(lex(source).unwrap(), source).unwrap()
+    }
+
+    #[test]
+    fn parses_assignment_and_compound() {
+        let prog = parse_src("x = 1\nx += 2");
+        assert_eq!(prog.len(), 2);
+        assert!(matches!(prog[0].1, Stmt::Assign { .. }));
+        assert!(matches!(
+            prog[1].1,
+            Stmt::AssignOp {
+                op: BinOp::Add,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn distinguishes_range_from_array() {
+        let prog = parse_src("r = [1..5]\na = [1, 5]");
+        assert!(matches!(
+            prog[0].1,
+            Stmt::Assign {
+                value: Expr::Range(..),
+                ..
+            }
+        ));
+        assert!(matches!(
+            prog[1].1,
+            Stmt::Assign {
+                value: Expr::Array(..),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_fn_def_with_block_body() {
+        let prog = parse_src("fn f(n) {\n x = n\n _ = x\n}");
+        assert!(matches!(
+            &prog[0].1,
+            Stmt::FnDef {
+                body: FnBody::Block(block),
+                ..
+            } if block.len() == 2
+        ));
+    }
+
+    #[test]
+    fn parses_fn_def_with_memo() {
+        let prog = parse_src("memo fn fib(n) = fib(n - 1) + fib(n - 2)");
+        assert!(matches!(
+            prog[0].1,
+            Stmt::FnDef { memoized: true, .. }
+        ));
+    }
+
+    #[test]
+    fn pipe_is_loosest_and_left_associative() {
+        // `x |> f(a + 1)` pipes into the call.
+        let prog = parse_src("_ = x |> f(a + 1)");
+        let Stmt::Assign { value, .. } = &prog[0].1 else {
+            panic!("expected assignment");
+        };
+        let Expr::Pipe(lhs, rhs) = value else {
+            panic!("expected pipe, got {value:?}");
+        };
+        assert_eq!(**lhs, Expr::Identifier(Symbol::intern("x")));
+        assert!(matches!(**rhs, Expr::Call(..)));
+
+        // `a |> f |> g` groups as `(a |> f) |> g`.
+        let prog = parse_src("_ = a |> f |> g");
+        let Stmt::Assign { value, .. } = &prog[0].1 else {
+            panic!("expected assignment");
+        };
+        assert!(
+            matches!(value, Expr::Pipe(lhs, _) if matches!(**lhs, Expr::Pipe(..))),
+            "{value:?}"
+        );
+    }
+
+    #[test]
+    fn deep_nesting_errors_instead_of_overflowing() {
+        let source = format!("x = {}1{}", "(".repeat(10_000), ")".repeat(10_000));
+        let err = parse(lex(&source).unwrap(), &source).unwrap_err();
+        assert!(err.to_string().contains("nesting too deep"), "{err}");
+
+        let source = format!("x = {}1", "!".repeat(10_000));
+        let err = parse(lex(&source).unwrap(), &source).unwrap_err();
+        assert!(err.to_string().contains("nesting too deep"), "{err}");
+
+        // Unbalanced input errors normally rather than panicking.
+        let source = "x = ((((1";
+        assert!(parse(lex(source).unwrap(), source).is_err());
+    }
+
+    #[test]
+    fn aliases_expand_at_parse_time() {
+        // The use site becomes the substituted expression, not a call.
+        let prog = parse_src("alias inb(i) = i >= 0 && i < 10\n_ = inb(n + 1)");
+        assert_eq!(prog.len(), 1);
+        let Stmt::Assign { value, .. } = &prog[0].1 else {
+            panic!("expected assignment");
+        };
+        assert_eq!(*value, parse_expr_src("(n + 1) >= 0 && (n + 1) < 10"));
+
+        // A parameterless alias expands at a bare mention.
+        let prog = parse_src("alias wide = len(input[0])\n_ = wide * 2");
+        let Stmt::Assign { value, .. } = &prog[0].1 else {
+            panic!("expected assignment");
+        };
+        assert_eq!(*value, parse_expr_src("len(input[0]) * 2"));
+    }
+
+    fn parse_expr_src(source: &str) -> Expr {
+        let prog = parse_src(&format!("_ = {source}"));
+        let Stmt::Assign { value, .. } = prog.into_iter().next().unwrap().1 else {
+            panic!("expected assignment");
+        };
+        value
+    }
+
+    #[test]
+    fn alias_misuse_errors_at_parse_time() {
+        let source = "alias inb(i) = i >= 0\n_ = inb(1, 2)";
+        let err = parse(lex(source).unwrap(), source).unwrap_err();
+        assert!(err.to_string().contains("alias inb expects 1 argument(s), got 2"), "{err}");
+
+        let source = "alias inb(i) = i >= 0\n_ = inb";
+        let err = parse(lex(source).unwrap(), source).unwrap_err();
+        assert!(err.to_string().contains("alias inb expects 1 argument(s)"), "{err}");
+    }
+
+    #[test]
+    fn match_patterns_parse_shapes_and_rest() {
+        let prog = parse_src(
+            "match (parts) {\n\
+             [a, \"->\", b] => { x = a }\n\
+             [head, ...tail] => { x = head }\n\
+             other => { x = other }\n\
+             }",
+        );
+        let Stmt::Match { arms, .. } = &prog[0].1 else {
+            panic!("expected match, got {:?}", prog[0].1);
+        };
+        assert_eq!(arms.len(), 3);
+        assert_eq!(
+            arms[0].pattern,
+            Pattern::Array(
+                vec![
+                    Pattern::Binding(Symbol::intern("a")),
+                    Pattern::Literal(Expr::Str("->".into())),
+                    Pattern::Binding(Symbol::intern("b")),
+                ],
+                None
+            )
+        );
+        assert_eq!(
+            arms[1].pattern,
+            Pattern::Array(
+                vec![Pattern::Binding(Symbol::intern("head"))],
+                Some(Symbol::intern("tail"))
+            )
+        );
+        assert_eq!(arms[2].pattern, Pattern::Binding(Symbol::intern("other")));
+    }
+
+    #[test]
+    fn builtin_names_parse_as_ordinary_calls() {
+        let prog = parse_src("n = len([1, 2])");
+        assert!(matches!(
+            &prog[0].1,
+            Stmt::Assign {
+                value: Expr::Call(name, ..),
+                ..
+            } if *name == Symbol::intern("len")
+        ));
+    }
+}
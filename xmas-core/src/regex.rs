@@ -0,0 +1,405 @@
+//! A small regular-expression engine behind the `match` and `matchAll`
+//! builtins.
+//!
+//! Supports the subset that covers puzzle-input parsing: literal characters,
+//! `.`, classes like `[a-z]` and `[^0-9]`, the escapes `\d` `\w` `\s` (and
+//! their uppercase negations), the quantifiers `*` `+` `?` (greedy),
+//! alternation `|`, capture groups `(...)`, and the anchors `^` and `$`.
+//! Matching backtracks over a compiled program, so patterns behave like the
+//! usual leftmost-greedy engines. Kept in-crate so the language stays
+//! dependency-free.
+
+/// A compiled pattern.
+pub struct Regex {
+    prog: Vec<Inst>,
+    /// How many capture groups the pattern has, not counting the implicit
+    /// group 0 around the whole match.
+    groups: usize,
+}
+
+enum Inst {
+    Char(char),
+    Any,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+    Start,
+    End,
+    /// Record the current position in capture slot `i` (2k is the start of
+    /// group k, 2k + 1 its end).
+    Save(usize),
+    Jmp(usize),
+    /// Try `a` first; on failure resume at `b`.
+    Split(usize, usize),
+    Match,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut compiler = Compiler {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            prog: Vec::new(),
+            groups: 0,
+        };
+        compiler.prog.push(Inst::Save(0));
+        compiler.alternation()?;
+        if compiler.pos < compiler.chars.len() {
+            // Only an unbalanced `)` can stop `alternation` early.
+            return Err("unmatched ) in pattern".to_string());
+        }
+        compiler.prog.push(Inst::Save(1));
+        compiler.prog.push(Inst::Match);
+        Ok(Regex {
+            prog: compiler.prog,
+            groups: compiler.groups,
+        })
+    }
+
+    /// The capture groups of the first (leftmost) match: element 0 is the
+    /// whole match, then one entry per group, `None` for groups the match
+    /// never entered.
+    pub fn captures(&self, text: &str) -> Option<Vec<Option<String>>> {
+        let chars: Vec<char> = text.chars().collect();
+        (0..=chars.len()).find_map(|start| Some(self.run(&chars, start)?.rendered))
+    }
+
+    /// The captures of every non-overlapping match, leftmost first. An
+    /// empty match advances by one character so scanning always terminates.
+    pub fn captures_all(&self, text: &str) -> Vec<Vec<Option<String>>> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = Vec::new();
+        let mut start = 0;
+        while start <= chars.len() {
+            let Some(found) = (start..=chars.len()).find_map(|s| {
+                self.run(&chars, s)
+                    .map(|caps| (s, caps.end, caps.rendered))
+            }) else {
+                break;
+            };
+            let (matched_at, end, caps) = found;
+            out.push(caps);
+            start = end.max(matched_at + 1);
+        }
+        out
+    }
+
+    fn run(&self, chars: &[char], start: usize) -> Option<Captures> {
+        let slots = 2 * (self.groups + 1);
+        let mut stack = vec![(0usize, start, vec![None; slots])];
+        while let Some((mut pc, mut pos, mut saves)) = stack.pop() {
+            loop {
+                match &self.prog[pc] {
+                    Inst::Char(c) => {
+                        if chars.get(pos) != Some(c) {
+                            break;
+                        }
+                        pos += 1;
+                        pc += 1;
+                    }
+                    Inst::Any => {
+                        if pos >= chars.len() {
+                            break;
+                        }
+                        pos += 1;
+                        pc += 1;
+                    }
+                    Inst::Class { negated, ranges } => {
+                        let Some(&c) = chars.get(pos) else { break };
+                        let inside = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+                        if inside == *negated {
+                            break;
+                        }
+                        pos += 1;
+                        pc += 1;
+                    }
+                    Inst::Start => {
+                        if pos != 0 {
+                            break;
+                        }
+                        pc += 1;
+                    }
+                    Inst::End => {
+                        if pos != chars.len() {
+                            break;
+                        }
+                        pc += 1;
+                    }
+                    Inst::Save(slot) => {
+                        saves[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Jmp(target) => pc = *target,
+                    Inst::Split(preferred, fallback) => {
+                        stack.push((*fallback, pos, saves.clone()));
+                        pc = *preferred;
+                    }
+                    Inst::Match => {
+                        let rendered = (0..=self.groups)
+                            .map(|group| {
+                                let (lo, hi) = (saves[2 * group], saves[2 * group + 1]);
+                                Some(chars[lo?..hi?].iter().collect())
+                            })
+                            .collect();
+                        return Some(Captures {
+                            end: saves[1].unwrap_or(start),
+                            rendered,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+struct Captures {
+    end: usize,
+    rendered: Vec<Option<String>>,
+}
+
+/// Compiles the pattern by recursive descent, emitting instructions as it
+/// goes and patching jump targets once the shapes are known.
+struct Compiler {
+    chars: Vec<char>,
+    pos: usize,
+    prog: Vec<Inst>,
+    groups: usize,
+}
+
+impl Compiler {
+    fn alternation(&mut self) -> Result<(), String> {
+        let mut branch_start = self.prog.len();
+        // Jmp targets of finished branches, all patched to the end.
+        let mut exits = Vec::new();
+        self.sequence()?;
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            // Wrap the finished branch: Split(branch, next), branch, Jmp(end).
+            // Earlier branches (and their exit Jmps) all sit before the
+            // insertion point, so no recorded index shifts.
+            self.prog.insert(branch_start, Inst::Split(0, 0));
+            let split = branch_start;
+            exits.push(self.prog.len());
+            self.prog.push(Inst::Jmp(0));
+            let next = self.prog.len();
+            self.patch_split(split, split + 1, next);
+            branch_start = next;
+            self.sequence()?;
+        }
+        let end = self.prog.len();
+        for exit in exits {
+            self.prog[exit] = Inst::Jmp(end);
+        }
+        Ok(())
+    }
+
+    fn sequence(&mut self) -> Result<(), String> {
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                return Ok(());
+            }
+            let atom_start = self.prog.len();
+            self.atom()?;
+            match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    self.prog.insert(atom_start, Inst::Split(0, 0));
+                    self.prog.push(Inst::Jmp(atom_start));
+                    let after = self.prog.len();
+                    self.patch_split(atom_start, atom_start + 1, after);
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    let after = self.prog.len() + 1;
+                    self.prog.push(Inst::Split(atom_start, after));
+                }
+                Some('?') => {
+                    self.pos += 1;
+                    self.prog.insert(atom_start, Inst::Split(0, 0));
+                    let after = self.prog.len();
+                    self.patch_split(atom_start, atom_start + 1, after);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn atom(&mut self) -> Result<(), String> {
+        match self.next().expect("sequence checked peek") {
+            '.' => self.prog.push(Inst::Any),
+            '^' => self.prog.push(Inst::Start),
+            '$' => self.prog.push(Inst::End),
+            '(' => {
+                self.groups += 1;
+                let group = self.groups;
+                self.prog.push(Inst::Save(2 * group));
+                self.alternation()?;
+                if self.next() != Some(')') {
+                    return Err("unclosed ( in pattern".to_string());
+                }
+                self.prog.push(Inst::Save(2 * group + 1));
+            }
+            '[' => {
+                let class = self.class()?;
+                self.prog.push(class);
+            }
+            '\\' => {
+                let escaped = self
+                    .next()
+                    .ok_or_else(|| "pattern ends with a lone \\".to_string())?;
+                self.prog.push(escape_inst(escaped));
+            }
+            '*' | '+' | '?' => return Err(format!("nothing to repeat before {}", self.preview())),
+            c => self.prog.push(Inst::Char(c)),
+        }
+        Ok(())
+    }
+
+    /// Parses the body of a `[...]` class; the `[` is already consumed.
+    fn class(&mut self) -> Result<Inst, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        loop {
+            match self.next() {
+                None => return Err("unclosed [ in pattern".to_string()),
+                Some(']') => break,
+                Some('\\') => {
+                    let escaped = self
+                        .next()
+                        .ok_or_else(|| "pattern ends with a lone \\".to_string())?;
+                    match escape_inst(escaped) {
+                        Inst::Class {
+                            negated: false,
+                            ranges: escaped_ranges,
+                        } => ranges.extend(escaped_ranges),
+                        _ => ranges.push((escaped, escaped)),
+                    }
+                }
+                Some(lo) if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') => {
+                    self.pos += 1;
+                    let hi = self
+                        .next()
+                        .ok_or_else(|| "unclosed [ in pattern".to_string())?;
+                    ranges.push((lo, hi));
+                }
+                Some(c) => ranges.push((c, c)),
+            }
+        }
+        Ok(Inst::Class { negated, ranges })
+    }
+
+    fn patch_split(&mut self, at: usize, preferred: usize, fallback: usize) {
+        self.prog[at] = Inst::Split(preferred, fallback);
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn preview(&self) -> char {
+        self.chars[self.pos - 1]
+    }
+}
+
+/// The instruction for an escaped character: a predefined class for
+/// `\d`-style escapes, the literal character otherwise.
+fn escape_inst(escaped: char) -> Inst {
+    let class = |negated, ranges: &[(char, char)]| Inst::Class {
+        negated,
+        ranges: ranges.to_vec(),
+    };
+    let digits = [('0', '9')];
+    let words = [('0', '9'), ('a', 'z'), ('A', 'Z'), ('_', '_')];
+    let spaces = [(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')];
+    match escaped {
+        'd' => class(false, &digits),
+        'D' => class(true, &digits),
+        'w' => class(false, &words),
+        'W' => class(true, &words),
+        's' => class(false, &spaces),
+        'S' => class(true, &spaces),
+        'n' => Inst::Char('\n'),
+        't' => Inst::Char('\t'),
+        c => Inst::Char(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captures(pattern: &str, text: &str) -> Option<Vec<Option<String>>> {
+        Regex::new(pattern).unwrap().captures(text)
+    }
+
+    #[test]
+    fn literal_and_class_matching() {
+        assert_eq!(
+            captures("c.t", "a cat sat"),
+            Some(vec![Some("cat".into())])
+        );
+        assert_eq!(captures("[a-z]+", "  abc "), Some(vec![Some("abc".into())]));
+        assert_eq!(captures("^x", "yx"), None);
+        assert_eq!(captures("x$", "xy"), None);
+        assert_eq!(captures(r"\d+", "no digits"), None);
+    }
+
+    #[test]
+    fn groups_capture_their_slices() {
+        let caps = captures(r"Game (\d+): (\d+) (red|blue)", "Game 12: 3 red, 4 blue").unwrap();
+        assert_eq!(
+            caps,
+            vec![
+                Some("Game 12: 3 red".into()),
+                Some("12".into()),
+                Some("3".into()),
+                Some("red".into()),
+            ]
+        );
+        // A group skipped by alternation stays unmatched.
+        let caps = captures("(a)|(b)", "b").unwrap();
+        assert_eq!(caps, vec![Some("b".into()), None, Some("b".into())]);
+    }
+
+    #[test]
+    fn quantifiers_are_greedy_with_backtracking() {
+        assert_eq!(
+            captures("a.*b", "xaybzb!"),
+            Some(vec![Some("aybzb".into())])
+        );
+        assert_eq!(captures("ab?c", "ac"), Some(vec![Some("ac".into())]));
+        assert_eq!(captures("ab+c", "ac"), None);
+    }
+
+    #[test]
+    fn captures_all_scans_without_overlap() {
+        let all = Regex::new(r"(\d+)").unwrap().captures_all("1, 22, 333");
+        let firsts: Vec<_> = all
+            .into_iter()
+            .map(|caps| caps[1].clone().unwrap())
+            .collect();
+        assert_eq!(firsts, ["1", "22", "333"]);
+        // Empty matches still advance.
+        assert_eq!(Regex::new("x?").unwrap().captures_all("ab").len(), 3);
+    }
+
+    #[test]
+    fn malformed_patterns_error() {
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("a)").is_err());
+        assert!(Regex::new("[ab").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("\\").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+//! Checkpoint serialization for interpreter globals.
+//!
+//! A checkpoint is a text token stream: a header, then one `name value`
+//! entry per global. Strings are length-prefixed rather than escaped, so any
+//! byte sequence round-trips. Everything a simulation keeps in variables is
+//! supported; values that cannot meaningfully outlive the run (caches,
+//! function references) are skipped with a note from the caller.
+
+use std::rc::Rc;
+
+use crate::interpreter::{stable_hash, BitSet, Graph, MapVal, RangeSet, RangeVal, SetVal, SparseGrid, Value};
+
+const HEADER: &str = "xmas-checkpoint 1";
+
+/// Whether `value` can be written to a checkpoint. Caches are identity
+/// objects and function references are resolved against the program, so
+/// neither survives a restart.
+pub fn serializable(value: &Value) -> bool {
+    match value {
+        Value::Cache(_) | Value::FnRef(_) => false,
+        Value::Array1D(items) => items.iter().all(serializable),
+        Value::Array2D(rows) => rows.iter().flatten().all(serializable),
+        Value::Sparse(grid) => {
+            serializable(&grid.default) && grid.cells.values().all(serializable)
+        }
+        Value::Graph(graph) => graph.nodes().iter().all(serializable),
+        Value::Map(map) => map
+            .entries
+            .iter()
+            .all(|(key, value)| serializable(key) && serializable(value)),
+        Value::Set(set) => set.items.iter().all(serializable),
+        _ => true,
+    }
+}
+
+/// Serializes `globals` into checkpoint text. Entries that fail
+/// [`serializable`] must be filtered out by the caller first.
+pub fn serialize(globals: &[(Rc<str>, Value)]) -> String {
+    let mut out = String::from(HEADER);
+    out.push('\n');
+    for (name, value) in globals {
+        out.push_str(name);
+        out.push(' ');
+        write_value(&mut out, value);
+        out.push('\n');
+    }
+    out
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Number(n) => out.push_str(&format!("n:{n}")),
+        Value::Bool(b) => out.push_str(&format!("b:{}", u8::from(*b))),
+        Value::Str(s) => out.push_str(&format!("s:{}:{s}", s.len())),
+        Value::Array1D(items) => {
+            out.push_str(&format!("a:{}", items.len()));
+            for item in items {
+                out.push(' ');
+                write_value(out, item);
+            }
+        }
+        Value::NumArray(nums) => {
+            out.push_str(&format!("a:{}", nums.len()));
+            for n in nums {
+                out.push_str(&format!(" n:{n}"));
+            }
+        }
+        Value::Array2D(rows) => {
+            out.push_str(&format!("g:{}", rows.len()));
+            for row in rows.iter() {
+                out.push(' ');
+                write_value(out, &Value::Array1D(row.clone()));
+            }
+        }
+        // A packed char grid writes in the general grid form, like NumArray
+        // writes as a plain array; it reloads as the equal Array2D.
+        Value::Grid(g) => {
+            out.push_str(&format!("g:{}", g.rows()));
+            for r in 0..g.rows() {
+                out.push(' ');
+                write_value(out, &Value::Array1D(g.row_values(r)));
+            }
+        }
+        Value::Range(r) => out.push_str(&format!("r:{}:{}:{}", r.start, r.len, r.step)),
+        Value::Point(r, c) => out.push_str(&format!("p:{r}:{c}")),
+        Value::Point3(x, y, z) => out.push_str(&format!("p3:{x}:{y}:{z}")),
+        Value::RangeSet(rs) => {
+            out.push_str(&format!("rs:{}", rs.intervals().len()));
+            for (start, end) in rs.intervals() {
+                out.push_str(&format!(" {start}:{end}"));
+            }
+        }
+        Value::Sparse(grid) => {
+            out.push_str(&format!("sp:{} ", grid.cells.len()));
+            write_value(out, &grid.default);
+            // Sorted so checkpoints of equal grids are byte-identical.
+            let mut cells: Vec<_> = grid.cells.iter().collect();
+            cells.sort_by_key(|(at, _)| **at);
+            for ((row, col), cell) in cells {
+                out.push_str(&format!(" p:{row}:{col} "));
+                write_value(out, cell);
+            }
+        }
+        Value::Bitset(bits) => {
+            out.push_str(&format!("bs:{}", bits.words().len()));
+            for word in bits.words() {
+                out.push_str(&format!(" {word:x}"));
+            }
+        }
+        Value::Graph(graph) => {
+            let pairs = graph.edge_pairs();
+            out.push_str(&format!("gr:{}:{}", graph.nodes().len(), pairs.len()));
+            for node in graph.nodes() {
+                out.push(' ');
+                write_value(out, node);
+            }
+            for (from, to) in pairs {
+                out.push_str(&format!(" e:{from}:{to}"));
+            }
+        }
+        Value::Map(map) => {
+            out.push_str(&format!("m:{}", map.entries.len()));
+            // Sorted by key fingerprint so checkpoints of equal maps are
+            // byte-identical.
+            let mut entries: Vec<_> = map.entries.iter().collect();
+            entries.sort_by_key(|(key, _)| stable_hash(key));
+            for (key, value) in entries {
+                out.push(' ');
+                write_value(out, key);
+                out.push(' ');
+                write_value(out, value);
+            }
+        }
+        Value::Set(set) => {
+            out.push_str(&format!("st:{}", set.items.len()));
+            // Sorted by fingerprint like map entries.
+            let mut items: Vec<_> = set.items.iter().collect();
+            items.sort_by_key(|item| stable_hash(item));
+            for item in items {
+                out.push(' ');
+                write_value(out, item);
+            }
+        }
+        Value::Cache(_) | Value::FnRef(_) => unreachable!("filtered by serializable()"),
+    }
+}
+
+/// Parses checkpoint text back into named globals.
+pub fn parse(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let rest = text
+        .strip_prefix(HEADER)
+        .ok_or_else(|| "not a checkpoint file (missing header)".to_string())?;
+    let mut reader = Reader { rest };
+    let mut globals = Vec::new();
+    loop {
+        reader.skip_whitespace();
+        if reader.rest.is_empty() {
+            return Ok(globals);
+        }
+        let name = reader.token()?.to_string();
+        reader.skip_whitespace();
+        let value = reader.value()?;
+        globals.push((name, value));
+    }
+}
+
+struct Reader<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// The next whitespace-delimited token.
+    fn token(&mut self) -> Result<&'a str, String> {
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(char::is_whitespace)
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err("unexpected end of checkpoint".to_string());
+        }
+        let (token, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(token)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        // Strings are handled before tokenizing: their payload is a byte
+        // count, not a delimiter.
+        if let Some(rest) = self.rest.strip_prefix("s:") {
+            let colon = rest
+                .find(':')
+                .ok_or_else(|| "malformed string in checkpoint".to_string())?;
+            let len: usize = rest[..colon]
+                .parse()
+                .map_err(|_| "malformed string length in checkpoint".to_string())?;
+            let payload = rest
+                .get(colon + 1..colon + 1 + len)
+                .ok_or_else(|| "truncated string in checkpoint".to_string())?;
+            self.rest = &rest[colon + 1 + len..];
+            return Ok(Value::Str(payload.to_string()));
+        }
+        let token = self.token()?;
+        let mut parts = token.split(':');
+        let tag = parts.next().unwrap_or_default();
+        let mut field = |what: &str| -> Result<i64, String> {
+            parts
+                .next()
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(|| format!("malformed {what} in checkpoint"))
+        };
+        match tag {
+            "n" => Ok(Value::Number(field("number")?)),
+            "b" => Ok(Value::Bool(field("bool")? != 0)),
+            "a" => {
+                let count = field("array length")?;
+                let items: Result<Vec<Value>, String> =
+                    (0..count).map(|_| self.value()).collect();
+                Ok(Value::array(items?))
+            }
+            "g" => {
+                let count = field("grid height")?;
+                let mut rows = Vec::with_capacity(count.max(0) as usize);
+                for _ in 0..count {
+                    match self.value()? {
+                        Value::Array1D(row) => rows.push(row),
+                        Value::NumArray(nums) => rows.push(crate::interpreter::unpack(&nums)),
+                        _ => return Err("malformed grid row in checkpoint".to_string()),
+                    }
+                }
+                Ok(Value::Array2D(Rc::new(rows)))
+            }
+            "r" => Ok(Value::Range(RangeVal {
+                start: field("range start")?,
+                len: field("range length")?,
+                step: field("range step")?,
+            })),
+            "p" => Ok(Value::Point(field("point row")?, field("point col")?)),
+            "rs" => {
+                let count: i64 = field("range set size")?;
+                let mut set = RangeSet::default();
+                for _ in 0..count {
+                    let start = field("interval start")?;
+                    let end: i64 = field("interval end")?;
+                    set.insert(start, end);
+                }
+                Ok(Value::RangeSet(Rc::new(set)))
+            }
+            "p3" => Ok(Value::Point3(
+                field("point x")?,
+                field("point y")?,
+                field("point z")?,
+            )),
+            "sp" => {
+                let count = field("sparse cell count")?;
+                let default = self.value()?;
+                let mut cells = std::collections::HashMap::new();
+                for _ in 0..count {
+                    let at = match self.value()? {
+                        Value::Point(row, col) => (row, col),
+                        _ => return Err("malformed sparse cell in checkpoint".to_string()),
+                    };
+                    cells.insert(at, self.value()?);
+                }
+                Ok(Value::Sparse(Rc::new(SparseGrid { cells, default })))
+            }
+            "m" => {
+                let count = field("map entry count")?;
+                let mut map = MapVal::default();
+                for _ in 0..count {
+                    let key = self.value()?;
+                    map.entries.insert(key, self.value()?);
+                }
+                Ok(Value::Map(Rc::new(map)))
+            }
+            "st" => {
+                let count = field("set size")?;
+                let mut set = SetVal::default();
+                for _ in 0..count {
+                    let item = self.value()?;
+                    set.items.insert(item);
+                }
+                Ok(Value::Set(Rc::new(set)))
+            }
+            "bs" => {
+                let count = field("bitset length")?;
+                let words: Result<Vec<u64>, String> = (0..count)
+                    .map(|_| {
+                        let word = self.token()?;
+                        u64::from_str_radix(word, 16)
+                            .map_err(|_| "malformed bitset word in checkpoint".to_string())
+                    })
+                    .collect();
+                Ok(Value::Bitset(Rc::new(BitSet::from_words(words?))))
+            }
+            "gr" => {
+                let nodes = field("graph node count")?;
+                let edges = field("graph edge count")?;
+                let nodes: Result<Vec<Value>, String> =
+                    (0..nodes).map(|_| self.value()).collect();
+                let nodes = nodes?;
+                let mut graph = Graph::default();
+                // Register every node first so ids line up with edge indices
+                // (isolated nodes included).
+                for node in &nodes {
+                    graph.add_node(node.clone());
+                }
+                for _ in 0..edges {
+                    let token = self.token()?;
+                    let pair = token
+                        .strip_prefix("e:")
+                        .and_then(|pair| pair.split_once(':'))
+                        .and_then(|(from, to)| {
+                            Some((from.parse::<usize>().ok()?, to.parse::<usize>().ok()?))
+                        })
+                        .ok_or_else(|| "malformed edge in checkpoint".to_string())?;
+                    let from = nodes.get(pair.0).cloned();
+                    let to = nodes.get(pair.1).cloned();
+                    match (from, to) {
+                        (Some(from), Some(to)) => graph.add_edge(from, to),
+                        _ => return Err("edge index out of range in checkpoint".to_string()),
+                    }
+                }
+                Ok(Value::Graph(Rc::new(graph)))
+            }
+            other => Err(format!("unknown value tag {other:?} in checkpoint")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn globals_round_trip() {
+        let sparse = SparseGrid {
+            cells: HashMap::from([((0, 1), Value::Str("#".into()))]),
+            default: Value::Str(".".into()),
+        };
+        let mut graph = Graph::default();
+        graph.add_edge(Value::Number(1), Value::Number(2));
+        let globals: Vec<(Rc<str>, Value)> = vec![
+            (Rc::from("step"), Value::Number(-42)),
+            (Rc::from("label"), Value::Str("two words\nand a newline".into())),
+            (Rc::from("seen"), Value::Bool(true)),
+            (
+                Rc::from("mixed"),
+                Value::Array1D(vec![Value::Number(1), Value::Str("x".into())]),
+            ),
+            (Rc::from("row"), Value::NumArray(vec![3, 1, 2])),
+            (
+                Rc::from("board"),
+                Value::Array2D(Rc::new(vec![vec![Value::Number(0), Value::Number(1)]])),
+            ),
+            (
+                Rc::from("span"),
+                Value::Range(RangeVal {
+                    start: 5,
+                    len: 10,
+                    step: 2,
+                }),
+            ),
+            (Rc::from("at"), Value::Point(3, -4)),
+            (Rc::from("world"), Value::Sparse(Rc::new(sparse))),
+            (Rc::from("net"), Value::Graph(Rc::new(graph))),
+            (
+                Rc::from("visited"),
+                Value::Set(Rc::new(SetVal {
+                    items: [Value::Number(3), Value::Str("x".into())].into_iter().collect(),
+                })),
+            ),
+            (
+                Rc::from("tally"),
+                Value::Map(Rc::new(MapVal {
+                    entries: HashMap::from([
+                        (Value::Str("a".into()), Value::Number(2)),
+                        (Value::Number(7), Value::Bool(false)),
+                    ]),
+                })),
+            ),
+        ];
+        let text = serialize(&globals);
+        let parsed = parse(&text).expect("checkpoint should parse");
+        assert_eq!(parsed.len(), globals.len());
+        for ((name, value), (back_name, back_value)) in globals.iter().zip(&parsed) {
+            assert_eq!(&**name, back_name);
+            assert_eq!(value, back_value, "{name} did not round-trip");
+        }
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse("not a checkpoint").is_err());
+        assert!(parse("xmas-checkpoint 1\nx q:5").is_err());
+        assert!(parse("xmas-checkpoint 1\nx s:99:short").is_err());
+    }
+}
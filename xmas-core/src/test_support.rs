@@ -0,0 +1,130 @@
+//! Helpers for tests that run xmas programs.
+//!
+//! Day tests keep repeating the same shape — run a snippet, unwrap the
+//! result, compare `_` — so [`assert_xmas_eq!`](crate::assert_xmas_eq) wraps
+//! it up: the program text, optional input, and the expected result in one
+//! line, with a failure message that names the offending program.
+
+use crate::interpreter::{values_equal, Value};
+use crate::run_source;
+
+/// Something a test can expect `_` to be. Values (and the plain Rust types
+/// that stand in for them) compare structurally, with the language's own
+/// `==` coercions; a string expectation compares against the rendered
+/// result, like the CLI would print it.
+pub trait Expected {
+    fn matches(&self, actual: &Value) -> bool;
+    fn render(&self) -> String;
+}
+
+impl Expected for Value {
+    fn matches(&self, actual: &Value) -> bool {
+        values_equal(self, actual)
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Expected for i64 {
+    fn matches(&self, actual: &Value) -> bool {
+        values_equal(&Value::Number(*self), actual)
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Expected for bool {
+    fn matches(&self, actual: &Value) -> bool {
+        values_equal(&Value::Bool(*self), actual)
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Expected for Vec<i64> {
+    fn matches(&self, actual: &Value) -> bool {
+        values_equal(&Value::NumArray(self.clone()), actual)
+    }
+
+    fn render(&self) -> String {
+        Value::NumArray(self.clone()).to_string()
+    }
+}
+
+impl Expected for &str {
+    fn matches(&self, actual: &Value) -> bool {
+        actual.to_string() == *self
+    }
+
+    fn render(&self) -> String {
+        (*self).to_string()
+    }
+}
+
+/// Runs `program` and checks `_` against `expected`, panicking with the
+/// program text on any failure. The engine behind
+/// [`assert_xmas_eq!`](crate::assert_xmas_eq); callers normally use the
+/// macro.
+pub fn assert_program_eq(program: &str, input: Option<&str>, expected: &dyn Expected) {
+    let result = match run_source(program, input) {
+        Ok(Some(value)) => value,
+        Ok(None) => panic!("program did not set _:\n{program}"),
+        Err(e) => panic!("program failed: {e}\n{program}"),
+    };
+    assert!(
+        expected.matches(&result),
+        "expected {}, got {result}\n{program}",
+        expected.render()
+    );
+}
+
+/// Asserts that a program sets `_` to the expected result.
+///
+/// `assert_xmas_eq!(program, expected)` runs without input;
+/// `assert_xmas_eq!(program, input, expected)` provides puzzle input. The
+/// expectation may be a [`Value`], a plain number, bool or number vector
+/// (compared structurally), or a `&str` (compared against the rendered
+/// result).
+#[macro_export]
+macro_rules! assert_xmas_eq {
+    ($program:expr, $expected:expr $(,)?) => {
+        $crate::test_support::assert_program_eq($program, None, &$expected)
+    };
+    ($program:expr, $input:expr, $expected:expr $(,)?) => {
+        $crate::test_support::assert_program_eq($program, Some($input), &$expected)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Value;
+
+    #[test]
+    fn compares_structured_and_rendered_expectations() {
+        assert_xmas_eq!("_ = 2 + 2", 4);
+        assert_xmas_eq!("_ = 1 < 2", true);
+        assert_xmas_eq!("_ = [1, 2] + [3]", vec![1, 2, 3]);
+        assert_xmas_eq!(r#"_ = "a" + "b""#, Value::Str("ab".into()));
+        // A string expectation compares against the rendered result.
+        assert_xmas_eq!("_ = [1, 2]", "[1, 2]");
+        assert_xmas_eq!("_ = len(input)", "ab\ncd", 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 5, got 4")]
+    fn mismatches_report_both_sides() {
+        assert_xmas_eq!("_ = 2 + 2", 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "program failed")]
+    fn program_errors_surface_in_the_panic() {
+        assert_xmas_eq!("_ = 1 / 0", 0);
+    }
+}
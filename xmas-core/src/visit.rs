@@ -0,0 +1,387 @@
+//! Traversal and rewriting over the AST.
+//!
+//! [`Visitor`] walks a program read-only and [`Folder`] rebuilds it node by
+//! node. Both default every method to a full walk, so a pass — an optimizer,
+//! a linter, an instrumenter — overrides only the node kinds it cares about
+//! and inherits the plumbing for everything else.
+
+use crate::ast::{Block, Expr, FnBody, MatchArm, Pattern, Stmt};
+
+/// Read-only traversal. Override a `visit_*` method to observe that node
+/// kind; call the matching `walk_*` function inside the override to continue
+/// into children.
+pub trait Visitor {
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern);
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for (_, stmt) in block {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::Assign { value, .. } => visitor.visit_expr(value),
+        Stmt::IndexAssign { target, value } => {
+            for index in &target.indices {
+                visitor.visit_expr(index);
+            }
+            visitor.visit_expr(value);
+        }
+        Stmt::AssignOp { target, value, .. } => {
+            for index in &target.indices {
+                visitor.visit_expr(index);
+            }
+            visitor.visit_expr(value);
+        }
+        Stmt::FnDef { body, .. } => match body {
+            FnBody::Expr(expr) => visitor.visit_expr(expr),
+            FnBody::Block(block) => visitor.visit_block(block),
+        },
+        Stmt::GlobalAssign { value, .. } => visitor.visit_expr(value),
+        Stmt::If {
+            cond,
+            then_block,
+            else_block,
+        } => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(then_block);
+            visitor.visit_block(else_block);
+        }
+        Stmt::While { cond, body, .. } => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(body);
+        }
+        Stmt::For {
+            iter, init, body, ..
+        } => {
+            visitor.visit_expr(iter);
+            if let Some((_, value)) = init {
+                visitor.visit_expr(value);
+            }
+            visitor.visit_block(body);
+        }
+        Stmt::Break { .. } | Stmt::Continue => {}
+        Stmt::Return(value) => visitor.visit_expr(value),
+        Stmt::Match { subject, arms } => {
+            visitor.visit_expr(subject);
+            for arm in arms {
+                visitor.visit_pattern(&arm.pattern);
+                visitor.visit_block(&arm.body);
+            }
+        }
+        Stmt::Expr(expr) => visitor.visit_expr(expr),
+    }
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Literal(literal) => visitor.visit_expr(literal),
+        Pattern::Binding(_) => {}
+        Pattern::Array(items, _) => {
+            for item in items {
+                visitor.visit_pattern(item);
+            }
+        }
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Number(_)
+        | Expr::Str(_)
+        | Expr::Bool(_)
+        | Expr::Identifier(_)
+        | Expr::Input
+        | Expr::InputMeta => {}
+        Expr::Array(items) => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        }
+        Expr::Map(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Range(lo, hi) => {
+            visitor.visit_expr(lo);
+            visitor.visit_expr(hi);
+        }
+        Expr::Unary(_, operand) => visitor.visit_expr(operand),
+        Expr::Binary(lhs, _, rhs) | Expr::Pipe(lhs, rhs) | Expr::Index(lhs, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Slice(base, lo, hi) => {
+            visitor.visit_expr(base);
+            if let Some(lo) = lo {
+                visitor.visit_expr(lo);
+            }
+            if let Some(hi) = hi {
+                visitor.visit_expr(hi);
+            }
+        }
+        Expr::Call(_, args, named) => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+            for (_, arg) in named {
+                visitor.visit_expr(arg);
+            }
+        }
+    }
+}
+
+/// Rebuilding traversal: each method takes a node by value and returns its
+/// replacement. Override a `fold_*` method to rewrite that node kind; call
+/// the matching `fold_*` free function inside the override to rebuild
+/// children first.
+pub trait Folder {
+    fn fold_block(&mut self, block: Block) -> Block {
+        fold_block(self, block)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        fold_stmt(self, stmt)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold_expr(self, expr)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        fold_pattern(self, pattern)
+    }
+}
+
+pub fn fold_block<F: Folder + ?Sized>(folder: &mut F, block: Block) -> Block {
+    block
+        .into_iter()
+        .map(|(line, stmt)| (line, folder.fold_stmt(stmt)))
+        .collect()
+}
+
+pub fn fold_stmt<F: Folder + ?Sized>(folder: &mut F, stmt: Stmt) -> Stmt {
+    match stmt {
+        Stmt::GlobalAssign { name, value } => Stmt::GlobalAssign {
+            name,
+            value: folder.fold_expr(value),
+        },
+        Stmt::Assign { name, value } => Stmt::Assign {
+            name,
+            value: folder.fold_expr(value),
+        },
+        Stmt::IndexAssign { mut target, value } => {
+            target.indices = target
+                .indices
+                .into_iter()
+                .map(|index| folder.fold_expr(index))
+                .collect();
+            Stmt::IndexAssign {
+                target,
+                value: folder.fold_expr(value),
+            }
+        }
+        Stmt::AssignOp {
+            mut target,
+            op,
+            value,
+        } => {
+            target.indices = target
+                .indices
+                .into_iter()
+                .map(|index| folder.fold_expr(index))
+                .collect();
+            Stmt::AssignOp {
+                target,
+                op,
+                value: folder.fold_expr(value),
+            }
+        }
+        Stmt::FnDef {
+            name,
+            params,
+            body,
+            memoized,
+            doc,
+        } => Stmt::FnDef {
+            name,
+            params,
+            body: match body {
+                FnBody::Expr(expr) => FnBody::Expr(folder.fold_expr(expr)),
+                FnBody::Block(block) => FnBody::Block(folder.fold_block(block)),
+            },
+            memoized,
+            doc,
+        },
+        Stmt::If {
+            cond,
+            then_block,
+            else_block,
+        } => Stmt::If {
+            cond: folder.fold_expr(cond),
+            then_block: folder.fold_block(then_block),
+            else_block: folder.fold_block(else_block),
+        },
+        Stmt::While { cond, body, label } => Stmt::While {
+            cond: folder.fold_expr(cond),
+            body: folder.fold_block(body),
+            label,
+        },
+        Stmt::For {
+            var,
+            iter,
+            init,
+            body,
+            label,
+        } => Stmt::For {
+            var,
+            iter: folder.fold_expr(iter),
+            init: init.map(|(name, value)| (name, folder.fold_expr(value))),
+            body: folder.fold_block(body),
+            label,
+        },
+        Stmt::Break { label } => Stmt::Break { label },
+        Stmt::Continue => Stmt::Continue,
+        Stmt::Return(value) => Stmt::Return(folder.fold_expr(value)),
+        Stmt::Match { subject, arms } => Stmt::Match {
+            subject: folder.fold_expr(subject),
+            arms: arms
+                .into_iter()
+                .map(|arm| MatchArm {
+                    pattern: folder.fold_pattern(arm.pattern),
+                    body: folder.fold_block(arm.body),
+                })
+                .collect(),
+        },
+        Stmt::Expr(expr) => Stmt::Expr(folder.fold_expr(expr)),
+    }
+}
+
+pub fn fold_pattern<F: Folder + ?Sized>(folder: &mut F, pattern: Pattern) -> Pattern {
+    match pattern {
+        Pattern::Literal(literal) => Pattern::Literal(folder.fold_expr(literal)),
+        Pattern::Binding(name) => Pattern::Binding(name),
+        Pattern::Array(items, rest) => Pattern::Array(
+            items
+                .into_iter()
+                .map(|item| folder.fold_pattern(item))
+                .collect(),
+            rest,
+        ),
+    }
+}
+
+pub fn fold_expr<F: Folder + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    fn boxed<F: Folder + ?Sized>(folder: &mut F, expr: Expr) -> Box<Expr> {
+        Box::new(folder.fold_expr(expr))
+    }
+    match expr {
+        Expr::Number(_)
+        | Expr::Str(_)
+        | Expr::Bool(_)
+        | Expr::Identifier(_)
+        | Expr::Input
+        | Expr::InputMeta => expr,
+        Expr::Array(items) => Expr::Array(
+            items
+                .into_iter()
+                .map(|item| folder.fold_expr(item))
+                .collect(),
+        ),
+        Expr::Map(entries) => Expr::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (folder.fold_expr(key), folder.fold_expr(value)))
+                .collect(),
+        ),
+        Expr::Range(lo, hi) => Expr::Range(boxed(folder, *lo), boxed(folder, *hi)),
+        Expr::Unary(op, operand) => Expr::Unary(op, boxed(folder, *operand)),
+        Expr::Binary(lhs, op, rhs) => Expr::Binary(boxed(folder, *lhs), op, boxed(folder, *rhs)),
+        Expr::Index(base, index) => Expr::Index(boxed(folder, *base), boxed(folder, *index)),
+        Expr::Slice(base, lo, hi) => Expr::Slice(
+            boxed(folder, *base),
+            lo.map(|lo| boxed(folder, *lo)),
+            hi.map(|hi| boxed(folder, *hi)),
+        ),
+        Expr::Call(name, args, named) => Expr::Call(
+            name,
+            args.into_iter().map(|arg| folder.fold_expr(arg)).collect(),
+            named
+                .into_iter()
+                .map(|(arg_name, arg)| (arg_name, folder.fold_expr(arg)))
+                .collect(),
+        ),
+        Expr::Pipe(lhs, rhs) => Expr::Pipe(boxed(folder, *lhs), boxed(folder, *rhs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BinOp;
+    use crate::lexer::lex;
+    use crate::parser::parse;
+
+    fn parse_src(source: &str) -> Block {
+        parse(lex(source).unwrap(), source).unwrap()
+    }
+
+    #[test]
+    fn visitor_reaches_every_expression() {
+        struct CountNumbers(usize);
+        impl Visitor for CountNumbers {
+            fn visit_expr(&mut self, expr: &Expr) {
+                if matches!(expr, Expr::Number(_)) {
+                    self.0 += 1;
+                }
+                walk_expr(self, expr);
+            }
+        }
+        let program = parse_src(
+            "fn f(x) = x + 1\n\
+             m = {\"a\": [2, 3]}\n\
+             if (f(4) > 5) { t = [6..7] } else { t = m[\"a\"][:8] }",
+        );
+        let mut counter = CountNumbers(0);
+        counter.visit_block(&program);
+        assert_eq!(counter.0, 8);
+    }
+
+    #[test]
+    fn folder_rewrites_nested_expressions() {
+        /// Folds `a + b` of two number literals into one literal.
+        struct FoldAdd;
+        impl Folder for FoldAdd {
+            fn fold_expr(&mut self, expr: Expr) -> Expr {
+                match fold_expr(self, expr) {
+                    Expr::Binary(lhs, BinOp::Add, rhs) => match (*lhs, *rhs) {
+                        (Expr::Number(a), Expr::Number(b)) => Expr::Number(a + b),
+                        (lhs, rhs) => Expr::binary(lhs, BinOp::Add, rhs),
+                    },
+                    other => other,
+                }
+            }
+        }
+        let program = parse_src("_ = (1 + 2) + (3 + x)");
+        let folded = FoldAdd.fold_block(program);
+        assert_eq!(folded, parse_src("_ = 3 + (3 + x)"));
+    }
+}
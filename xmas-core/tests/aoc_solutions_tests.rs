@@ -0,0 +1,81 @@
+//! Runs every solution listed in the repo-root `solutions.toml` against its
+//! input and checks the known-good answer, so interpreter changes can't
+//! silently break the checked-in solutions. Adding a day's regression check
+//! is a data change in the manifest, not a new test.
+
+use xmas_core::run_source;
+
+#[derive(Debug, Default)]
+struct Solution {
+    program: String,
+    input: String,
+    expected: String,
+}
+
+/// Parses the manifest: `[[solution]]` tables with `key = "value"` entries.
+/// Deliberately minimal — just what the manifest uses, not general TOML.
+fn parse_manifest(text: &str) -> Vec<Solution> {
+    let mut solutions: Vec<Solution> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "[[solution]]" {
+            solutions.push(Solution::default());
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .unwrap_or_else(|| panic!("solutions.toml line {}: expected key = \"value\"", number + 1));
+        let value = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or_else(|| panic!("solutions.toml line {}: value must be quoted", number + 1))
+            .to_string();
+        let entry = solutions
+            .last_mut()
+            .unwrap_or_else(|| panic!("solutions.toml line {}: key before [[solution]]", number + 1));
+        match key.trim() {
+            "program" => entry.program = value,
+            "input" => entry.input = value,
+            "expected" => entry.expected = value,
+            other => panic!("solutions.toml line {}: unknown key {other}", number + 1),
+        }
+    }
+    solutions
+}
+
+fn run_solution(program: &str, input: &str) -> Result<String, String> {
+    let source = std::fs::read_to_string(format!("../{program}"))
+        .map_err(|e| format!("cannot read {program}: {e}"))?;
+    let input = std::fs::read_to_string(format!("../{input}"))
+        .map_err(|e| format!("cannot read {input}: {e}"))?;
+    run_source(&source, Some(&input))
+        .map_err(|e| e.to_string())?
+        .map(|value| value.to_string())
+        .ok_or_else(|| "did not set _".to_string())
+}
+
+#[test]
+fn checked_in_solutions_match_manifest() {
+    let manifest = std::fs::read_to_string("../solutions.toml")
+        .expect("solutions.toml should exist at the repo root");
+    let solutions = parse_manifest(&manifest);
+    assert!(!solutions.is_empty(), "solutions.toml lists no solutions");
+
+    // Collect every mismatch so one broken day doesn't hide the rest.
+    let mut failures = Vec::new();
+    for solution in &solutions {
+        match run_solution(&solution.program, &solution.input) {
+            Ok(result) if result == solution.expected => {}
+            Ok(result) => failures.push(format!(
+                "{}: expected {}, got {result}",
+                solution.program, solution.expected
+            )),
+            Err(e) => failures.push(format!("{}: {e}", solution.program)),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
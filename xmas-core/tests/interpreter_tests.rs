@@ -0,0 +1,1805 @@
+//! End-to-end language tests: run a source snippet and check the value of `_`.
+
+use xmas_core::interpreter::Value;
+use xmas_core::run_source;
+
+fn run(source: &str) -> Value {
+    run_source(source, None)
+        .expect("program should run")
+        .expect("program should set _")
+}
+
+fn run_with_input(source: &str, input: &str) -> Value {
+    run_source(source, Some(input))
+        .expect("program should run")
+        .expect("program should set _")
+}
+
+#[test]
+fn arithmetic_precedence() {
+    assert_eq!(run("_ = 2 + 3 * 4"), Value::Number(14));
+    assert_eq!(run("_ = (2 + 3) * 4"), Value::Number(20));
+    assert_eq!(run("_ = 7 % 3"), Value::Number(1));
+}
+
+#[test]
+fn string_concat_and_tilde() {
+    assert_eq!(run(r#"_ = "a" + "b""#), Value::Str("ab".into()));
+    assert_eq!(run(r#"_ = ~"42" + 1"#), Value::Number(43));
+}
+
+#[test]
+fn dollar_converts_to_string() {
+    assert_eq!(run("_ = $42"), Value::Str("42".into()));
+    assert_eq!(run("_ = $12 + $34"), Value::Str("1234".into()));
+    assert_eq!(run("_ = ~$99 + 1"), Value::Number(100));
+    assert_eq!(run("_ = len($[1, 2])"), Value::Number(6));
+}
+
+#[test]
+fn string_and_array_repetition() {
+    assert_eq!(run(r#"_ = "ab" * 3"#), Value::Str("ababab".into()));
+    assert_eq!(
+        run("_ = [0] * 3"),
+        Value::Array1D(vec![Value::Number(0), Value::Number(0), Value::Number(0)])
+    );
+    assert_eq!(run(r#"_ = len([1, 2] * 2)"#), Value::Number(4));
+    assert!(run_source(r#"_ = "x" * -1"#, None).is_err());
+}
+
+#[test]
+fn for_loop_accumulates() {
+    let source = "
+        total = 0
+        for (n in [1..5]) {
+            total += n
+        }
+        _ = total
+    ";
+    assert_eq!(run(source), Value::Number(10));
+}
+
+#[test]
+fn compound_assignment_on_indexed_elements() {
+    let source = "
+        counts = fill(3, 0)
+        counts[1] += 5
+        counts[1] *= 2
+        _ = counts
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(0), Value::Number(10), Value::Number(0)])
+    );
+    let source = "
+        grid = fill2d(2, 2, 1)
+        grid[0][1] += 9
+        _ = grid[0]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(1), Value::Number(10)])
+    );
+    // Indexing without a compound operator is still just an expression.
+    let source = "
+        a = [1, 2]
+        a[0] == 1
+        _ = a[0]
+    ";
+    assert_eq!(run(source), Value::Number(1));
+}
+
+#[test]
+fn labeled_break_exits_nested_loops() {
+    let source = "
+        found = 0
+        outer: for (r in [0..10]) {
+            for (c in [0..10]) {
+                if (r * 10 + c == 42) {
+                    found = r * 100 + c
+                    break outer
+                }
+            }
+        }
+        _ = found
+    ";
+    assert_eq!(run(source), Value::Number(402));
+    let source = "
+        n = 0
+        loop: while (true) {
+            n += 1
+            if (n == 5) { break loop }
+        }
+        _ = n
+    ";
+    assert_eq!(run(source), Value::Number(5));
+    let err = run_source("x: for (i in [0..2]) { break y }", None).unwrap_err();
+    assert!(err.to_string().contains("no enclosing loop"), "{err}");
+}
+
+#[test]
+fn while_loop_and_if() {
+    let source = "
+        n = 1
+        while (n < 100) {
+            n *= 2
+        }
+        if (n == 128) { _ = n } else { _ = 0 }
+    ";
+    assert_eq!(run(source), Value::Number(128));
+}
+
+#[test]
+fn user_function_call() {
+    let source = "
+        fn double(x) = x * 2
+        _ = double(21)
+    ";
+    assert_eq!(run(source), Value::Number(42));
+}
+
+#[test]
+fn keyword_argument_calls() {
+    let source = "
+        fn scale(value, by) = value * by
+        _ = scale(7, by = 6)
+    ";
+    assert_eq!(run(source), Value::Number(42));
+    let source = "
+        fn scale(value, by) = value * by
+        _ = scale(by = 6, value = 7)
+    ";
+    assert_eq!(run(source), Value::Number(42));
+    let err = run_source("fn f(a) = a\n_ = f(b = 1)", None).unwrap_err();
+    assert!(err.to_string().contains("no parameter named b"), "{err}");
+    let err = run_source("fn f(a, b) = a\n_ = f(1, a = 2)", None).unwrap_err();
+    assert!(err.to_string().contains("already given positionally"), "{err}");
+    let err = run_source("fn f(a, b) = a\n_ = f(a = 1)", None).unwrap_err();
+    assert!(err.to_string().contains("missing argument b"), "{err}");
+}
+
+#[test]
+fn function_params_restore_shadowed_globals() {
+    let source = "
+        x = 10
+        fn double(x) = x * 2
+        y = double(3)
+        _ = x + y
+    ";
+    assert_eq!(run(source), Value::Number(16));
+}
+
+#[test]
+fn indexing_and_slicing() {
+    assert_eq!(run("_ = [10, 20, 30][1]"), Value::Number(20));
+    assert_eq!(run("_ = [10, 20, 30][-1]"), Value::Number(30));
+    assert_eq!(
+        run("_ = [1, 2, 3, 4][1:3]"),
+        Value::Array1D(vec![Value::Number(2), Value::Number(3)])
+    );
+    assert_eq!(run(r#"_ = "hello"[1:100]"#), Value::Str("ello".into()));
+}
+
+#[test]
+fn builtin_calls() {
+    assert_eq!(run("_ = len([1, 2, 3])"), Value::Number(3));
+    assert_eq!(run("_ = max([3, 1, 2])"), Value::Number(3));
+    assert_eq!(run("_ = min(5, 2)"), Value::Number(2));
+    assert_eq!(run("_ = floor(7, 2)"), Value::Number(3));
+    assert_eq!(run("_ = ceil(7, 2)"), Value::Number(4));
+}
+
+#[test]
+fn help_returns_doc_comments() {
+    let source = r#"
+        /// Doubles a number.
+        fn double(x) = x * 2
+        _ = help("double")
+    "#;
+    assert_eq!(
+        run(source),
+        Value::Str("fn double(x)\nDoubles a number.".into())
+    );
+    let help = run(r#"_ = help("len")"#);
+    assert_eq!(
+        help,
+        Value::Str("len(x): the length of a string, array or range".into())
+    );
+    let err = run_source(r#"_ = help("nope")"#, None).unwrap_err();
+    assert!(err.to_string().contains("unknown function"), "{err}");
+}
+
+#[test]
+fn user_functions_shadow_builtins() {
+    let source = "
+        fn len(x) = 99
+        _ = len([1, 2, 3])
+    ";
+    assert_eq!(run(source), Value::Number(99));
+    // Builtin names are ordinary identifiers, so they can even be variables.
+    assert_eq!(run("max = 5\n_ = max + 1"), Value::Number(6));
+}
+
+#[test]
+fn fill_and_generate_builtins() {
+    assert_eq!(
+        run("_ = fill(3, 7)"),
+        Value::Array1D(vec![Value::Number(7), Value::Number(7), Value::Number(7)])
+    );
+    assert_eq!(
+        run("_ = fill2d(2, 3, 0)[1]"),
+        Value::Array1D(vec![Value::Number(0), Value::Number(0), Value::Number(0)])
+    );
+    let source = "
+        fn square(i) = i * i
+        _ = generate(4, square)
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::Number(0),
+            Value::Number(1),
+            Value::Number(4),
+            Value::Number(9)
+        ])
+    );
+}
+
+#[test]
+fn digit_builtins() {
+    assert_eq!(
+        run("_ = digits(1024)"),
+        Value::Array1D(vec![
+            Value::Number(1),
+            Value::Number(0),
+            Value::Number(2),
+            Value::Number(4)
+        ])
+    );
+    assert_eq!(
+        run("_ = digits(0)"),
+        Value::Array1D(vec![Value::Number(0)])
+    );
+    assert_eq!(run("_ = fromDigits(digits(90210))"), Value::Number(90210));
+    assert_eq!(run("_ = numLen(90210)"), Value::Number(5));
+    assert_eq!(run("_ = numLen(0)"), Value::Number(1));
+    assert_eq!(run("_ = numLen(-345)"), Value::Number(3));
+    let err = run_source("_ = fromDigits([1, 12])", None).unwrap_err();
+    assert!(err.to_string().contains("expected a digit"), "{err}");
+}
+
+#[test]
+fn sort_builtins() {
+    assert_eq!(
+        run("_ = sort([3, 1, 2])"),
+        Value::Array1D(vec![Value::Number(1), Value::Number(2), Value::Number(3)])
+    );
+    let source = "
+        fn neg(x) = 0 - x
+        _ = sortBy([1, 3, 2], neg)
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(3), Value::Number(2), Value::Number(1)])
+    );
+}
+
+#[test]
+fn lazy_range_queries() {
+    // None of these materialize the billion-element range.
+    assert_eq!(run("_ = len([1..1000000000])"), Value::Number(999999999));
+    assert_eq!(
+        run("_ = contains([1..1000000000], 123456789)"),
+        Value::Bool(true)
+    );
+    assert_eq!(run("_ = contains([1..10], 10)"), Value::Bool(false));
+    assert_eq!(run("_ = [1..1000000000][5]"), Value::Number(6));
+    assert_eq!(run("_ = [1..1000000000][-1]"), Value::Number(999999999));
+    assert_eq!(run("_ = reverse([1..1000000000])[0]"), Value::Number(999999999));
+    assert_eq!(run("_ = len(reverse([1..5]))"), Value::Number(4));
+}
+
+#[test]
+fn deep_equality_on_grids_and_nested_arrays() {
+    let source = "
+        a = fill2d(2, 2, 0)
+        b = fill2d(2, 2, 0)
+        _ = a == b
+    ";
+    assert_eq!(run(source), Value::Bool(true));
+    assert_eq!(run("_ = [[1], [2]] == [[1], [2]]"), Value::Bool(true));
+    assert_eq!(run("_ = [[1], [2]] != [[1], [3]]"), Value::Bool(true));
+    // A 2D array equals the equivalent array of rows.
+    assert_eq!(run("_ = fill2d(1, 2, 5) == [[5, 5]]"), Value::Bool(true));
+    // Numbers and bools coerce.
+    assert_eq!(run("_ = [1, 0] == [true, false]"), Value::Bool(true));
+    assert_eq!(run("_ = [1..3] == [1, 2]"), Value::Bool(true));
+}
+
+#[test]
+fn input_is_a_char_grid() {
+    let source = "
+        _ = input[0][1]
+    ";
+    assert_eq!(run_with_input(source, "ab\ncd\n"), Value::Str("b".into()));
+}
+
+#[test]
+fn named_part_results() {
+    use xmas_core::interpreter::Interpreter;
+    use xmas_core::{lexer, parser};
+
+    let source = "_part2 = 20\n_part1 = 10";
+    let program = parser::parse(lexer::lex(source).unwrap(), source).unwrap();
+    let mut interp = Interpreter::new();
+    interp.run(&program).unwrap();
+    assert_eq!(
+        interp.named_results(),
+        vec![("part1", Value::Number(10)), ("part2", Value::Number(20))]
+    );
+    assert_eq!(interp.result(), None);
+}
+
+#[test]
+fn trace_records_statements_and_values() {
+    use xmas_core::interpreter::Interpreter;
+    use xmas_core::{lexer, parser};
+
+    let source = "x = 1 + 2\nx += 1";
+    let program = parser::parse(lexer::lex(source).unwrap(), source).unwrap();
+    let path = std::env::temp_dir().join("xmas_trace_test.log");
+    {
+        let mut interp = Interpreter::new();
+        interp.set_trace(Box::new(std::fs::File::create(&path).unwrap()));
+        interp.run(&program).unwrap();
+    }
+    let trace = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert!(trace.contains("line 1: x = 3"), "{trace}");
+    assert!(trace.contains("line 2: x = 4"), "{trace}");
+}
+
+#[test]
+fn folded_profile_counts_call_stacks() {
+    use xmas_core::interpreter::Interpreter;
+    use xmas_core::{lexer, parser};
+
+    let source = "
+        fn inner(x) = x + 1
+        fn outer(x) = inner(x) * 2
+        _ = outer(1)
+    ";
+    let program = parser::parse(lexer::lex(source).unwrap(), source).unwrap();
+    let mut interp = Interpreter::new();
+    interp.enable_folded_profiling();
+    interp.run(&program).unwrap();
+    let folded = interp.folded_report().unwrap();
+    assert!(folded.contains("main;outer;inner 1"), "{folded}");
+}
+
+#[test]
+fn undefined_variable_errors() {
+    let err = run_source("_ = nope", None).unwrap_err();
+    assert!(err.to_string().contains("undefined variable"), "{err}");
+}
+
+#[test]
+fn overflow_modes() {
+    let big = i64::MAX;
+    let err = run_source(&format!("_ = {big} + 1"), None).unwrap_err();
+    assert!(err.to_string().contains("number overflow"), "{err}");
+    assert_eq!(
+        run(&format!("wrapping()\n_ = {big} + 1")),
+        Value::Number(i64::MIN)
+    );
+    assert_eq!(
+        run(&format!("saturating()\n_ = {big} + 1")),
+        Value::Number(i64::MAX)
+    );
+    // `checked()` restores the default.
+    let source = format!("wrapping()\nchecked()\n_ = {big} + 1");
+    assert!(run_source(&source, None).is_err());
+}
+
+#[test]
+fn division_by_zero_errors() {
+    let err = run_source("_ = 1 / 0", None).unwrap_err();
+    assert!(err.to_string().contains("division by zero"), "{err}");
+}
+
+#[test]
+fn packed_number_arrays_are_transparent() {
+    // All-number arrays use the packed representation...
+    assert!(matches!(run("_ = [3, 1, 2]"), Value::NumArray(_)));
+    assert!(matches!(run(r#"_ = [1, "a"]"#), Value::Array1D(_)));
+    // ...but compare, print, index and mutate exactly like the general form.
+    assert_eq!(
+        run("_ = sort([3, 1, 2])"),
+        Value::Array1D(vec![Value::Number(1), Value::Number(2), Value::Number(3)])
+    );
+    assert_eq!(run("_ = $[1, 2]"), Value::Str("[1, 2]".into()));
+    assert_eq!(run("a = [1, 2, 3]\n_ = a[-1]"), Value::Number(3));
+    assert_eq!(
+        run("a = fill(3, 0)\na[1] += 5\n_ = a"),
+        Value::Array1D(vec![Value::Number(0), Value::Number(5), Value::Number(0)])
+    );
+    // Updates through an index stay packed; mixed forms still compare equal.
+    assert!(matches!(
+        run("a = [1, 2]\na[0] += 1\n_ = a"),
+        Value::NumArray(_)
+    ));
+    assert_eq!(run("_ = [1, true] == [1, 1]"), Value::Bool(true));
+}
+
+#[test]
+fn argmin_and_argmax() {
+    assert_eq!(run("_ = argmax([3, 9, 2])"), Value::Number(1));
+    assert_eq!(run("_ = argmin([3, 9, 2])"), Value::Number(2));
+    // Ties go to the first occurrence; a key function reorders what wins.
+    assert_eq!(run("_ = argmin([1, 1, 2])"), Value::Number(0));
+    assert_eq!(
+        run("fn neg(x) = 0 - x\n_ = argmax([3, 9, 2], neg)"),
+        Value::Number(2)
+    );
+    let err = run_source("_ = argmax([])", None).unwrap_err();
+    assert!(err.to_string().contains("empty array"), "{err}");
+}
+
+#[test]
+fn rotate_wraps_in_both_directions() {
+    assert_eq!(
+        run("_ = rotate([1, 2, 3, 4], 1)"),
+        Value::Array1D(vec![
+            Value::Number(4),
+            Value::Number(1),
+            Value::Number(2),
+            Value::Number(3)
+        ])
+    );
+    assert_eq!(run("_ = rotate([1, 2, 3], -1)[0]"), Value::Number(2));
+    // Shifts larger than the length wrap; empty arrays are a no-op.
+    assert_eq!(run("_ = rotate([1, 2, 3], 7)"), run("_ = rotate([1, 2, 3], 1)"));
+    assert_eq!(run("_ = len(rotate([], 5))"), Value::Number(0));
+}
+
+#[test]
+fn pairs_covers_every_unordered_pair() {
+    assert_eq!(run("_ = len(pairs([1, 2, 3, 4]))"), Value::Number(6));
+    assert_eq!(
+        run("_ = pairs([1, 2, 3])[0]"),
+        Value::Array1D(vec![Value::Number(1), Value::Number(2)])
+    );
+    assert_eq!(
+        run("_ = pairs([1, 2, 3])[-1]"),
+        Value::Array1D(vec![Value::Number(2), Value::Number(3)])
+    );
+    assert_eq!(run("_ = len(pairs([7]))"), Value::Number(0));
+}
+
+#[test]
+fn string_extraction_helpers() {
+    assert_eq!(run(r#"_ = sumOfDigits("a1b2c3")"#), Value::Number(6));
+    assert_eq!(run(r#"_ = sumOfDigits("no digits")"#), Value::Number(0));
+    assert_eq!(
+        run(r#"_ = extract("17x9: ok", "{}x{}: {}")"#),
+        Value::Array1D(vec![
+            Value::Str("17".into()),
+            Value::Str("9".into()),
+            Value::Str("ok".into())
+        ])
+    );
+    assert_eq!(
+        run(r#"_ = ~extract("Game 3: 1 red", "Game {}: {}")[0]"#),
+        Value::Number(3)
+    );
+    let err = run_source(r#"_ = extract("abc", "x{}")"#, None).unwrap_err();
+    assert!(err.to_string().contains("does not start with"), "{err}");
+}
+
+#[test]
+fn points_unify_the_grid_api() {
+    let grid = "ab\ncd";
+    assert_eq!(run_with_input("_ = find2d(input, \"c\")", grid), Value::Point(1, 0));
+    assert_eq!(run_with_input("_ = find2d(input, \"z\")", grid), Value::Point(-1, -1));
+    // A point indexes a grid directly, and its components index like [r, c].
+    assert_eq!(
+        run_with_input("_ = input[find2d(input, \"d\")]", grid),
+        Value::Str("d".into())
+    );
+    assert_eq!(run("_ = point(3, 4)[1]"), Value::Number(4));
+    // Coordinate arithmetic and array compatibility.
+    assert_eq!(run("_ = point(1, 2) + point(3, 4)"), Value::Point(4, 6));
+    assert_eq!(run("_ = point(5, 5) - point(1, 2) * 2"), Value::Point(3, 1));
+    assert_eq!(run("_ = point(1, 2) == [1, 2]"), Value::Bool(true));
+    assert_eq!(
+        run_with_input("_ = len(neighbors(input, 0, 0))", grid),
+        Value::Number(2)
+    );
+    assert_eq!(
+        run_with_input("_ = neighbors(input, 0, 0)[0]", grid),
+        Value::Point(1, 0)
+    );
+    // Compound assignment through a point.
+    let source = "
+        grid = fill2d(2, 2, 0)
+        grid[point(1, 1)] += 9
+        _ = grid[1][1]
+    ";
+    assert_eq!(run(source), Value::Number(9));
+}
+
+#[test]
+fn sparse_grids_default_and_convert() {
+    let source = "
+        sand = sparse(0)
+        sand[point(5, 500)] += 1
+        sand[point(-2, 490)] += 7
+        _ = sand[point(5, 500)] + sand[point(99, 99)]
+    ";
+    assert_eq!(run(source), Value::Number(1));
+    let source = "
+        sg = sparse(0)
+        sg[point(1, 1)] += 3
+        sg[point(2, 4)] += 5
+        _ = bounds(sg)
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Point(1, 1), Value::Point(2, 4)])
+    );
+    // Round trip through the dense form.
+    let source = "
+        sg = sparse(0)
+        sg[point(0, 0)] += 1
+        sg[point(1, 2)] += 9
+        _ = dense(sg)[1]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(0), Value::Number(0), Value::Number(9)])
+    );
+    assert_eq!(
+        run_with_input("_ = len(sparse(input, \".\"))", "x.\n.y"),
+        Value::Number(2)
+    );
+    let err = run_source("_ = bounds(sparse(0))", None).unwrap_err();
+    assert!(err.to_string().contains("no cells set"), "{err}");
+}
+
+#[test]
+fn to_array_materializes_lazy_values() {
+    assert_eq!(
+        run("_ = toArray([1..4])"),
+        Value::Array1D(vec![Value::Number(1), Value::Number(2), Value::Number(3)])
+    );
+    assert_eq!(run("_ = sort(toArray([1..100]))[-1]"), Value::Number(99));
+    assert_eq!(
+        run("_ = toArray(point(2, 7))"),
+        Value::Array1D(vec![Value::Number(2), Value::Number(7)])
+    );
+    assert_eq!(
+        run(r#"_ = toArray("hi")"#),
+        Value::Array1D(vec![Value::Str("h".into()), Value::Str("i".into())])
+    );
+    let source = "
+        sg = sparse(0)
+        sg[point(1, 1)] += 5
+        _ = toArray(sg)[0]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Point(1, 1), Value::Number(5)])
+    );
+}
+
+#[test]
+fn is_empty_and_truthiness_agree() {
+    assert_eq!(run("_ = isEmpty([])"), Value::Bool(true));
+    assert_eq!(run("_ = isEmpty([1])"), Value::Bool(false));
+    assert_eq!(run(r#"_ = isEmpty("")"#), Value::Bool(true));
+    assert_eq!(run("_ = isEmpty([3..3])"), Value::Bool(true));
+    assert_eq!(run("_ = isEmpty(sparse(0))"), Value::Bool(true));
+    assert!(run_source("_ = isEmpty(7)", None).is_err());
+    // Truthiness matches: empty containers are falsy, points are truthy.
+    assert_eq!(run("_ = !([])"), Value::Bool(true));
+    assert_eq!(run("_ = !point(0, 0)"), Value::Bool(false));
+    assert_eq!(run(r#"_ = !(sparse(" "))"#), Value::Bool(true));
+}
+
+#[test]
+fn graph_adjacency_operations() {
+    let build = r#"
+        g = graph()
+        g = addEdge(g, "a", "b")
+        g = addEdge(g, "a", "c")
+        g = addEdge(g, "b", "d")
+        g = addEdge(g, "x", "y")
+    "#;
+    assert_eq!(
+        run(&format!("{build}\n_ = neighbors(g, \"a\")")),
+        Value::Array1D(vec![Value::Str("b".into()), Value::Str("c".into())])
+    );
+    assert_eq!(run(&format!("{build}\n_ = len(nodes(g))")), Value::Number(6));
+    assert_eq!(
+        run(&format!("{build}\n_ = len(connectedComponents(g))")),
+        Value::Number(2)
+    );
+    assert_eq!(
+        run(&format!("{build}\n_ = topoSort(g)[0]")),
+        Value::Str("a".into())
+    );
+    let err = run_source(
+        r#"
+        g = addEdge(addEdge(graph(), 1, 2), 2, 1)
+        _ = topoSort(g)
+        "#,
+        None,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("cycle"), "{err}");
+}
+
+#[test]
+fn max_flow_and_min_cut() {
+    // Two triangles joined by a single bridge edge.
+    let build = r#"
+        g = graph()
+        g = addEdge(g, "a", "b")
+        g = addEdge(g, "b", "c")
+        g = addEdge(g, "c", "a")
+        g = addEdge(g, "c", "d")
+        g = addEdge(g, "d", "e")
+        g = addEdge(g, "e", "f")
+        g = addEdge(g, "f", "d")
+    "#;
+    assert_eq!(
+        run(&format!("{build}\n_ = maxFlow(g, \"a\", \"e\")")),
+        Value::Number(1)
+    );
+    // Parallel edges stack capacity.
+    assert_eq!(
+        run("g = addEdge(addEdge(graph(), 1, 2), 1, 2)\n_ = maxFlow(g, 1, 2)"),
+        Value::Number(2)
+    );
+    // The minimum cut severs the bridge, leaving the two triangles.
+    assert_eq!(
+        run(&format!("{build}\n_ = len(minCut(g)[0])")),
+        Value::Number(3)
+    );
+    assert_eq!(
+        run(&format!("{build}\n_ = contains(minCut(g)[1], \"e\")")),
+        Value::Bool(true)
+    );
+    let err = run_source("_ = maxFlow(graph(), 1, 1)", None).unwrap_err();
+    assert!(err.to_string().contains("must differ"), "{err}");
+}
+
+#[test]
+fn memo_cache_stats_and_clear() {
+    let source = r#"
+        memo fn f(n) = n * n
+        warm = f(3) + f(3) + f(3) + f(4)
+        _ = memoStats("f")
+    "#;
+    // Two distinct arguments miss; the repeated calls hit.
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(2), Value::Number(2), Value::Number(2)])
+    );
+    let cleared = r#"
+        memo fn f(n) = n * n
+        warm = f(3) + f(4)
+        freed = memoClear("f")
+        _ = [freed, memoStats("f")[2]]
+    "#;
+    assert_eq!(
+        run(cleared),
+        Value::Array1D(vec![Value::Number(2), Value::Number(0)])
+    );
+    let err = run_source("fn g(n) = n\n_ = memoStats(\"g\")", None).unwrap_err();
+    assert!(err.to_string().contains("not a memo fn"), "{err}");
+}
+
+#[test]
+fn grid_builds_a_char_grid_from_a_string() {
+    assert_eq!(
+        run("g = grid(\"ab\\ncd\")\n_ = g[1][0]"),
+        Value::Str("c".into())
+    );
+    assert_eq!(run(r#"_ = len(grid("ab\ncd\ne"))"#), Value::Number(3));
+    // A grid built from a rendered string matches the input form.
+    assert_eq!(
+        run_with_input("_ = grid(\"xy\") == input", "xy"),
+        Value::Bool(true)
+    );
+    assert!(run_source("_ = grid(42)", None).is_err());
+}
+
+#[test]
+fn map_applies_a_function_per_element() {
+    assert_eq!(
+        run("fn double(x) = x * 2\n_ = map([1, 2, 3], double)"),
+        Value::Array1D(vec![Value::Number(2), Value::Number(4), Value::Number(6)])
+    );
+    assert_eq!(
+        run("fn square(x) = x * x\n_ = map([1..4], square)"),
+        Value::Array1D(vec![Value::Number(1), Value::Number(4), Value::Number(9)])
+    );
+    assert_eq!(
+        run(r#"fn shout(s) = s + "!"
+_ = map(["a", "b"], shout)"#),
+        Value::Array1D(vec![Value::Str("a!".into()), Value::Str("b!".into())])
+    );
+    assert!(run_source("fn id(x) = x\n_ = map(5, id)", None).is_err());
+}
+
+#[test]
+fn render_round_trips_with_grid() {
+    assert_eq!(
+        run(r#"_ = render(grid("ab\ncd"))"#),
+        Value::Str("ab\ncd".into())
+    );
+    // A sparse grid renders its bounding box, defaults filled in.
+    let source = r##"
+        sg = sparse(grid("#..\n..#"), ".")
+        _ = render(sg)
+    "##;
+    assert_eq!(run(source), Value::Str("#..\n..#".into()));
+    // The empty-cell character can be overridden.
+    let source = r##"
+        sg = sparse(grid("#..\n..#"), ".")
+        _ = render(sg, " ")
+    "##;
+    assert_eq!(run(source), Value::Str("#  \n  #".into()));
+    assert_eq!(run(r#"_ = render(sparse("."))"#), Value::Str("".into()));
+    // Without --animate, animate() is a pass-through no-op.
+    assert_eq!(
+        run(r#"_ = animate(grid("ab")) == grid("ab")"#),
+        Value::Bool(true)
+    );
+}
+
+#[test]
+fn filter_keeps_truthy_results() {
+    assert_eq!(
+        run("fn odd(x) = x % 2\n_ = filter([1, 2, 3, 4, 5], odd)"),
+        Value::Array1D(vec![Value::Number(1), Value::Number(3), Value::Number(5)])
+    );
+    assert_eq!(
+        run("fn big(x) = x > 7\n_ = filter([1..10], big)"),
+        Value::Array1D(vec![Value::Number(8), Value::Number(9)])
+    );
+    // Any truthy value keeps the element, not just true.
+    assert_eq!(
+        run(r#"fn itself(s) = s
+_ = filter(["a", "", "b"], itself)"#),
+        Value::Array1D(vec![Value::Str("a".into()), Value::Str("b".into())])
+    );
+    assert!(run_source("fn id(x) = x\n_ = filter(5, id)", None).is_err());
+}
+
+#[test]
+fn reduce_folds_with_an_initial_accumulator() {
+    assert_eq!(
+        run("fn add(acc, x) = acc + x\n_ = reduce([1, 2, 3, 4], add, 0)"),
+        Value::Number(10)
+    );
+    assert_eq!(
+        run("fn mul(acc, x) = acc * x\n_ = reduce([1..5], mul, 1)"),
+        Value::Number(24)
+    );
+    // The initial value comes back untouched for an empty array.
+    assert_eq!(
+        run("fn add(acc, x) = acc + x\n_ = reduce([], add, 42)"),
+        Value::Number(42)
+    );
+    assert!(run_source("fn f(a, b) = a\n_ = reduce(5, f, 0)", None).is_err());
+}
+
+#[test]
+fn bitset_operations() {
+    let source = "
+        seen = set(set(set(bitset(), 3), 900001), 3)
+        _ = [count(seen), get(seen, 3), get(seen, 4)]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(2), Value::Bool(true), Value::Bool(false)])
+    );
+    let source = "
+        a = set(set(bitset(), 1), 2)
+        b = set(set(bitset(), 2), 3)
+        _ = [count(and(a, b)), count(or(a, b)), count(xor(a, b))]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(1), Value::Number(3), Value::Number(2)])
+    );
+    // Combining trims, so differently-grown sets still compare equal.
+    assert_eq!(
+        run("_ = and(set(bitset(), 99), bitset()) == bitset()"),
+        Value::Bool(true)
+    );
+    let err = run_source("_ = set(bitset(), -1)", None).unwrap_err();
+    assert!(err.to_string().contains("negative"), "{err}");
+}
+
+#[test]
+fn lru_cache_evicts_least_recently_used() {
+    let source = r#"
+        c = cache(2)
+        c = put(c, "a", 1)
+        c = put(c, "b", 2)
+        touched = get(c, "a")
+        c = put(c, "d", 4)
+        _ = [len(c), contains(c, "a"), contains(c, "b"), get(c, "d")]
+    "#;
+    // "b" was the least recently used when "d" arrived.
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::Number(2),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Number(4),
+        ])
+    );
+    // Re-putting an existing key refreshes it instead of growing the cache.
+    let source = r#"
+        c = cache(2)
+        c = put(put(put(c, 1, 10), 2, 20), 1, 11)
+        _ = [len(c), get(c, 1)]
+    "#;
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![Value::Number(2), Value::Number(11)])
+    );
+    let err = run_source(r#"_ = get(cache(2), "nope")"#, None).unwrap_err();
+    assert!(err.to_string().contains("not in the cache"), "{err}");
+    let err = run_source("_ = cache(0)", None).unwrap_err();
+    assert!(err.to_string().contains("must be positive"), "{err}");
+}
+
+#[test]
+fn hash_is_a_stable_fingerprint() {
+    // Equal values fingerprint identically, across separate runs.
+    assert_eq!(run("_ = hash([1, [2, 3]])"), run("_ = hash([1, [2, 3]])"));
+    assert_eq!(run(r#"_ = hash("abc") == hash("abc")"#), Value::Bool(true));
+    assert_eq!(run(r#"_ = hash("abc") == hash("abd")"#), Value::Bool(false));
+    // Packed and general arrays are the same value, so the same hash.
+    assert_eq!(
+        run(r#"_ = hash([1, 2]) == hash(["x", 1, 2][1:3])"#),
+        Value::Bool(true)
+    );
+    // Pinned so a hashing change can't slip by unnoticed: FNV-1a of "xmas".
+    assert_eq!(
+        run(r#"_ = hash("xmas")"#),
+        Value::Number(0x2357_f41a_7b1c_85b0_u64 as i64)
+    );
+}
+
+#[test]
+fn functions_are_lexically_scoped() {
+    // A body resolves free identifiers at its definition site, not through
+    // the caller's parameters.
+    let source = "
+        fn inner() = x
+        fn outer(x) = inner()
+        x = 5
+        _ = outer(99)
+    ";
+    assert_eq!(run(source), Value::Number(5));
+    // Globals defined after the function are still visible: capture is by
+    // scope, not by snapshot.
+    let source = "
+        fn late() = y * 2
+        y = 21
+        _ = late()
+    ";
+    assert_eq!(run(source), Value::Number(42));
+    // Nested calls with colliding parameter names stay separate.
+    let source = "
+        fn g(n) = n + 1
+        fn f(n) = g(n * 10) + n
+        _ = f(3)
+    ";
+    assert_eq!(run(source), Value::Number(34));
+}
+
+#[test]
+fn runaway_recursion_errors_cleanly() {
+    let err = run_source("fn f(n) = f(n + 1)\n_ = f(0)", None).unwrap_err();
+    assert!(err.to_string().contains("stack overflow"), "{err}");
+    // The interpreter stays usable: the failed call left no stray bindings.
+    let source = "
+        fn f(n) = f(n + 1)
+        fn depth2(n) = n
+        _ = depth2(7)
+    ";
+    assert_eq!(run(source), Value::Number(7));
+}
+
+#[test]
+fn deadline_falls_back_when_the_budget_expires() {
+    // A call tree that doubles nine times per level: far too much work for
+    // one millisecond, but only ever 13 frames deep.
+    let slow = "
+        fn w0(n) = (n * 31 + 7) % 9973
+        fn w1(n) = w0(w0(n))
+        fn w2(n) = w1(w1(n))
+        fn w3(n) = w2(w2(n))
+        fn w4(n) = w3(w3(n))
+        fn w5(n) = w4(w4(n))
+        fn w6(n) = w5(w5(n))
+        fn w7(n) = w6(w6(n))
+        fn w8(n) = w7(w7(n))
+        fn w9(n) = w8(w8(n))
+        fn w10(n) = w9(w9(n))
+        fn w11(n) = w10(w10(n))
+        fn w12(n) = w11(w11(n))
+        fn slow() = w12(w12(w12(w12(1))))
+    ";
+    assert_eq!(
+        run(&format!("{slow}\n_ = deadline(1, slow, -1)")),
+        Value::Number(-1)
+    );
+    // A fallback function is called rather than returned.
+    assert_eq!(
+        run(&format!("{slow}\nfn guess() = 42\n_ = deadline(1, slow, guess)")),
+        Value::Number(42)
+    );
+    // A block that finishes in time returns its own result.
+    assert_eq!(
+        run("fn quick() = 6 * 7\n_ = deadline(1000, quick, -1)"),
+        Value::Number(42)
+    );
+    let err = run_source("fn f() = 1\n_ = deadline(-5, f, 0)", None).unwrap_err();
+    assert!(err.to_string().contains("budget"), "{err}");
+}
+
+#[test]
+fn map_literals_index_by_key() {
+    let source = r#"
+        m = {"a": 1, "b": 2}
+        _ = m["a"] + m["b"]
+    "#;
+    assert_eq!(run(source), Value::Number(3));
+    // Any hashable value works as a key.
+    assert_eq!(
+        run(r#"_ = {point(0, 1): "x"}[point(0, 1)]"#),
+        Value::Str("x".into())
+    );
+    let err = run_source(r#"_ = {"a": 1}["z"]"#, None).unwrap_err();
+    assert!(err.to_string().contains("not in the map"), "{err}");
+}
+
+#[test]
+fn map_builtins_update_and_inspect() {
+    let source = r#"
+        m = {"a": 1}
+        m = put(m, "b", 2)
+        m = put(m, "a", 10)
+        m = del(m, "missing")
+        _ = [m["a"], len(m), has(m, "b"), has(m, "z"), len(del(m, "a"))]
+    "#;
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::Number(10),
+            Value::Number(2),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Number(1),
+        ])
+    );
+    // keys() comes out sorted, values() in the same order.
+    assert_eq!(
+        run(r#"_ = keys({"b": 2, "a": 1, "c": 3})"#),
+        Value::Array1D(vec![
+            Value::Str("a".into()),
+            Value::Str("b".into()),
+            Value::Str("c".into()),
+        ])
+    );
+    assert_eq!(
+        run(r#"_ = values({3: "c", 1: "a", 2: "b"})"#),
+        Value::Array1D(vec![
+            Value::Str("a".into()),
+            Value::Str("b".into()),
+            Value::Str("c".into()),
+        ])
+    );
+    // Compound assignment updates an existing entry in place.
+    let source = r#"
+        tally = {"x": 0}
+        tally["x"] += 5
+        _ = tally["x"]
+    "#;
+    assert_eq!(run(source), Value::Number(5));
+}
+
+#[test]
+fn sets_track_membership() {
+    let source = "
+        seen = set([1, 2, 2, 3])
+        seen = add(seen, 4)
+        seen = remove(seen, 1)
+        _ = [len(seen), has(seen, 2), has(seen, 1), contains(seen, 4)]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::Number(3),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Bool(true),
+        ])
+    );
+    // toArray comes out sorted, so set contents are easy to assert on.
+    assert_eq!(
+        run("_ = toArray(set([3, 1, 2, 1]))"),
+        Value::NumArray(vec![1, 2, 3])
+    );
+}
+
+#[test]
+fn set_operations_combine_elementwise() {
+    let source = "
+        a = set([1, 2, 3])
+        b = set([2, 3, 4])
+        _ = [toArray(union(a, b)), toArray(intersect(a, b)), toArray(difference(a, b))]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::NumArray(vec![1, 2, 3, 4]),
+            Value::NumArray(vec![2, 3]),
+            Value::NumArray(vec![1]),
+        ])
+    );
+    let err = run_source("_ = union(set([1]), 2)", None).unwrap_err();
+    assert!(err.to_string().contains("two sets"), "{err}");
+}
+
+#[test]
+fn aliases_abbreviate_repeated_expressions() {
+    let source = "
+        alias inb(i) = i >= 0 && i < 5
+        total = 0
+        for (i in [-2..8]) { if (inb(i)) { total += 1 } }
+        _ = total
+    ";
+    assert_eq!(run(source), Value::Number(5));
+    // An alias parameter only stands in for itself; other names in the body
+    // still refer to the surrounding program.
+    let source = "
+        limit = 3
+        alias capped(x) = min([x, limit])
+        _ = capped(10) + capped(2)
+    ";
+    assert_eq!(run(source), Value::Number(5));
+}
+
+#[test]
+fn runtime_errors_carry_the_statement_line() {
+    let err = run_source("x = 1\n_ = nope", None).unwrap_err();
+    assert_eq!(err.kind, xmas_core::error::ErrorKind::Runtime);
+    assert_eq!(err.span, Some(xmas_core::error::Span { line: 2, col: 1 }));
+    assert_eq!(err.to_string(), "line 2, col 1: undefined variable: nope");
+}
+
+#[test]
+fn match_destructures_array_shapes() {
+    let source = "
+        instr = [\"turn_on\", 1, 2]
+        match (instr) {
+        [\"toggle\", a] => { _ = -a }
+        [\"turn_on\", a, b] => { _ = a + b }
+        }
+    ";
+    assert_eq!(run(source), Value::Number(3));
+}
+
+#[test]
+fn match_rest_pattern_binds_the_remainder() {
+    let source = "
+        match ([1, 2, 3, 4]) {
+        [head, ...tail] => { _ = [head, len(tail)] }
+        }
+    ";
+    assert_eq!(run(source), Value::NumArray(vec![1, 3]));
+    // A bare name is a catch-all that binds the whole subject.
+    let source = "
+        match ([\"x\"]) {
+        [1, 2] => { _ = 0 }
+        whole => { _ = len(whole) }
+        }
+    ";
+    assert_eq!(run(source), Value::Number(1));
+}
+
+#[test]
+fn match_without_a_matching_arm_errors() {
+    let err = run_source("match ([1]) {\n[1, 2] => { _ = 0 }\n}", None).unwrap_err();
+    assert!(err.to_string().contains("no arm matched"), "{err}");
+}
+
+#[test]
+fn input_meta_describes_the_loaded_input() {
+    let source = "
+        m = input.meta
+        _ = [m[\"lines\"], m[\"width\"]]
+    ";
+    assert_eq!(
+        run_source(source, Some("ab\ncdef\n")).unwrap().unwrap(),
+        Value::NumArray(vec![2, 4])
+    );
+    assert_eq!(
+        run_source("_ = input.meta[\"trailingNewline\"]", Some("x"))
+            .unwrap()
+            .unwrap(),
+        Value::Bool(false)
+    );
+    // The file name is empty unless the embedder provides one.
+    assert_eq!(
+        run_source("_ = input.meta[\"file\"]", Some("x"))
+            .unwrap()
+            .unwrap(),
+        Value::Str(String::new())
+    );
+    let err = run_source("_ = input.meta", None).unwrap_err();
+    assert!(err.to_string().contains("no input provided"), "{err}");
+}
+
+#[test]
+fn safe_div_and_get_or_return_defaults() {
+    assert_eq!(run("_ = safeDiv(10, 3, -1)"), Value::Number(3));
+    assert_eq!(run("_ = safeDiv(10, 0, -1)"), Value::Number(-1));
+    assert_eq!(run("_ = getOr([5, 6], 1, 0)"), Value::Number(6));
+    assert_eq!(run("_ = getOr([5, 6], 9, 0)"), Value::Number(0));
+    assert_eq!(
+        run("_ = getOr({\"a\": 1}, \"b\", -1)"),
+        Value::Number(-1)
+    );
+    let err = run_source("_ = safeDiv(\"x\", 1, 0)", None).unwrap_err();
+    assert!(err.to_string().contains("2 numbers"), "{err}");
+}
+
+#[test]
+fn numeric_aggregates_fold_arrays() {
+    assert_eq!(run("_ = sum([1, 2, 3])"), Value::Number(6));
+    assert_eq!(run("_ = sum([])"), Value::Number(0));
+    assert_eq!(run("_ = product([2, 3, 4])"), Value::Number(24));
+    assert_eq!(run("_ = [minOf([3, 1, 2]), maxOf([3, 1, 2])]"), Value::NumArray(vec![1, 3]));
+    let err = run_source("_ = sum([1, \"x\"])", None).unwrap_err();
+    assert!(err.to_string().contains("element 1 is a str"), "{err}");
+    let err = run_source("_ = minOf([])", None).unwrap_err();
+    assert!(err.to_string().contains("empty array"), "{err}");
+}
+
+#[test]
+fn split_breaks_strings_on_delimiters() {
+    assert_eq!(
+        run("_ = split(\"1,2,3\", \",\")"),
+        Value::Array1D(vec![
+            Value::Str("1".into()),
+            Value::Str("2".into()),
+            Value::Str("3".into()),
+        ])
+    );
+    // The one-argument form splits on runs of whitespace.
+    assert_eq!(run("_ = len(split(\" a  b\\nc \"))"), Value::Number(3));
+    // Splitting on a blank line separates input sections.
+    assert_eq!(
+        run("_ = len(split(\"a\\nb\\n\\nc\", \"\\n\\n\"))"),
+        Value::Number(2)
+    );
+    let err = run_source("_ = split(\"ab\", \"\")", None).unwrap_err();
+    assert!(err.to_string().contains("cannot be empty"), "{err}");
+}
+
+#[test]
+fn sort_by_ranks_by_several_keys() {
+    // Primary key ascending, ties broken by the secondary key descending.
+    let source = "
+        fn first(p) = p[0]
+        fn second(p) = p[1]
+        rows = [[1, 2], [0, 9], [1, 7], [0, 3]]
+        _ = sortBy(rows, [first, second], [false, true])
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::NumArray(vec![0, 9]),
+            Value::NumArray(vec![0, 3]),
+            Value::NumArray(vec![1, 7]),
+            Value::NumArray(vec![1, 2]),
+        ])
+    );
+    // A single bool reverses the single-key form.
+    assert_eq!(
+        run("fn id(x) = x\n_ = sortBy([2, 3, 1], id, true)"),
+        Value::NumArray(vec![3, 2, 1])
+    );
+    let err = run_source("fn id(x) = x\n_ = sortBy([1], [id, id], [true])", None).unwrap_err();
+    assert!(err.to_string().contains("2 key function(s) but 1"), "{err}");
+}
+
+#[test]
+fn join_renders_arrays_as_strings() {
+    assert_eq!(
+        run("_ = join([1, 2, 3], \",\")"),
+        Value::Str("1,2,3".into())
+    );
+    assert_eq!(
+        run("_ = join(split(\"a b c\"), \"-\")"),
+        Value::Str("a-b-c".into())
+    );
+    assert_eq!(run("_ = join([], \",\")"), Value::Str(String::new()));
+    let err = run_source("_ = join(\"abc\", \",\")", None).unwrap_err();
+    assert!(err.to_string().contains("expects an array"), "{err}");
+}
+
+#[test]
+fn array_intersect_and_difference_respect_multiplicity() {
+    // Each element of b cancels at most one equal element of a; order
+    // follows a.
+    assert_eq!(
+        run("_ = intersect([1, 2, 2, 3], [2, 2, 4])"),
+        Value::NumArray(vec![2, 2])
+    );
+    assert_eq!(
+        run("_ = intersect([1, 2, 2], [2])"),
+        Value::NumArray(vec![2])
+    );
+    assert_eq!(
+        run("_ = difference([1, 2, 2, 3], [2])"),
+        Value::NumArray(vec![1, 2, 3])
+    );
+    assert_eq!(
+        run("_ = difference([\"a\", \"b\"], [\"b\", \"c\"])"),
+        Value::Array1D(vec![Value::Str("a".into())])
+    );
+}
+
+#[test]
+fn regex_builtins_capture_groups() {
+    let source = "
+        caps = match(\"Game 12: 3 red, 4 blue\", \"Game (\\\\d+)\")
+        _ = ~caps[1]
+    ";
+    assert_eq!(run(source), Value::Number(12));
+    // No match comes back as an empty (falsy) array.
+    assert_eq!(run("_ = len(match(\"abc\", \"\\\\d\"))"), Value::Number(0));
+    let source = "
+        rows = matchAll(\"1 red, 24 blue\", \"(\\\\d+) (\\\\w+)\")
+        _ = [~rows[0][1], ~rows[1][1]]
+    ";
+    assert_eq!(run(source), Value::NumArray(vec![1, 24]));
+    let err = run_source("_ = match(\"a\", \"(a\")", None).unwrap_err();
+    assert!(err.to_string().contains("unclosed ("), "{err}");
+}
+
+#[test]
+fn nums_extracts_every_integer() {
+    assert_eq!(
+        run("_ = nums(\"x=3, y=-14..q7\")"),
+        Value::NumArray(vec![3, -14, 7])
+    );
+    assert_eq!(run("_ = nums(\"no digits\")"), Value::NumArray(vec![]));
+    // A minus with no digit after it is punctuation, not a sign.
+    assert_eq!(run("_ = nums(\"a - b 5\")"), Value::NumArray(vec![5]));
+    assert_eq!(
+        run("_ = nums([\"1\", \"2\", \" \", \"-\", \"3\"])"),
+        Value::NumArray(vec![12, -3])
+    );
+}
+
+#[test]
+fn split_at_and_partition_divide_arrays() {
+    assert_eq!(
+        run("_ = splitAt([1, 2, 3, 4], 1)"),
+        Value::Array1D(vec![
+            Value::NumArray(vec![1]),
+            Value::NumArray(vec![2, 3, 4]),
+        ])
+    );
+    assert_eq!(
+        run("_ = splitAt([1, 2], 9)"),
+        Value::Array1D(vec![Value::NumArray(vec![1, 2]), Value::NumArray(vec![])])
+    );
+    let source = "
+        fn even(n) = n % 2 == 0
+        _ = partition([1, 2, 3, 4, 5], even)
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::NumArray(vec![2, 4]),
+            Value::NumArray(vec![1, 3, 5]),
+        ])
+    );
+}
+
+#[test]
+fn grid_distances_runs_bfs_over_passable_cells() {
+    let source = "
+        g = grid(\"..#\\n.##\\n...\")
+        fn open(cell) = cell != \"#\"
+        _ = gridDistances(g, point(0, 0), open)
+    ";
+    let rows = match run(source) {
+        Value::Array2D(rows) => rows,
+        other => panic!("expected a 2d array, got {other:?}"),
+    };
+    let flat: Vec<i64> = rows
+        .iter()
+        .flatten()
+        .map(|v| match v {
+            Value::Number(n) => *n,
+            other => panic!("expected numbers, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(flat, vec![0, 1, -1, 1, -1, -1, 2, 3, 4]);
+}
+
+#[test]
+fn index_assignment_mutates_in_place() {
+    assert_eq!(run("a = [1, 2, 3]\na[1] = 9\n_ = a"), Value::NumArray(vec![1, 9, 3]));
+    assert_eq!(
+        run("g = fill2d(2, 2, 0)\ng[1][0] = 5\n_ = g[1][0]"),
+        Value::Number(5)
+    );
+    assert_eq!(
+        run("a = [[1], [2]]\na[0][0] = \"x\"\n_ = a[0][0]"),
+        Value::Str("x".into())
+    );
+    let err = run_source("a = [1]\na[3] = 0", None).unwrap_err();
+    assert!(err.to_string().contains("out of bounds"), "{err}");
+}
+
+#[test]
+fn polygon_area_and_perimeter() {
+    // A 3x4 axis-aligned rectangle: area 12, boundary length 14.
+    let source = "
+        pts = [point(0, 0), point(0, 4), point(3, 4), point(3, 0)]
+        _ = [polygonArea(pts), perimeter(pts)]
+    ";
+    assert_eq!(run(source), Value::NumArray(vec![12, 14]));
+    // Pick's theorem: interior = area - perimeter / 2 + 1.
+    let source = "
+        pts = [point(0, 0), point(0, 4), point(3, 4), point(3, 0)]
+        _ = polygonArea(pts) - perimeter(pts) / 2 + 1
+    ";
+    assert_eq!(run(source), Value::Number(6));
+    let err = run_source("_ = polygonArea([point(0, 0), 1])", None).unwrap_err();
+    assert!(err.to_string().contains("expected a point"), "{err}");
+}
+
+#[test]
+fn segment_geometry_builtins() {
+    assert_eq!(
+        run("_ = segmentsIntersect(point(0, 0), point(4, 4), point(0, 4), point(4, 0))"),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        run("_ = segmentsIntersect(point(0, 0), point(1, 1), point(3, 3), point(4, 4))"),
+        Value::Bool(false)
+    );
+    // Touching at an endpoint counts.
+    assert_eq!(
+        run("_ = segmentsIntersect(point(0, 0), point(2, 2), point(2, 2), point(5, 2))"),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        run("_ = pointsOnLine(point(1, 1), point(3, 3))"),
+        Value::Array1D(vec![
+            Value::Point(1, 1),
+            Value::Point(2, 2),
+            Value::Point(3, 3),
+        ])
+    );
+    assert_eq!(
+        run("_ = pointsOnLine(point(0, 9), point(0, 7))"),
+        Value::Array1D(vec![
+            Value::Point(0, 9),
+            Value::Point(0, 8),
+            Value::Point(0, 7),
+        ])
+    );
+}
+
+#[test]
+fn block_function_bodies_bind_locally() {
+    let source = "
+        x = 10
+        fn f(n) {
+            x = n * 2
+            _ = x + 1
+        }
+        y = f(5)
+        _ = [x, y]
+    ";
+    assert_eq!(run(source), Value::NumArray(vec![10, 11]));
+    let err = run_source("fn f() { y = 1 }\n_ = f()", None).unwrap_err();
+    assert!(err.to_string().contains("must assign _"), "{err}");
+}
+
+#[test]
+fn global_keyword_escapes_the_function_scope() {
+    let source = "
+        counter = 0
+        fn bump() {
+            global counter = counter + 1
+            _ = counter
+        }
+        bump()
+        bump()
+        _ = counter
+    ";
+    assert_eq!(run(source), Value::Number(2));
+}
+
+#[test]
+fn point3_arithmetic_and_rotations() {
+    assert_eq!(
+        run("_ = point3(1, 2, 3) + point3(10, 20, 30)"),
+        Value::Point3(11, 22, 33)
+    );
+    assert_eq!(
+        run("_ = point3(4, 5, 6) - point3(1, 1, 1)"),
+        Value::Point3(3, 4, 5)
+    );
+    assert_eq!(run("_ = point3(1, 2, 3) * 2"), Value::Point3(2, 4, 6));
+    assert_eq!(run("_ = point3(7, 8, 9)[2]"), Value::Number(9));
+    assert_eq!(run("_ = rotate3(point3(1, 2, 3), 0)"), Value::Point3(1, 2, 3));
+    // All 24 orientations of an asymmetric point are distinct.
+    assert_eq!(
+        run("_ = len(set(rotations3(point3(1, 2, 3))))"),
+        Value::Number(24)
+    );
+    let err = run_source("_ = rotate3(point3(0, 0, 0), 24)", None).unwrap_err();
+    assert!(err.to_string().contains("not in [0..24]"), "{err}");
+}
+
+#[test]
+fn unlabeled_break_and_continue() {
+    let source = "
+        total = 0
+        for (n in [1..100]) {
+            if (n == 5) { break }
+            total += n
+        }
+        _ = total
+    ";
+    assert_eq!(run(source), Value::Number(10));
+    // break exits only the innermost loop.
+    let source = "
+        count = 0
+        for (r in [0..3]) {
+            for (c in [0..10]) {
+                if (c == 1) { break }
+                count += 1
+            }
+        }
+        _ = count
+    ";
+    assert_eq!(run(source), Value::Number(3));
+    let source = "
+        total = 0
+        for (n in [1..10]) {
+            if (n % 2 == 0) { continue }
+            total += n
+        }
+        _ = total
+    ";
+    assert_eq!(run(source), Value::Number(25));
+    let err = run_source("break", None).unwrap_err();
+    assert!(err.to_string().contains("break outside of a loop"), "{err}");
+    let err = run_source("continue", None).unwrap_err();
+    assert!(err.to_string().contains("continue outside of a loop"), "{err}");
+}
+
+#[test]
+fn return_unwinds_the_function_body() {
+    let source = "
+        fn classify(n) {
+            if (n < 0) { return \"negative\" }
+            if (n == 0) { return \"zero\" }
+            _ = \"positive\"
+        }
+        _ = [classify(0 - 5), classify(0), classify(3)]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::Str("negative".into()),
+            Value::Str("zero".into()),
+            Value::Str("positive".into()),
+        ])
+    );
+    // return unwinds out of loops inside the body.
+    let source = "
+        fn firstEven(arr) {
+            for (n in arr) {
+                if (n % 2 == 0) { return n }
+            }
+            _ = 0 - 1
+        }
+        _ = firstEven([3, 7, 8, 9])
+    ";
+    assert_eq!(run(source), Value::Number(8));
+    let err = run_source("return 1", None).unwrap_err();
+    assert!(err.to_string().contains("return outside of a function"), "{err}");
+}
+
+#[test]
+fn range_set_tracks_interval_coverage() {
+    let source = "
+        rs = rangeSet()
+        rs = add(rs, [0..10])
+        rs = add(rs, [20..30])
+        rs = add(rs, [5..25])
+        _ = covered(rs)
+    ";
+    assert_eq!(run(source), Value::Number(30));
+    let source = "
+        rs = add(add(rangeSet(), [0..1000000000]), [2000000000..2000000005])
+        rs = remove(rs, [10..20])
+        _ = [covered(rs), len(spans(rs))]
+    ";
+    assert_eq!(run(source), Value::NumArray(vec![999999995, 3]));
+    assert_eq!(
+        run("_ = has(add(rangeSet(), [3..6]), 5)"),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        run("_ = has(add(rangeSet(), [3..6]), 6)"),
+        Value::Bool(false)
+    );
+}
+
+#[test]
+fn eval_runs_expressions_in_the_current_state() {
+    assert_eq!(run("_ = eval(\"2 + 3 * 4\")"), Value::Number(14));
+    // The evaluated expression sees program variables and functions.
+    let source = "
+        x = 10
+        fn double(n) = n * 2
+        _ = eval(\"double(x) + 1\")
+    ";
+    assert_eq!(run(source), Value::Number(21));
+    let err = run_source("_ = eval(\"1 +\")", None).unwrap_err();
+    assert!(err.to_string().contains("eval:"), "{err}");
+    let err = run_source("_ = eval(\"1 2\")", None).unwrap_err();
+    assert!(err.to_string().contains("single expression"), "{err}");
+}
+
+#[test]
+fn packed_grids_behave_like_2d_arrays() {
+    let source = "
+        g = grid(\"ab\\ncd\")
+        _ = [g[1][0], g[point(0, 1)], $len(g)]
+    ";
+    assert_eq!(
+        run(source),
+        Value::Array1D(vec![
+            Value::Str("c".into()),
+            Value::Str("b".into()),
+            Value::Str("2".into()),
+        ])
+    );
+    // Dimension helpers and transpose.
+    assert_eq!(
+        run("g = grid(\"abc\\ndef\")\n_ = [rows(g), cols(g)]"),
+        Value::NumArray(vec![2, 3])
+    );
+    assert_eq!(
+        run("_ = render(transpose(grid(\"abc\\ndef\")))"),
+        Value::Str("ad\nbe\ncf".into())
+    );
+    assert_eq!(
+        run("_ = inBounds(grid(\"ab\\ncd\"), point(1, 1))"),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        run("_ = inBounds(grid(\"ab\\ncd\"), 2, 0)"),
+        Value::Bool(false)
+    );
+    // Writing a non-char value falls back to the general form.
+    assert_eq!(
+        run("g = grid(\"ab\\ncd\")\ng[0][0] = 9\n_ = g[0]"),
+        Value::Array1D(vec![Value::Number(9), Value::Str("b".into())])
+    );
+}
+
+#[test]
+fn eval_with_precedence_follows_the_map() {
+    // Same level: strictly left to right.
+    assert_eq!(
+        run("_ = evalWithPrecedence(\"1 + 2 * 3 + 4 * 5\", {\"+\": 1, \"*\": 1})"),
+        Value::Number(65)
+    );
+    // Addition binding tighter than multiplication.
+    assert_eq!(
+        run("_ = evalWithPrecedence(\"2 * 3 + (4 * 5)\", {\"+\": 2, \"*\": 1})"),
+        Value::Number(46)
+    );
+    let err = run_source("_ = evalWithPrecedence(\"1 & 2\", {\"+\": 1})", None).unwrap_err();
+    assert!(err.to_string().contains("unexpected '&'"), "{err}");
+}
+
+#[test]
+fn neighbors4_and_neighbors8_respect_the_bounds() {
+    assert_eq!(
+        run("_ = len(neighbors4(grid(\"ab\\ncd\"), point(0, 0)))"),
+        Value::Number(2)
+    );
+    assert_eq!(
+        run("_ = len(neighbors8(grid(\"abc\\ndef\\nghi\"), 1, 1))"),
+        Value::Number(8)
+    );
+    // Dimensions instead of a grid, corner cell.
+    assert_eq!(
+        run("_ = len(neighbors8(point(10, 10), point(9, 9)))"),
+        Value::Number(3)
+    );
+}
+
+#[test]
+fn bfs_searches_through_callback_neighbors() {
+    let src = "g = grid(\"S..\\n.#.\\n..E\")
+        fn open(p) = inBounds(g, p) && g[p[0]][p[1]] != \"#\"
+        fn step(p) = filter(neighbors4(g, p), open)
+        fn done(p) = g[p[0]][p[1]] == \"E\"
+        _ = bfs(point(0, 0), step, done)";
+    assert_eq!(run(src), Value::Number(4));
+    let path = "fn step(n) = [n * 2, n + 3]
+        fn done(n) = n == 11
+        _ = bfsPath(2, step, done)";
+    assert_eq!(
+        run(path),
+        Value::NumArray(vec![2, 4, 8, 11])
+    );
+    // No route at all comes back as -1 rather than an error.
+    assert_eq!(
+        run("fn stuck(n) = []\n fn five(n) = n == 5\n _ = bfs(0, stuck, five)"),
+        Value::Number(-1)
+    );
+}
+
+#[test]
+fn warnings_surface_silent_data_issues() {
+    use xmas_core::interpreter::Interpreter;
+    use xmas_core::{lexer, parser};
+
+    let source = "arr = [1, 2, 3]\na = arr[0:99]\nn = ~\"007\"\ng = grid(\"ab\\nxyz\")\n_ = 1";
+    let program = parser::parse(lexer::lex(source).unwrap(), source).unwrap();
+    let mut interp = Interpreter::new();
+    interp.run(&program).unwrap();
+    let warnings = interp.warnings();
+    assert!(
+        warnings.iter().any(|w| w == "slice [0:99] was clamped to fit"),
+        "{warnings:?}"
+    );
+    assert!(
+        warnings.iter().any(|w| w == "~ read \"007\" as 7"),
+        "{warnings:?}"
+    );
+    assert!(
+        warnings
+            .iter()
+            .any(|w| w == "grid rows have differing widths (2 to 3)"),
+        "{warnings:?}"
+    );
+    // A clean program reports nothing.
+    let mut interp = Interpreter::new();
+    let source = "_ = ~\"7\"";
+    let program = parser::parse(lexer::lex(source).unwrap(), source).unwrap();
+    interp.run(&program).unwrap();
+    assert!(interp.warnings().is_empty());
+}
+
+#[test]
+fn strict_slices_error_instead_of_clamping() {
+    assert_eq!(
+        run("strictSlices()\n _ = [1, 2, 3][1:3]"),
+        Value::NumArray(vec![2, 3])
+    );
+    let err = run_source("strictSlices()\n _ = [1, 2, 3][0:99]", None).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("slice [0:99] is out of range for length 3"),
+        "{err}"
+    );
+    let err = run_source("strictSlices()\n _ = \"hello\"[-9:]", None).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("slice [-9:] is out of range for length 5"),
+        "{err}"
+    );
+}
+
+#[test]
+fn strings_behave_like_char_arrays() {
+    // Concatenation reads arrays as the chars they spell, and vice versa.
+    assert_eq!(
+        run("_ = \"ab\" + [\"c\", \"d\"]"),
+        Value::Str("abcd".into())
+    );
+    assert_eq!(
+        run("_ = [\"a\"] + \"bc\" == [\"a\", \"b\", \"c\"]"),
+        Value::Bool(true)
+    );
+    // `~` parses whatever the value spells, string or array.
+    assert_eq!(run("_ = ~\"42\""), Value::Number(42));
+    assert_eq!(run("_ = ~[\"4\", \"2\"]"), Value::Number(42));
+    assert_eq!(run("_ = ~[4, 2]"), Value::Number(42));
+    // Indexing, slicing and len already agree between the two.
+    assert_eq!(run("_ = \"hello\"[1] + \"hello\"[1:3]"), Value::Str("eel".into()));
+}
+
+#[test]
+fn booleans_compare_on_the_number_line() {
+    assert_eq!(run("_ = true > false"), Value::Bool(true));
+    assert_eq!(run("_ = 5 > true"), Value::Bool(true));
+    assert_eq!(run("_ = false <= 0"), Value::Bool(true));
+    assert_eq!(run("_ = 1 == true"), Value::Bool(true));
+    // Strings stay out of the numeric column.
+    let err = run_source("_ = \"a\" < true", None).unwrap_err();
+    assert!(
+        err.to_string().contains("cannot compare string with bool"),
+        "{err}"
+    );
+}
+
+#[test]
+fn pipe_threads_the_value_into_the_call() {
+    assert_eq!(
+        run("fn double(n) = n * 2\n _ = 3 |> double |> max(10)"),
+        Value::Number(10)
+    );
+    assert_eq!(
+        run("_ = [3, 1, 2] |> sort |> min"),
+        Value::Number(1)
+    );
+    let err = run_source("_ = 1 |> 2 + 3", None).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("the right side of |> must be a function name or call"),
+        "{err}"
+    );
+}
+
+#[test]
+fn pipeline_placeholder_names_the_incoming_value() {
+    assert_eq!(
+        run("fn num(s) = ~s\n _ = \"1,2,3\" |> split(_, \",\") |> map(_, num) |> sum"),
+        Value::Number(6)
+    );
+    // The placeholder can sit anywhere in the argument list, more than once.
+    assert_eq!(run("_ = 3 |> max(10, _) |> min(_, _)"), Value::Number(10));
+    // Inside a called function `_` is the callee's own, not the stage's.
+    let src = "fn total(arr) {\n _ = sum(arr)\n }\n _ = [1, 2] |> total(_)";
+    assert_eq!(run(src), Value::Number(3));
+}
+
+#[test]
+fn aggregates_accumulate_beyond_the_i64_range() {
+    // The running total may leave the i64 range as long as it comes back.
+    let big = i64::MAX;
+    assert_eq!(
+        run(&format!("_ = sum([{big}, {big}, -{big}, -{big}])")),
+        Value::Number(0)
+    );
+    let err = run_source(&format!("_ = sum([{big}, {big}])"), None).unwrap_err();
+    assert!(
+        err.to_string().contains("does not fit in a number"),
+        "{err}"
+    );
+    let err = run_source(&format!("_ = product([{big}, {big}])"), None).unwrap_err();
+    assert!(
+        err.to_string().contains("does not fit in a number"),
+        "{err}"
+    );
+}